@@ -0,0 +1,43 @@
+//! # btc-dev-utils
+//!
+//! Helpers for driving a Bitcoin Core node during development: wallet management,
+//! multisig setup, coin selection, PSBT workflows and transaction verification.
+//!
+//! Every operation takes the node [`Settings`] (or a [`Context`] wrapping them) and
+//! returns a typed result, so the output of one step can be fed straight into the next:
+//!
+//! ```no_run
+//! use std::str::FromStr;
+//!
+//! use bitcoin::{Address, Amount, FeeRate};
+//! use btc_dev_utils::{Context, Settings, TxLayout, UTXOStrategy};
+//! use btc_dev_utils::modules::wallet_ops::{create_psbt, process_psbt};
+//!
+//! let ctx = Context::new(Settings::default());
+//! let recipient = Address::from_str("bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080")
+//!     .unwrap()
+//!     .assume_checked();
+//!
+//! let psbt = create_psbt(
+//!     "multisig_wallet",
+//!     &recipient,
+//!     Amount::from_btc(1.0).unwrap(),
+//!     FeeRate::from_sat_per_vb(2).unwrap(),
+//!     &UTXOStrategy::LargestFirst.into(),
+//!     &TxLayout::default(),
+//!     ctx.settings(),
+//! ).unwrap();
+//!
+//! let signed = process_psbt("signer_1", &psbt.psbt, ctx.settings()).unwrap();
+//! assert!(!signed.psbt.is_empty());
+//! ```
+
+pub mod modules;
+pub mod settings;
+pub mod utils;
+
+pub use modules::context::Context;
+pub use modules::verification::verify_signed_tx;
+pub use modules::wallet::Wallet;
+pub use settings::Settings;
+pub use utils::utils::{strat_handler, TxLayout, UTXOStrategy};
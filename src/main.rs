@@ -1,13 +1,12 @@
 use std::{error::Error, fmt};
 
-use log::{error, info};
+use log::{error, info, warn};
 
 use clap::Parser;
 
-use modules::client::{
-    analyze_psbt,
+use btc_dev_utils::modules::client::{
     broadcast_tx_wrapper,
-    combine_psbts, decode_psbt,
+    combine_psbts,
     decode_raw_tx,
     finalize_psbt,
     finalize_psbt_and_broadcast,
@@ -17,36 +16,54 @@ use modules::client::{
     get_tx_wrapper,
     rescan_blockchain
 };
-use modules::errors::SettingsError;
-use modules::wallet_ops::{
+use btc_dev_utils::modules::errors::{CliError, OutputError, SettingsError};
+use btc_dev_utils::modules::fee_bump::{bump_fee, cpfp};
+use btc_dev_utils::modules::payouts::load_payouts;
+use btc_dev_utils::modules::policy::{broadcast_checked, check_policy};
+use btc_dev_utils::modules::psbt_analysis::{analyze_psbt, decode_psbt};
+use btc_dev_utils::modules::wallet::SendOptions;
+use btc_dev_utils::modules::wallet_ops::{
+    create_batch_psbt,
     create_psbt,
     get_address_info,
     derive_addresses,
+    export_multisig_wallet,
     get_balances,
     get_new_address,
     get_wallet_info,
+    import_multisig_wallet,
     list_descriptors_wrapper,
+    list_locked_utxos,
     list_unspent,
+    lock_utxos,
     mine_blocks_wrapper,
+    multisig_spend,
+    new_multisig_from_xpubs,
     new_multisig_wallet,
     new_wallet,
     process_psbt,
+    release_tx_inputs,
     send_btc,
-    sign_tx_wrapper
+    sign_batch_tx_wrapper,
+    sign_tx_wrapper,
+    unlock_utxos
 };
-use modules::verification::verify_signed_tx;
+use btc_dev_utils::modules::rpcauth::generate_rpcauth;
+use btc_dev_utils::modules::simulation::{load_pool, simulate_coin_selection, synthetic_pool, AmountRange, SimulationConfig};
+use btc_dev_utils::modules::script_debug::{debug_script, debug_script_offline};
+use btc_dev_utils::modules::verification::{input_report, input_report_offline, verify_signed_tx, verify_signed_tx_offline};
 
-use settings::Settings;
+use btc_dev_utils::settings::Settings;
 
-use utils::cli::{Cli, Action};
-
-mod settings;
-mod modules;
-mod utils;
+use btc_dev_utils::utils::cli::{require_network, Cli, Action};
+use btc_dev_utils::utils::output::print_output;
+use btc_dev_utils::utils::utils::UTXOStrategy;
 
 #[derive(Debug)]
 enum AppError {
     SettingsError(SettingsError),
+    CliError(CliError),
+    OutputError(OutputError),
     Other(Box<dyn Error>),
 }
 
@@ -56,6 +73,8 @@ impl fmt::Display for AppError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             AppError::SettingsError(err) => write!(f, "Settings error: {}", err),
+            AppError::CliError(err) => write!(f, "Invalid arguments: {}", err),
+            AppError::OutputError(err) => write!(f, "Output error: {}", err),
             AppError::Other(err) => write!(f, "Other error: {}", err),
         }
     }
@@ -67,6 +86,18 @@ impl From<SettingsError> for AppError {
     }
 }
 
+impl From<CliError> for AppError {
+    fn from(err: CliError) -> Self {
+        AppError::CliError(err)
+    }
+}
+
+impl From<OutputError> for AppError {
+    fn from(err: OutputError) -> Self {
+        AppError::OutputError(err)
+    }
+}
+
 impl From<Box<dyn Error>> for AppError {
     fn from(err: Box<dyn Error>) -> Self {
         AppError::Other(err)
@@ -92,35 +123,169 @@ fn main() -> Result<(), AppError> {
         }
     };
 
-    match args.action {
-        Action::GetBlockHeight => get_block_height(&settings).map_err(|e| Box::new(e) as Box<dyn Error>)?,
-        Action::NewWallet => new_wallet(&args.wallet_name, &settings).map_err(|e| Box::new(e) as Box<dyn Error>)?,
-        Action::GetWalletInfo => get_wallet_info(&args.wallet_name, &settings).map_err(|e| Box::new(e) as Box<dyn Error>)?,
-        Action::ListDescriptors => list_descriptors_wrapper(&args.wallet_name, &settings).map_err(|e| Box::new(e) as Box<dyn Error>)?,
-        Action::NewMultisig => new_multisig_wallet(args.nrequired, &args.wallet_names, &args.multisig_name, &settings).map_err(|e| Box::new(e) as Box<dyn Error>)?,
-        Action::GetNewAddress => get_new_address(&args.wallet_name, &args.address_type, &settings).map_err(|e| Box::new(e) as Box<dyn Error>)?,
-        Action::GetAddressInfo => get_address_info(&args.wallet_name, &args.address, &settings).map_err(|e| Box::new(e) as Box<dyn Error>)?,
-        Action::DeriveAddresses => derive_addresses(&args.descriptor, &args.start, &args.end, &settings).map_err(|e| Box::new(e) as Box<dyn Error>)?,
-        Action::RescanBlockchain => rescan_blockchain(&settings).map_err(|e| Box::new(e) as Box<dyn Error>)?,
-        Action::GetBalance => get_balances(&args.wallet_name, &settings).map_err(|e| Box::new(e) as Box<dyn Error>)?,
-        Action::GetSpendableBalance => get_spendable_balance(&args.address, &settings).map_err(|e| Box::new(e) as Box<dyn Error>)?,
-        Action::MineBlocks => mine_blocks_wrapper(&args.wallet_name, args.blocks, &settings).map_err(|e| Box::new(e) as Box<dyn Error>)?,
-        Action::ListUnspent => list_unspent(&args.wallet_name, &settings).map_err(|e| Box::new(e) as Box<dyn Error>)?,
-        Action::GetTx => get_tx_wrapper(&args.txid, &settings).map_err(|e| Box::new(e) as Box<dyn Error>)?,
-        Action::GetTxOut => get_tx_out_wrapper(&args.txid, args.vout, Some(args.confirmations), &settings).map_err(|e| Box::new(e) as Box<dyn Error>)?,
-        Action::SignTx => sign_tx_wrapper(&args.wallet_name, &args.recipient, args.amount, args.fee_amount, args.utxo_strat, &settings).map_err(|e| Box::new(e) as Box<dyn Error>)?,
-        Action::DecodeRawTx => decode_raw_tx(&args.tx_hex, &settings).map_err(|e| Box::new(e) as Box<dyn Error>)?,
-        Action::BroadcastTx => broadcast_tx_wrapper(&args.tx_hex, args.max_fee_rate, &settings).map_err(|e| Box::new(e) as Box<dyn Error>)?,
-        Action::SendBtc => send_btc(&args.wallet_name, &args.recipient, args.amount, &settings).map_err(|e| Box::new(e) as Box<dyn Error>)?,
-        Action::CreatePsbt => create_psbt(&args.wallet_name, &args.recipient, args.amount, args.fee_amount, args.utxo_strat, &settings).map_err(|e| Box::new(e) as Box<dyn Error>)?,
-        Action::DecodePsbt => decode_psbt(&args.psbt_hex, &settings).map_err(|e| Box::new(e) as Box<dyn Error>)?,
-        Action::AnalyzePsbt => analyze_psbt(&args.psbt_hex, &settings).map_err(|e| Box::new(e) as Box<dyn Error>)?,
-        Action::WalletProcessPsbt => process_psbt(&args.wallet_name, &args.psbt_hex, &settings).map_err(|e| Box::new(e) as Box<dyn Error>)?,
-        Action::CombinePsbts => combine_psbts(&args.psbts, &settings).map_err(|e| Box::new(e) as Box<dyn Error>)?,
-        Action::FinalizePsbt => finalize_psbt(&args.psbt_hex, &settings).map_err(|e| Box::new(e) as Box<dyn Error>)?,
-        Action::FinalizePsbtAndBroadcast => finalize_psbt_and_broadcast(&args.psbt_hex, &settings).map_err(|e| Box::new(e) as Box<dyn Error>)?,
-        Action::VerifySignedTx => verify_signed_tx(&args.tx_hex, &settings).map_err(|e| Box::new(e) as Box<dyn Error>)?,
+    let settings = match &args.profile {
+        Some(profile) => settings.with_profile(profile).map_err(|err| {
+            error!("Error applying profile {}: {}", profile, err);
+            AppError::from(err)
+        })?,
+        None => settings,
+    };
+
+    let network = settings.network;
+    let result = match args.action {
+        Action::GetBlockHeight => print_output(&get_block_height(&settings).map_err(|e| Box::new(e) as Box<dyn Error>)?, &args.output),
+        Action::NewWallet(a) => print_output(&new_wallet(&a.wallet_name, &settings).map_err(|e| Box::new(e) as Box<dyn Error>)?, &args.output),
+        Action::GetWalletInfo(a) => print_output(&get_wallet_info(&a.wallet_name, &settings).map_err(|e| Box::new(e) as Box<dyn Error>)?, &args.output),
+        Action::ListDescriptors(a) => print_output(&list_descriptors_wrapper(&a.wallet_name, &settings).map_err(|e| Box::new(e) as Box<dyn Error>)?, &args.output),
+        Action::NewMultisig(a) => print_output(&new_multisig_wallet(a.nrequired, &a.wallet_names, &a.multisig_name, a.script_type, &settings).map_err(|e| Box::new(e) as Box<dyn Error>)?, &args.output),
+        Action::NewMultisigFromXpubs(a) => print_output(&new_multisig_from_xpubs(a.nrequired, &a.xpubs, &a.multisig_name, a.script_type, &settings).map_err(|e| Box::new(e) as Box<dyn Error>)?, &args.output),
+        Action::ExportMultisig(a) => print_output(&export_multisig_wallet(&a.wallet_name, a.format, &a.file, &settings).map_err(|e| Box::new(e) as Box<dyn Error>)?, &args.output),
+        Action::ImportMultisig(a) => print_output(&import_multisig_wallet(&a.file, a.multisig_name.as_deref(), &settings).map_err(|e| Box::new(e) as Box<dyn Error>)?, &args.output),
+        Action::GetNewAddress(a) => print_output(&get_new_address(&a.wallet_name, &a.address_type, &settings).map_err(|e| Box::new(e) as Box<dyn Error>)?, &args.output),
+        Action::GetAddressInfo(a) => {
+            let address = require_network(&a.address, network)?;
+            print_output(&get_address_info(&a.wallet_name, &address, &settings).map_err(|e| Box::new(e) as Box<dyn Error>)?, &args.output)
+        }
+        Action::DeriveAddresses(a) => {
+            if a.start > a.end {
+                return Err(CliError::InvalidRange(a.start, a.end).into());
+            }
+            print_output(&derive_addresses(&a.descriptor, &a.start, &a.end, &settings).map_err(|e| Box::new(e) as Box<dyn Error>)?, &args.output)
+        }
+        Action::RescanBlockchain => print_output(&rescan_blockchain(&settings).map_err(|e| Box::new(e) as Box<dyn Error>)?, &args.output),
+        Action::GetBalance(a) => print_output(&get_balances(&a.wallet_name, &settings).map_err(|e| Box::new(e) as Box<dyn Error>)?, &args.output),
+        Action::GetSpendableBalance(a) => {
+            let address = require_network(&a.address, network)?;
+            print_output(&get_spendable_balance(&address, &settings).map_err(|e| Box::new(e) as Box<dyn Error>)?, &args.output)
+        }
+        Action::MineBlocks(a) => print_output(&mine_blocks_wrapper(&a.wallet_name, a.blocks, &settings).map_err(|e| Box::new(e) as Box<dyn Error>)?, &args.output),
+        Action::ListUnspent(a) => print_output(&list_unspent(&a.wallet_name, &settings).map_err(|e| Box::new(e) as Box<dyn Error>)?, &args.output),
+        Action::LockUtxos(a) => print_output(&lock_utxos(&a.wallet_name, &a.utxos, a.persistent, &settings).map_err(|e| Box::new(e) as Box<dyn Error>)?, &args.output),
+        Action::UnlockUtxos(a) => {
+            let released = match &a.tx_hex {
+                Some(tx_hex) => release_tx_inputs(&a.wallet_name, tx_hex, &settings),
+                None => unlock_utxos(&a.wallet_name, &a.utxos, &settings),
+            };
+            print_output(&released.map_err(|e| Box::new(e) as Box<dyn Error>)?, &args.output)
+        }
+        Action::ListLockedUtxos(a) => print_output(&list_locked_utxos(&a.wallet_name, &settings).map_err(|e| Box::new(e) as Box<dyn Error>)?, &args.output),
+        Action::GetTx(a) => print_output(&get_tx_wrapper(&a.txid.to_string(), &settings).map_err(|e| Box::new(e) as Box<dyn Error>)?, &args.output),
+        Action::GetTxOut(a) => print_output(&get_tx_out_wrapper(&a.txid.to_string(), a.vout, Some(a.confirmations), &settings).map_err(|e| Box::new(e) as Box<dyn Error>)?, &args.output),
+        Action::SignTx(a) => {
+            let recipient = require_network(&a.recipient, network)?;
+            print_output(&sign_tx_wrapper(&a.wallet_name, &recipient, a.amount, a.fee_rate, &a.coins.coin_control(), &a.layout.layout(), &settings).map_err(|e| Box::new(e) as Box<dyn Error>)?, &args.output)
+        }
+        Action::SignBatchTx(a) => {
+            let payouts = load_payouts(&a.payout_file, network).map_err(|e| Box::new(e) as Box<dyn Error>)?;
+            print_output(&sign_batch_tx_wrapper(&a.wallet_name, &payouts, a.fee_rate, &a.coins.coin_control(), &a.layout.layout(), &settings).map_err(|e| Box::new(e) as Box<dyn Error>)?, &args.output)
+        }
+        Action::DecodeRawTx(a) => print_output(&decode_raw_tx(&a.tx_hex, &settings).map_err(|e| Box::new(e) as Box<dyn Error>)?, &args.output),
+        Action::BroadcastTx(a) => {
+            let broadcast = match a.preflight {
+                true => broadcast_checked(&a.tx_hex, a.max_fee_rate, &settings).map_err(|e| Box::new(e) as Box<dyn Error>),
+                false => broadcast_tx_wrapper(&a.tx_hex, a.max_fee_rate, &settings).map_err(|e| Box::new(e) as Box<dyn Error>),
+            };
+            // a rejected transaction must not leave its inputs locked either, but a failed
+            // release only warns so it cannot hide the broadcast result
+            if let Some(wallet_name) = &a.wallet_name {
+                if let Err(err) = release_tx_inputs(wallet_name, &a.tx_hex, &settings) {
+                    warn!("Could not release the locks on the transaction's inputs: {}", err);
+                }
+            }
+            print_output(&broadcast?, &args.output)
+        }
+        Action::CheckPolicy(a) => {
+            let report = check_policy(&a.tx_hex, a.max_fee_rate, &settings).map_err(|e| Box::new(e) as Box<dyn Error>)?;
+            match a.issues {
+                true => print_output(&report.issues, &args.output),
+                false => print_output(&report, &args.output),
+            }
+        }
+        Action::SendBtc(a) => {
+            let recipient = require_network(&a.recipient, network)?;
+            let options = SendOptions {
+                fee_rate: a.fee_rate,
+                conf_target: a.conf_target,
+                estimate_mode: a.estimate_mode,
+                subtract_fee: a.subtract_fee,
+                replaceable: a.replaceable,
+                change_address: a.change_address.as_ref().map(|address| require_network(address, network)).transpose()?,
+                change_type: a.change_type,
+                locktime: a.locktime,
+                add_to_wallet: !a.no_broadcast,
+            };
+            print_output(&send_btc(&a.wallet_name, &recipient, a.amount, &options, &settings).map_err(|e| Box::new(e) as Box<dyn Error>)?, &args.output)
+        }
+        Action::CreatePsbt(a) => {
+            let recipient = require_network(&a.recipient, network)?;
+            print_output(&create_psbt(&a.wallet_name, &recipient, a.amount, a.fee_rate, &a.coins.coin_control(), &a.layout.layout(), &settings).map_err(|e| Box::new(e) as Box<dyn Error>)?, &args.output)
+        }
+        Action::CreateBatchPsbt(a) => {
+            let payouts = load_payouts(&a.payout_file, network).map_err(|e| Box::new(e) as Box<dyn Error>)?;
+            print_output(&create_batch_psbt(&a.wallet_name, &payouts, a.fee_rate, &a.coins.coin_control(), &a.layout.layout(), &settings).map_err(|e| Box::new(e) as Box<dyn Error>)?, &args.output)
+        }
+        Action::MultisigSpend(a) => {
+            let recipient = require_network(&a.spend.recipient, network)?;
+            print_output(&multisig_spend(&a.spend.wallet_name, &a.cosigners, &recipient, a.spend.amount, a.spend.fee_rate, &a.spend.coins.coin_control(), &a.spend.layout.layout(), &settings).map_err(|e| Box::new(e) as Box<dyn Error>)?, &args.output)
+        }
+        Action::BumpFee(a) => print_output(&bump_fee(&a.wallet_name, &a.txid, a.fee_rate, a.method, &settings).map_err(|e| Box::new(e) as Box<dyn Error>)?, &args.output),
+        Action::Cpfp(a) => print_output(&cpfp(&a.wallet_name, &a.txid, a.vout, a.fee_rate, a.submit_package, &settings).map_err(|e| Box::new(e) as Box<dyn Error>)?, &args.output),
+        Action::DecodePsbt(a) => print_output(&decode_psbt(&a.psbt_hex, settings.network).map_err(|e| Box::new(e) as Box<dyn Error>)?, &args.output),
+        Action::AnalyzePsbt(a) => print_output(&analyze_psbt(&a.psbt_hex, settings.network).map_err(|e| Box::new(e) as Box<dyn Error>)?, &args.output),
+        Action::WalletProcessPsbt(a) => print_output(&process_psbt(&a.wallet_name, &a.psbt_hex, &settings).map_err(|e| Box::new(e) as Box<dyn Error>)?, &args.output),
+        Action::CombinePsbts(a) => print_output(&combine_psbts(&a.psbts, &settings).map_err(|e| Box::new(e) as Box<dyn Error>)?, &args.output),
+        Action::FinalizePsbt(a) => print_output(&finalize_psbt(&a.psbt_hex, &settings).map_err(|e| Box::new(e) as Box<dyn Error>)?, &args.output),
+        Action::FinalizePsbtAndBroadcast(a) => print_output(&finalize_psbt_and_broadcast(&a.psbt_hex, &settings).map_err(|e| Box::new(e) as Box<dyn Error>)?, &args.output),
+        Action::VerifySignedTx(a) if a.report => {
+            let report = match a.prevouts.prevout_source() {
+                Some(prevouts) => input_report_offline(&a.tx_hex, &prevouts),
+                None => input_report(&a.tx_hex, &settings),
+            };
+            print_output(&report.map_err(|e| Box::new(e) as Box<dyn Error>)?, &args.output)
+        }
+        Action::VerifySignedTx(a) => {
+            let verified = match a.prevouts.prevout_source() {
+                Some(prevouts) => verify_signed_tx_offline(&a.tx_hex, &prevouts),
+                None => verify_signed_tx(&a.tx_hex, &settings),
+            };
+            print_output(&verified.map_err(|e| Box::new(e) as Box<dyn Error>)?, &args.output)
+        }
+        Action::DebugScript(a) => {
+            let report = match a.prevouts.prevout_source() {
+                Some(prevouts) => debug_script_offline(&a.tx_hex, a.input, &prevouts, &a.breakpoints),
+                None => debug_script(&a.tx_hex, a.input, &a.breakpoints, &settings),
+            }.map_err(|e| Box::new(e) as Box<dyn Error>)?;
+            match a.trace {
+                true => print_output(&report.steps, &args.output),
+                false => print_output(&report, &args.output),
+            }
+        }
+        Action::SimulateCoinSelection(a) => {
+            let seed = a.seed.unwrap_or_else(rand::random);
+            info!("Simulating with seed {}", seed);
+            let pool = match &a.pool_file {
+                Some(path) => load_pool(path),
+                None => synthetic_pool(a.pool_size, &AmountRange { distribution: a.pool_distribution, min: a.pool_min, max: a.pool_max }, seed),
+            }.map_err(|e| Box::new(e) as Box<dyn Error>)?;
+            let strategies = match a.strategies.is_empty() {
+                true => UTXOStrategy::CANDIDATES.into_iter().chain([UTXOStrategy::LowestWaste]).collect(),
+                false => a.strategies,
+            };
+            let config = SimulationConfig {
+                strategies,
+                payments: a.payments,
+                payment_amounts: AmountRange { distribution: a.payment_distribution, min: a.payment_min, max: a.payment_max },
+                fee_rate: a.fee_rate,
+                seed,
+            };
+            let report = simulate_coin_selection(&pool, &config).map_err(|e| Box::new(e) as Box<dyn Error>)?;
+            match a.steps {
+                true => print_output(&report.steps, &args.output),
+                false => print_output(&report.summary, &args.output),
+            }
+        }
+        Action::GenerateRpcAuth(a) => print_output(&generate_rpcauth(&a.rpc_username, a.rpc_password.as_deref()), &args.output),
     };
 
-    Ok(())
+    result.map_err(AppError::from)
 }
@@ -1,29 +1,86 @@
+use std::env;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use bitcoincore_rpc::jsonrpc::{self, simple_http::SimpleHttpTransport};
 use bitcoincore_rpc::{Auth, Client};
 use bitcoin::Network;
-use crate::settings::Settings;
+use crate::settings::{RpcAuth, Settings};
 
 use super::errors::ClientError;
 
 pub fn create_rpc_client(settings: &Settings, wallet_name: Option<&str>) -> Result<Client, ClientError> {
-    let port = match settings.network {
-        Network::Bitcoin => 8332,
-        Network::Testnet => 18332,
-        Network::Regtest => 18443,
-        Network::Signet => 38332,
-        _ => return Err(ClientError::UnsupportedNetwork),
+    let auth = resolve_auth(settings)?;
+
+    let base_url = settings.rpc_url()?;
+    let url = match wallet_name {
+        None => base_url,
+        Some(name) => format!("{}/wallet/{}", base_url, name),
     };
-    // TODO: allow for other authentication
-    let auth = Auth::UserPass(
-        settings.bitcoin_rpc_username.clone(),
-        settings.bitcoin_rpc_password.clone(),
-    );
 
-    // let auth = bitcoincore_rpc::Auth::CookieFile("/Users/alex/Library/Application Support/Bitcoin/regtest/.cookie".to_string().parse().unwrap());
+    let mut builder = SimpleHttpTransport::builder().url(&url)?;
+    if let (Some(user), pass) = auth.get_user_pass().map_err(ClientError::CannotConnect)? {
+        builder = builder.auth(user, pass);
+    }
+    if let Some(timeout) = settings.timeout_secs {
+        builder = builder.timeout(Duration::from_secs(timeout));
+    }
+    if let Some(proxy) = settings.rpc_proxy()? {
+        builder = builder.proxy_addr(&proxy.address)?;
+        if let Some((username, password)) = proxy.auth {
+            builder = builder.proxy_auth(username, password);
+        }
+    }
 
-    let url = match wallet_name {
-        None => format!("{}:{}", settings.network_url, port),
-        Some(name) => format!("{}:{}/wallet/{}", settings.network_url, port, name),
+    Ok(Client::from_jsonrpc(jsonrpc::Client::with_transport(builder.build())))
+}
+
+/// Turn the configured [`RpcAuth`] into credentials the RPC client understands.
+pub fn resolve_auth(settings: &Settings) -> Result<Auth, ClientError> {
+    match settings.rpc_auth().ok_or(ClientError::MissingCredentials)? {
+        RpcAuth::UserPass { username, password } => Ok(Auth::UserPass(username, password)),
+        RpcAuth::CookieFile { path, datadir } => {
+            let cookie = match path {
+                Some(path) => path,
+                None => default_cookie_path(settings.network, datadir)?,
+            };
+            if !cookie.is_file() {
+                return Err(ClientError::CookieFileNotFound(cookie));
+            }
+            Ok(Auth::CookieFile(cookie))
+        }
+        RpcAuth::Env { username_var, password_var } => {
+            let username = env::var(&username_var).map_err(|_| ClientError::MissingEnvVar(username_var))?;
+            let password = env::var(&password_var).map_err(|_| ClientError::MissingEnvVar(password_var))?;
+            Ok(Auth::UserPass(username, password))
+        }
+    }
+}
+
+/// Location of bitcoind's `.cookie` for `network`, inside `datadir` or the platform's default
+/// Bitcoin Core data directory.
+pub fn default_cookie_path(network: Network, datadir: Option<PathBuf>) -> Result<PathBuf, ClientError> {
+    let mut path = match datadir {
+        Some(datadir) => datadir,
+        None => default_datadir().ok_or(ClientError::DatadirNotFound)?,
     };
+    match network {
+        Network::Bitcoin => {}
+        Network::Testnet => path.push("testnet3"),
+        Network::Regtest => path.push("regtest"),
+        Network::Signet => path.push("signet"),
+        _ => return Err(ClientError::UnsupportedNetwork),
+    }
+    path.push(".cookie");
+    Ok(path)
+}
 
-    Client::new(&url, auth.clone()).map_err(ClientError::CannotConnect)
-}
\ No newline at end of file
+fn default_datadir() -> Option<PathBuf> {
+    if cfg!(target_os = "windows") {
+        env::var_os("APPDATA").map(|appdata| PathBuf::from(appdata).join("Bitcoin"))
+    } else if cfg!(target_os = "macos") {
+        env::var_os("HOME").map(|home| PathBuf::from(home).join("Library/Application Support/Bitcoin"))
+    } else {
+        env::var_os("HOME").map(|home| PathBuf::from(home).join(".bitcoin"))
+    }
+}
@@ -1,10 +1,11 @@
 use std::str::FromStr;
 
-use bitcoin::{Address, Amount};
-use bitcoincore_rpc::json::{GetRawTransactionResult, GetTxOutResult, ScanTxOutRequest};
+use bitcoin::{Address, Amount, BlockHash, Txid};
+use bitcoincore_rpc::json::{DecodeRawTransactionResult, GetRawTransactionResult, GetTxOutResult, ScanTxOutRequest, TestMempoolAcceptResult};
 use bitcoincore_rpc::{json::FinalizePsbtResult, RpcApi, Client};
 
 use log::info;
+use serde::Serialize;
 use serde_json::{json, Value};
 
 use crate::settings::Settings;
@@ -12,32 +13,89 @@ use crate::modules::bitcoind::create_rpc_client;
 
 use super::errors::BitcoindError;
 
-/// Blockchain Ops
+#[derive(Debug, Serialize)]
+pub struct BlockHeight {
+    pub height: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MinedBlocks {
+    pub address: Address,
+    pub block_hashes: Vec<BlockHash>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RescanResult {
+    pub start_height: usize,
+    pub stop_height: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BroadcastedTx {
+    pub txid: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CombinedPsbt {
+    pub psbt: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FinalizedTx {
+    pub txid: String,
+    pub hex: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SpendableUtxo {
+    pub txid: Txid,
+    pub vout: u32,
+    #[serde(with = "bitcoin::amount::serde::as_btc")]
+    pub amount: Amount,
+    pub confirmations: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SpendableBalance {
+    pub address: Address,
+    #[serde(with = "bitcoin::amount::serde::as_btc")]
+    pub total_spendable: Amount,
+    pub utxos: Vec<SpendableUtxo>,
+}
+
+// Blockchain Ops
 
-pub fn get_block_height(settings: &Settings) -> Result<(), BitcoindError> {
+/// Current height of the best chain.
+pub fn get_block_height(settings: &Settings) -> Result<BlockHeight, BitcoindError> {
     let client: Client = create_rpc_client(settings, None)?;
     let height = client.get_block_count()?;
     info!("Block height: {}", height);
-    Ok(())
+    Ok(BlockHeight { height })
 }
 
-pub fn mine_blocks(blocks: Option<u64>, address: &Address, settings: &Settings) -> Result<(), BitcoindError> {
+/// Mine `blocks` (default 1) blocks paying the coinbase to `address`.
+pub fn mine_blocks(blocks: Option<u64>, address: &Address, settings: &Settings) -> Result<MinedBlocks, BitcoindError> {
     let client: Client = create_rpc_client(settings, None)?;
     let blocks_to_mine = blocks.unwrap_or(1);
     info!("Mining {} blocks", blocks_to_mine);
-    client.generate_to_address(blocks_to_mine, address)?;
+    let block_hashes = client.generate_to_address(blocks_to_mine, address)?;
     info!("Mined {} blocks to address {}", blocks_to_mine, address);
-    Ok(())
+    Ok(MinedBlocks {
+        address: address.clone(),
+        block_hashes,
+    })
 }
 
-pub fn rescan_blockchain(settings: &Settings) -> Result<(), BitcoindError> {
+/// Rescan the chain from genesis for wallet transactions.
+pub fn rescan_blockchain(settings: &Settings) -> Result<RescanResult, BitcoindError> {
     let client = create_rpc_client(settings, None)?;
-    client.rescan_blockchain(Some(0), None)?;
-    Ok(())
+    let (start_height, stop_height) = client.rescan_blockchain(Some(0), None)?;
+    Ok(RescanResult { start_height, stop_height })
 }
 
-/// Transaction Ops
+// Transaction Ops
 
+/// Fetch a transaction by id. Requires `-txindex` for transactions outside the wallet.
 pub fn get_tx(txid: &str, settings: &Settings) -> Result<GetRawTransactionResult, BitcoindError> {
     let client: Client = create_rpc_client(settings, None)?;
     let txid_converted = bitcoin::Txid::from_str(txid).map_err(|_| BitcoindError::InvalidTxId)?;
@@ -45,12 +103,14 @@ pub fn get_tx(txid: &str, settings: &Settings) -> Result<GetRawTransactionResult
     Ok(tx)
 }
 
-pub fn get_tx_wrapper(txid: &str, settings: &Settings) -> Result<(), BitcoindError> {
+/// [`get_tx`] for the `get-tx` command.
+pub fn get_tx_wrapper(txid: &str, settings: &Settings) -> Result<GetRawTransactionResult, BitcoindError> {
     let tx = get_tx(txid, settings)?;
-    info!("{:#?}", tx);
-    Ok(())
+    info!("Fetched transaction {}", tx.txid);
+    Ok(tx)
 }
 
+/// Fetch an unspent output, optionally requiring a minimum number of confirmations.
 pub fn get_tx_out(txid: &str, vout: u32, confirmations: Option<u32>, settings: &Settings) -> Result<GetTxOutResult, BitcoindError> {
     let client: Client = create_rpc_client(settings, None)?;
     let txid_converted = bitcoin::Txid::from_str(txid).map_err(|_| BitcoindError::InvalidTxId)?;
@@ -66,16 +126,23 @@ pub fn get_tx_out(txid: &str, vout: u32, confirmations: Option<u32>, settings: &
     Ok(tx_out)
 }
 
-pub fn get_tx_out_wrapper(txid: &str, vout: u32, confirmations: Option<u32>, settings: &Settings) -> Result<(), BitcoindError> {
+/// [`get_tx_out`] for the `get-tx-out` command.
+pub fn get_tx_out_wrapper(txid: &str, vout: u32, confirmations: Option<u32>, settings: &Settings) -> Result<GetTxOutResult, BitcoindError> {
     let tx_out = get_tx_out(txid, vout, confirmations, settings)?;
-    info!("{:#?}", tx_out);
-    Ok(())
+    info!("Fetched TxOut {}:{}", txid, vout);
+    Ok(tx_out)
 }
 
-pub fn broadcast_tx(client: &Client, tx_hex: &str, max_fee_rate: Option<f64>) -> Result<String, BitcoindError> {
-    let max_fee_rate = max_fee_rate.map(|fee_rate| {
+/// A max fee rate in sat/vB as the BTC/kvB the node's `maxfeerate` arguments take.
+fn max_fee_rate_btc_per_kvb(max_fee_rate: Option<f64>) -> f64 {
+    max_fee_rate.map(|fee_rate| {
         (fee_rate / 100_000_000.0 * 1000.0).to_string().parse::<f64>().unwrap_or(0.1)
-    }).unwrap_or(0.1);
+    }).unwrap_or(0.1)
+}
+
+/// Submit a raw transaction with `sendrawtransaction`. `max_fee_rate` is in sat/vB.
+pub fn broadcast_tx(client: &Client, tx_hex: &str, max_fee_rate: Option<f64>) -> Result<String, BitcoindError> {
+    let max_fee_rate = max_fee_rate_btc_per_kvb(max_fee_rate);
 
     let tx_id: Value = client.call(
         "sendrawtransaction",
@@ -88,50 +155,52 @@ pub fn broadcast_tx(client: &Client, tx_hex: &str, max_fee_rate: Option<f64>) ->
     Ok(tx_id_str)
 }
 
-pub fn broadcast_tx_wrapper(tx_hex: &str, max_fee_rate: f64, settings: &Settings) -> Result<(), BitcoindError> {
-    let client: Client = create_rpc_client(settings, None)?;
-    broadcast_tx(&client, tx_hex, Some(max_fee_rate))?;
-    Ok(())
+/// Ask the node whether it would accept a raw transaction, without broadcasting it.
+/// `max_fee_rate` is in sat/vB.
+pub fn test_mempool_accept(client: &Client, tx_hex: &str, max_fee_rate: Option<f64>) -> Result<TestMempoolAcceptResult, BitcoindError> {
+    let results: Vec<TestMempoolAcceptResult> = client.call(
+        "testmempoolaccept",
+        &[json!([tx_hex]), json!(max_fee_rate_btc_per_kvb(max_fee_rate))],
+    )?;
+    results.into_iter().next()
+        .ok_or_else(|| BitcoindError::Other("Empty testmempoolaccept response".to_string()))
 }
 
-pub fn decode_raw_tx(tx_hex: &str, settings: &Settings) -> Result<(), BitcoindError> {
-    let client = create_rpc_client(settings, None)?;
-    let tx = client.decode_raw_transaction(tx_hex, None)?;
-    info!("{:#?}", tx);
-    Ok(())
+/// [`broadcast_tx`] using a fresh node client.
+pub fn broadcast_tx_wrapper(tx_hex: &str, max_fee_rate: f64, settings: &Settings) -> Result<BroadcastedTx, BitcoindError> {
+    let client: Client = create_rpc_client(settings, None)?;
+    let txid = broadcast_tx(&client, tx_hex, Some(max_fee_rate))?;
+    Ok(BroadcastedTx { txid })
 }
 
-/// PSBT Ops
-
-pub fn decode_psbt(psbt: &str, settings: &Settings) -> Result<(), BitcoindError> {
+/// Decode a raw transaction hex with the node.
+pub fn decode_raw_tx(tx_hex: &str, settings: &Settings) -> Result<DecodeRawTransactionResult, BitcoindError> {
     let client = create_rpc_client(settings, None)?;
-    let psbt: serde_json::Value = client.call("decodepsbt", &[json!(psbt)])?;
-    info!("PSBT: {:#?}", psbt);
-    Ok(())
+    let tx = client.decode_raw_transaction(tx_hex, None)?;
+    info!("Decoded transaction {}", tx.txid);
+    Ok(tx)
 }
 
-pub fn analyze_psbt(psbt: &str, settings: &Settings) -> Result<(), BitcoindError> {
-    let client = create_rpc_client(settings, None)?;
-    let psbt: serde_json::Value = client.call("analyzepsbt", &[json!(psbt)])?;
-    info!("PSBT: {:#?}", psbt);
-    Ok(())
-}
+// PSBT Ops
 
-pub fn combine_psbts(psbts: &Vec<String>, settings: &Settings) -> Result<(), BitcoindError> {
+/// Merge signatures from several copies of the same PSBT.
+pub fn combine_psbts(psbts: &[String], settings: &Settings) -> Result<CombinedPsbt, BitcoindError> {
     let client = create_rpc_client(settings, None)?;
-    let res = client.combine_psbt(&psbts[..])?;
-    info!("CombinedPSBT: {:#?}", res);
-    Ok(())
+    let psbt = client.combine_psbt(psbts)?;
+    info!("Combined {} PSBTs", psbts.len());
+    Ok(CombinedPsbt { psbt })
 }
 
-pub fn finalize_psbt(psbt: &str, settings: &Settings) -> Result<(), BitcoindError> {
+/// Finalize a PSBT, returning the network transaction once it is complete.
+pub fn finalize_psbt(psbt: &str, settings: &Settings) -> Result<FinalizePsbtResult, BitcoindError> {
     let client = create_rpc_client(settings, None)?;
     let res = client.finalize_psbt(psbt, None)?;
-    info!("FinalizedPSBT: {:#?}", res);
-    Ok(())
+    info!("PSBT complete: {}", res.complete);
+    Ok(res)
 }
 
-pub fn finalize_psbt_and_broadcast(psbt: &str, settings: &Settings) -> Result<(), BitcoindError> {
+/// Finalize a complete PSBT and broadcast the resulting transaction.
+pub fn finalize_psbt_and_broadcast(psbt: &str, settings: &Settings) -> Result<FinalizedTx, BitcoindError> {
     let client: Client = create_rpc_client(settings, None)?;
     let res: FinalizePsbtResult = client.finalize_psbt(psbt, None)?;
     if !res.complete {
@@ -144,14 +213,19 @@ pub fn finalize_psbt_and_broadcast(psbt: &str, settings: &Settings) -> Result<()
 
     let tx_id: String = broadcast_tx(&client, &raw_hex, Some(0.0))?;
     info!("Tx broadcasted: {}", tx_id);
-    Ok(())
+    Ok(FinalizedTx {
+        txid: tx_id,
+        hex: raw_hex,
+    })
 }
 
-/// Address Ops
+// Address Ops
 
+/// Sum the UTXOs held by `address` using `scantxoutset`.
+///
 /// NOTE: this function does not check if the UTXO is from coinbase rewards or not, it only
 /// checks if the UTXO has greater than or equal to 6 confirmations.
-pub fn get_spendable_balance(address: &Address, settings: &Settings) -> Result<(), BitcoindError> {
+pub fn get_spendable_balance(address: &Address, settings: &Settings) -> Result<SpendableBalance, BitcoindError> {
     let client = create_rpc_client(settings, None)?;
 
     let descriptor = format!("addr({})", address);
@@ -161,7 +235,7 @@ pub fn get_spendable_balance(address: &Address, settings: &Settings) -> Result<(
     let current_height = client.get_block_count()?;
 
     let mut total_spendable = Amount::from_sat(0);
-    let mut spendable_utxos = Vec::new();
+    let mut spendable_utxos: Vec<SpendableUtxo> = Vec::new();
 
     info!("Address: {}", address);
     info!("UTXOs:");
@@ -171,7 +245,12 @@ pub fn get_spendable_balance(address: &Address, settings: &Settings) -> Result<(
 
         if confirmations >= 6 {
             total_spendable += utxo.amount;
-            spendable_utxos.push(utxo);
+            spendable_utxos.push(SpendableUtxo {
+                txid: utxo.txid,
+                vout: utxo.vout,
+                amount: utxo.amount,
+                confirmations,
+            });
 
             info!("  UTXO {}: {} BTC (txid: {}, vout: {}, confirmations: {})", 
                   index + 1, 
@@ -185,5 +264,9 @@ pub fn get_spendable_balance(address: &Address, settings: &Settings) -> Result<(
     info!("Total Spendable Balance: {} BTC", total_spendable.to_btc());
     info!("Number of Spendable UTXOs: {}", spendable_utxos.len());
 
-    Ok(())
+    Ok(SpendableBalance {
+        address: address.clone(),
+        total_spendable,
+        utxos: spendable_utxos,
+    })
 }
@@ -0,0 +1,51 @@
+use std::path::PathBuf;
+
+use bitcoincore_rpc::Client;
+
+use crate::modules::bitcoind::create_rpc_client;
+use crate::modules::errors::{ClientError, SettingsError, WalletError};
+use crate::modules::wallet::Wallet;
+use crate::settings::Settings;
+
+/// Handle on a single bitcoind node. Owns the [`Settings`] that every operation in
+/// [`crate::modules::client`], [`crate::modules::wallet_ops`] and
+/// [`crate::modules::verification`] takes, and hands out RPC clients and wallets.
+pub struct Context {
+    settings: Settings,
+}
+
+impl Context {
+    pub fn new(settings: Settings) -> Self {
+        Context { settings }
+    }
+
+    /// Read the settings from a TOML file, as the binary does with `--settings-file`.
+    pub fn from_toml_file(path: &PathBuf) -> Result<Self, SettingsError> {
+        Ok(Context::new(Settings::from_toml_file(path)?))
+    }
+
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    /// RPC client for node-level calls.
+    pub fn client(&self) -> Result<Client, ClientError> {
+        create_rpc_client(&self.settings, None)
+    }
+
+    /// RPC client scoped to the named wallet.
+    pub fn wallet_client(&self, wallet_name: &str) -> Result<Client, ClientError> {
+        create_rpc_client(&self.settings, Some(wallet_name))
+    }
+
+    /// Load (or create, if allowed by the settings) the named wallet.
+    pub fn wallet(&self, wallet_name: &str) -> Result<Wallet, WalletError> {
+        Wallet::new(wallet_name, &self.settings)
+    }
+}
+
+impl From<Settings> for Context {
+    fn from(settings: Settings) -> Self {
+        Context::new(settings)
+    }
+}
@@ -1,7 +1,14 @@
 use std::{error::Error, fmt, io};
+use std::path::PathBuf;
 
+use bitcoin::blockdata::constants::COINBASE_MATURITY;
+use bitcoin::{Amount, FeeRate, Network, OutPoint, Txid};
 use bitcoincore_rpc::Error as RpcError;
 use bitcoin::consensus::encode::Error as EncodeError;
+use bitcoin::psbt::PsbtParseError;
+
+use crate::modules::multisig::WalletFileFormat;
+use crate::utils::utils::MultisigScriptType;
 
 /// Bitcoind Errors
 
@@ -52,6 +59,12 @@ impl From<ClientError> for BitcoindError {
 pub enum ClientError {
     CannotConnect(RpcError),
     UnsupportedNetwork,
+    MissingCredentials,
+    CookieFileNotFound(PathBuf),
+    DatadirNotFound,
+    MissingEnvVar(String),
+    InvalidSettings(SettingsError),
+    Transport(bitcoincore_rpc::jsonrpc::simple_http::Error),
 }
 
 impl fmt::Display for ClientError {
@@ -59,6 +72,12 @@ impl fmt::Display for ClientError {
         match self {
             ClientError::CannotConnect(err) => write!(f, "Cannot connect to Bitcoin Core: {}", err),
             ClientError::UnsupportedNetwork => write!(f, "Unsupported network"),
+            ClientError::MissingCredentials => write!(f, "No RPC credentials configured, add an [auth] table to the settings file"),
+            ClientError::CookieFileNotFound(path) => write!(f, "Cookie file not found at {}", path.display()),
+            ClientError::DatadirNotFound => write!(f, "Cannot determine the default Bitcoin Core data directory"),
+            ClientError::MissingEnvVar(var) => write!(f, "Environment variable {} is not set", var),
+            ClientError::InvalidSettings(err) => write!(f, "Invalid settings: {}", err),
+            ClientError::Transport(err) => write!(f, "Transport error: {}", err),
         }
     }
 }
@@ -67,7 +86,9 @@ impl Error for ClientError {
     fn source(&self) -> Option<&(dyn Error + 'static)> {
         match self {
             ClientError::CannotConnect(err) => Some(err),
-            ClientError::UnsupportedNetwork => None,
+            ClientError::InvalidSettings(err) => Some(err),
+            ClientError::Transport(err) => Some(err),
+            _ => None,
         }
     }
 }
@@ -78,6 +99,18 @@ impl From<RpcError> for ClientError {
     }
 }
 
+impl From<SettingsError> for ClientError {
+    fn from(err: SettingsError) -> Self {
+        ClientError::InvalidSettings(err)
+    }
+}
+
+impl From<bitcoincore_rpc::jsonrpc::simple_http::Error> for ClientError {
+    fn from(err: bitcoincore_rpc::jsonrpc::simple_http::Error) -> Self {
+        ClientError::Transport(err)
+    }
+}
+
 /// Verification Errors
 
 #[derive(Debug)]
@@ -88,6 +121,11 @@ pub enum VerificationError {
     UTXOCheckError(usize, String),
     TransactionVerificationFailed(String),
     UTXOError(String),
+    MissingPrevout(usize, OutPoint),
+    InputOutOfRange(usize, usize),
+    InvalidPsbt(String),
+    Io(io::Error),
+    Json(serde_json::Error),
 }
 
 impl fmt::Display for VerificationError {
@@ -99,6 +137,11 @@ impl fmt::Display for VerificationError {
             VerificationError::UTXOCheckError(index, e) => write!(f, "Error checking UTXO for input {}: {}", index, e),
             VerificationError::TransactionVerificationFailed(e) => write!(f, "Transaction verification failed: {}", e),
             VerificationError::UTXOError(e) => write!(f, "Error checking UTXO: {}", e),
+            VerificationError::MissingPrevout(index, outpoint) => write!(f, "No previous output given for input {} ({})", index, outpoint),
+            VerificationError::InputOutOfRange(index, count) => write!(f, "Input {} does not exist, the transaction has {} inputs", index, count),
+            VerificationError::InvalidPsbt(e) => write!(f, "Invalid PSBT: {}", e),
+            VerificationError::Io(e) => write!(f, "IO error: {}", e),
+            VerificationError::Json(e) => write!(f, "Invalid prevout file: {}", e),
         }
     }
 }
@@ -117,6 +160,66 @@ impl From<bitcoin::consensus::encode::Error> for VerificationError {
     }
 }
 
+impl From<io::Error> for VerificationError {
+    fn from(err: io::Error) -> Self {
+        VerificationError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for VerificationError {
+    fn from(err: serde_json::Error) -> Self {
+        VerificationError::Json(err)
+    }
+}
+
+/// Multisig Errors
+
+#[derive(Debug)]
+pub enum MultisigError {
+    InvalidKey(String),
+    UnsupportedKeyPath(String),
+    KeyNetworkMismatch(String, Network),
+    UnsupportedDescriptor(String),
+    UnsupportedScriptType(MultisigScriptType, WalletFileFormat),
+    MalformedFile(String),
+    ChecksumMismatch(String),
+    MissingWalletName,
+    FirstAddressMismatch(String, String),
+    Io(io::Error),
+}
+
+impl fmt::Display for MultisigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MultisigError::InvalidKey(key) => write!(f, "Invalid cosigner key {}, expected [fingerprint/path]xpub", key),
+            MultisigError::UnsupportedKeyPath(key) => write!(f, "Cosigner key {} must be an account xpub without a child path", key),
+            MultisigError::KeyNetworkMismatch(key, network) => write!(f, "Key {} is not valid on network {}", key, network),
+            MultisigError::UnsupportedDescriptor(desc) => write!(f, "Not a sorted multisig descriptor: {}", desc),
+            MultisigError::UnsupportedScriptType(script_type, format) => write!(f, "{:?} multisig wallets cannot be exported as {:?}", script_type, format),
+            MultisigError::MalformedFile(reason) => write!(f, "Malformed wallet file: {}", reason),
+            MultisigError::ChecksumMismatch(desc) => write!(f, "Invalid descriptor checksum: {}", desc),
+            MultisigError::MissingWalletName => write!(f, "The wallet file has no name, pass one with --multisig-name"),
+            MultisigError::FirstAddressMismatch(expected, actual) => write!(f, "First address {} does not match the derived address {}", expected, actual),
+            MultisigError::Io(err) => write!(f, "I/O error: {}", err),
+        }
+    }
+}
+
+impl Error for MultisigError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            MultisigError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for MultisigError {
+    fn from(err: io::Error) -> Self {
+        MultisigError::Io(err)
+    }
+}
+
 // Wallet Ops Errors
 
 #[derive(Debug)]
@@ -129,6 +232,18 @@ pub enum WalletOpsError {
     InsufficientBalance,
     NoUnspentTransactions,
     NotMultisigWallet,
+    NoCosigners,
+    TooManyCosigners(usize, usize),
+    InvalidThreshold(u32, usize),
+    DuplicateCosigner(String),
+    MultisigError(MultisigError),
+    InvalidPsbt(String),
+    SigningIncomplete(usize),
+    DuplicateUtxo(OutPoint),
+    UtxoUnavailable(OutPoint),
+    NotWalletUtxo(OutPoint),
+    ImmatureCoinbase(OutPoint, u32),
+    UtxoLocked(OutPoint),
     DescriptorError(miniscript::Error),
     JsonError(serde_json::Error),
     Other(String),
@@ -145,6 +260,18 @@ impl fmt::Display for WalletOpsError {
             WalletOpsError::InsufficientBalance => write!(f, "Insufficient balance"),
             WalletOpsError::NoUnspentTransactions => write!(f, "No unspent transactions"),
             WalletOpsError::NotMultisigWallet => write!(f, "Wallet is not a multisig wallet"),
+            WalletOpsError::NoCosigners => write!(f, "A multisig wallet needs at least one cosigner"),
+            WalletOpsError::TooManyCosigners(total, max) => write!(f, "{} cosigners exceed the limit of {} keys", total, max),
+            WalletOpsError::InvalidThreshold(nrequired, total) => write!(f, "Invalid threshold {}-of-{}, required signatures must be between 1 and {}", nrequired, total, total),
+            WalletOpsError::DuplicateCosigner(name) => write!(f, "Cosigner {} is listed more than once", name),
+            WalletOpsError::MultisigError(err) => write!(f, "Multisig error: {}", err),
+            WalletOpsError::InvalidPsbt(err) => write!(f, "Invalid PSBT: {}", err),
+            WalletOpsError::SigningIncomplete(cosigners) => write!(f, "PSBT is still incomplete after {} cosigners signed", cosigners),
+            WalletOpsError::DuplicateUtxo(outpoint) => write!(f, "UTXO {} is listed more than once", outpoint),
+            WalletOpsError::UtxoUnavailable(outpoint) => write!(f, "UTXO {} does not exist or is already spent", outpoint),
+            WalletOpsError::NotWalletUtxo(outpoint) => write!(f, "UTXO {} does not belong to the wallet", outpoint),
+            WalletOpsError::ImmatureCoinbase(outpoint, confirmations) => write!(f, "Coinbase UTXO {} has {} confirmations and cannot be spent before {}", outpoint, confirmations, COINBASE_MATURITY),
+            WalletOpsError::UtxoLocked(outpoint) => write!(f, "UTXO {} is locked, release it with unlock-utxos first", outpoint),
             WalletOpsError::DescriptorError(err) => write!(f, "Descriptor error: {}", err),
             WalletOpsError::JsonError(err) => write!(f, "JSON error: {}", err),
             WalletOpsError::Other(err) => write!(f, "Other error: {}", err),
@@ -184,6 +311,12 @@ impl From<UtilsError> for WalletOpsError {
     }
 }
 
+impl From<MultisigError> for WalletOpsError {
+    fn from(err: MultisigError) -> Self {
+        WalletOpsError::MultisigError(err)
+    }
+}
+
 impl From<miniscript::Error> for WalletOpsError {
     fn from(err: miniscript::Error) -> Self {
         WalletOpsError::DescriptorError(err)
@@ -196,6 +329,92 @@ impl From<serde_json::Error> for WalletOpsError {
     }
 }
 
+/// Fee Bump Errors
+
+#[derive(Debug)]
+pub enum FeeBumpError {
+    WalletError(WalletError),
+    RpcError(RpcError),
+    BitcoindError(BitcoindError),
+    DeserializationError(EncodeError),
+    AlreadyConfirmed(Txid),
+    NotReplaceable(Txid),
+    HasDescendants(Txid, u64),
+    FeeRateTooLow(FeeRate, FeeRate),
+    NoChangeOutput(Txid),
+    InsufficientChange(Amount, Amount),
+    BumpFailed(String),
+    NoPrivateKeys(String),
+    ParentNotInMempool(Txid),
+    NotWalletOutput(OutPoint),
+    NoWalletOutput(Txid),
+    PackageRejected(String),
+}
+
+impl fmt::Display for FeeBumpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FeeBumpError::WalletError(err) => write!(f, "Wallet error: {}", err),
+            FeeBumpError::RpcError(err) => write!(f, "RPC error: {}", err),
+            FeeBumpError::BitcoindError(err) => write!(f, "Bitcoind error: {}", err),
+            FeeBumpError::DeserializationError(err) => write!(f, "Failed to deserialize transaction: {}", err),
+            FeeBumpError::AlreadyConfirmed(txid) => write!(f, "Transaction {} is already confirmed", txid),
+            FeeBumpError::NotReplaceable(txid) => write!(f, "Transaction {} does not signal BIP125 replaceability", txid),
+            FeeBumpError::HasDescendants(txid, count) => write!(f, "Transaction {} has {} descendants in the mempool", txid, count),
+            FeeBumpError::FeeRateTooLow(fee_rate, min_rate) => write!(
+                f,
+                "Fee rate {} sat/vB is below the {} sat/vB a replacement needs",
+                fee_rate.to_sat_per_kwu() as f64 / 250.0,
+                min_rate.to_sat_per_kwu() as f64 / 250.0,
+            ),
+            FeeBumpError::NoChangeOutput(txid) => write!(f, "Could not identify a single change output in transaction {}", txid),
+            FeeBumpError::InsufficientChange(value, fee) => write!(f, "Output of {} cannot pay {} of fee and stay above dust", value, fee),
+            FeeBumpError::BumpFailed(err) => write!(f, "Fee bump failed: {}", err),
+            FeeBumpError::NoPrivateKeys(wallet) => write!(f, "Wallet {} has no private keys to sign the child", wallet),
+            FeeBumpError::ParentNotInMempool(txid) => write!(f, "Transaction {} is neither in the mempool nor paid for by the wallet", txid),
+            FeeBumpError::NotWalletOutput(outpoint) => write!(f, "Output {} is spent or does not belong to the wallet", outpoint),
+            FeeBumpError::NoWalletOutput(txid) => write!(f, "Transaction {} has no unspent output belonging to the wallet", txid),
+            FeeBumpError::PackageRejected(result) => write!(f, "Package rejected: {}", result),
+        }
+    }
+}
+
+impl Error for FeeBumpError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            FeeBumpError::WalletError(err) => Some(err),
+            FeeBumpError::RpcError(err) => Some(err),
+            FeeBumpError::BitcoindError(err) => Some(err),
+            FeeBumpError::DeserializationError(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<WalletError> for FeeBumpError {
+    fn from(err: WalletError) -> Self {
+        FeeBumpError::WalletError(err)
+    }
+}
+
+impl From<RpcError> for FeeBumpError {
+    fn from(err: RpcError) -> Self {
+        FeeBumpError::RpcError(err)
+    }
+}
+
+impl From<BitcoindError> for FeeBumpError {
+    fn from(err: BitcoindError) -> Self {
+        FeeBumpError::BitcoindError(err)
+    }
+}
+
+impl From<EncodeError> for FeeBumpError {
+    fn from(err: EncodeError) -> Self {
+        FeeBumpError::DeserializationError(err)
+    }
+}
+
 /// Wallet Errors
 
 #[derive(Debug)]
@@ -206,6 +425,8 @@ pub enum WalletError {
     SigningFailed(String),
     RpcError(RpcError),
     AddressNotFound,
+    LockFailed,
+    InvalidPsbt(String),
 }
 
 impl fmt::Display for WalletError {
@@ -216,7 +437,9 @@ impl fmt::Display for WalletError {
             WalletError::AddressNetworkMismatch => write!(f, "Address network mismatch"),
             WalletError::SigningFailed(err) => write!(f, "Signing failed: {}", err),
             WalletError::RpcError(err) => write!(f, "RPC error: {}", err),
-            WalletError::AddressNotFound => write!(f, "Address not found in transaction details"),
+            WalletError::AddressNotFound => write!(f, "Address not found in transaction outputs"),
+            WalletError::LockFailed => write!(f, "The node did not change the UTXO locks"),
+            WalletError::InvalidPsbt(err) => write!(f, "Invalid transaction returned by the wallet: {}", err),
         }
     }
 }
@@ -250,6 +473,7 @@ pub enum UtilsError {
     ExternalXpubNotFound,
     InternalXpubNotFound,
     InsufficientUTXOs,
+    UncoveredTarget(Amount, Amount),
     JsonParsingError(serde_json::Error),
 }
 
@@ -259,6 +483,7 @@ impl fmt::Display for UtilsError {
             UtilsError::ExternalXpubNotFound => write!(f, "External xpub descriptor not found"),
             UtilsError::InternalXpubNotFound => write!(f, "Internal xpub descriptor not found"),
             UtilsError::InsufficientUTXOs => write!(f, "Insufficient UTXOs to meet target amount"),
+            UtilsError::UncoveredTarget(selected, required) => write!(f, "Selected UTXOs total {} but the amount plus fee is {}", selected, required),
             UtilsError::JsonParsingError(e) => write!(f, "JSON parsing error: {}", e),
         }
     }
@@ -272,6 +497,252 @@ impl From<serde_json::Error> for UtilsError {
     }
 }
 
+/// Payout Errors
+
+#[derive(Debug)]
+pub enum PayoutError {
+    NoPayouts,
+    MissingAmount(usize),
+    InvalidAmount(usize, String),
+    InvalidAddress(usize, String),
+    WrongNetwork(usize, String, Network),
+    DuplicateAddress(usize, String, usize),
+    BelowDust(usize, Amount, Amount),
+    Io(io::Error),
+    Json(serde_json::Error),
+}
+
+impl fmt::Display for PayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PayoutError::NoPayouts => write!(f, "The payout file has no payouts"),
+            PayoutError::MissingAmount(entry) => write!(f, "Payout {}: missing amount", entry),
+            PayoutError::InvalidAmount(entry, amount) => write!(f, "Payout {}: invalid amount {}", entry, amount),
+            PayoutError::InvalidAddress(entry, address) => write!(f, "Payout {}: invalid address {}", entry, address),
+            PayoutError::WrongNetwork(entry, address, network) => write!(f, "Payout {}: address {} is not valid on network {}", entry, address, network),
+            PayoutError::DuplicateAddress(entry, address, first) => write!(f, "Payout {}: address {} is already paid by payout {}", entry, address, first),
+            PayoutError::BelowDust(entry, amount, dust) => write!(f, "Payout {}: {} is below the dust limit of {}", entry, amount, dust),
+            PayoutError::Io(err) => write!(f, "IO error: {}", err),
+            PayoutError::Json(err) => write!(f, "Invalid payout file: {}", err),
+        }
+    }
+}
+
+impl Error for PayoutError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            PayoutError::Io(err) => Some(err),
+            PayoutError::Json(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for PayoutError {
+    fn from(err: io::Error) -> Self {
+        PayoutError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for PayoutError {
+    fn from(err: serde_json::Error) -> Self {
+        PayoutError::Json(err)
+    }
+}
+
+/// Policy Errors
+
+#[derive(Debug)]
+pub enum PolicyError {
+    HexDecodeError(hex::FromHexError),
+    DeserializationError(EncodeError),
+    Bitcoind(BitcoindError),
+    Prevouts(VerificationError),
+    Rejected(String),
+}
+
+impl fmt::Display for PolicyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PolicyError::HexDecodeError(e) => write!(f, "Failed to decode transaction hex: {}", e),
+            PolicyError::DeserializationError(e) => write!(f, "Failed to deserialize transaction: {}", e),
+            PolicyError::Bitcoind(e) => write!(f, "Bitcoind error: {}", e),
+            PolicyError::Prevouts(e) => write!(f, "Failed to fetch spent outputs: {}", e),
+            PolicyError::Rejected(reasons) => write!(f, "Not broadcasting, the transaction would be rejected: {}", reasons),
+        }
+    }
+}
+
+impl Error for PolicyError {}
+
+impl From<hex::FromHexError> for PolicyError {
+    fn from(err: hex::FromHexError) -> Self {
+        PolicyError::HexDecodeError(err)
+    }
+}
+
+impl From<EncodeError> for PolicyError {
+    fn from(err: EncodeError) -> Self {
+        PolicyError::DeserializationError(err)
+    }
+}
+
+impl From<BitcoindError> for PolicyError {
+    fn from(err: BitcoindError) -> Self {
+        PolicyError::Bitcoind(err)
+    }
+}
+
+impl From<ClientError> for PolicyError {
+    fn from(err: ClientError) -> Self {
+        PolicyError::Bitcoind(BitcoindError::ClientError(err))
+    }
+}
+
+impl From<VerificationError> for PolicyError {
+    fn from(err: VerificationError) -> Self {
+        PolicyError::Prevouts(err)
+    }
+}
+
+/// PSBT Errors
+
+#[derive(Debug)]
+pub enum PsbtError {
+    Parse(PsbtParseError),
+}
+
+impl fmt::Display for PsbtError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PsbtError::Parse(e) => write!(f, "Invalid PSBT: {}", e),
+        }
+    }
+}
+
+impl Error for PsbtError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            PsbtError::Parse(e) => Some(e),
+        }
+    }
+}
+
+impl From<PsbtParseError> for PsbtError {
+    fn from(err: PsbtParseError) -> Self {
+        PsbtError::Parse(err)
+    }
+}
+
+/// Simulation Errors
+
+#[derive(Debug)]
+pub enum SimulationError {
+    InvalidAmountRange(Amount, Amount),
+    EmptyPool,
+    Io(io::Error),
+    Json(serde_json::Error),
+}
+
+impl fmt::Display for SimulationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SimulationError::InvalidAmountRange(min, max) => write!(f, "Invalid amount range: {} to {}", min, max),
+            SimulationError::EmptyPool => write!(f, "The UTXO pool is empty"),
+            SimulationError::Io(err) => write!(f, "IO error: {}", err),
+            SimulationError::Json(err) => write!(f, "Invalid list_unspent dump: {}", err),
+        }
+    }
+}
+
+impl Error for SimulationError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SimulationError::Io(err) => Some(err),
+            SimulationError::Json(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SimulationError {
+    fn from(err: io::Error) -> Self {
+        SimulationError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for SimulationError {
+    fn from(err: serde_json::Error) -> Self {
+        SimulationError::Json(err)
+    }
+}
+
+/// Cli Errors
+
+#[derive(Debug)]
+pub enum CliError {
+    AddressNetworkMismatch(String, Network),
+    InvalidRange(u32, u32),
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CliError::AddressNetworkMismatch(address, network) => write!(f, "Address {} is not valid on network {}", address, network),
+            CliError::InvalidRange(start, end) => write!(f, "Invalid range: start {} is greater than end {}", start, end),
+        }
+    }
+}
+
+impl Error for CliError {}
+
+/// Output Errors
+
+#[derive(Debug)]
+pub enum OutputError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    Yaml(serde_yaml::Error),
+}
+
+impl fmt::Display for OutputError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OutputError::Io(err) => write!(f, "IO error: {}", err),
+            OutputError::Json(err) => write!(f, "JSON serialization error: {}", err),
+            OutputError::Yaml(err) => write!(f, "YAML serialization error: {}", err),
+        }
+    }
+}
+
+impl Error for OutputError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            OutputError::Io(err) => Some(err),
+            OutputError::Json(err) => Some(err),
+            OutputError::Yaml(err) => Some(err),
+        }
+    }
+}
+
+impl From<io::Error> for OutputError {
+    fn from(err: io::Error) -> Self {
+        OutputError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for OutputError {
+    fn from(err: serde_json::Error) -> Self {
+        OutputError::Json(err)
+    }
+}
+
+impl From<serde_yaml::Error> for OutputError {
+    fn from(err: serde_yaml::Error) -> Self {
+        OutputError::Yaml(err)
+    }
+}
+
 /// Settings Errors
 
 #[derive(Debug)]
@@ -279,6 +750,13 @@ pub enum SettingsError {
     Io(io::Error),
     TomlDe(toml::de::Error),
     TomlSer(toml::ser::Error),
+    UnknownProfile(String),
+    InvalidUrl(String),
+    PlainHttpOnly(String),
+    InvalidProxy(String),
+    ConflictingPorts(u16, u16),
+    InvalidTimeout,
+    UnsupportedNetwork,
 }
 
 impl fmt::Display for SettingsError {
@@ -287,6 +765,13 @@ impl fmt::Display for SettingsError {
             SettingsError::Io(err) => write!(f, "IO error: {}", err),
             SettingsError::TomlDe(err) => write!(f, "TOML deserialization error: {}", err),
             SettingsError::TomlSer(err) => write!(f, "TOML serialization error: {}", err),
+            SettingsError::UnknownProfile(name) => write!(f, "Unknown profile: {}", name),
+            SettingsError::InvalidUrl(url) => write!(f, "Invalid network_url {}, expected http://host[:port] without a path", url),
+            SettingsError::PlainHttpOnly(url) => write!(f, "network_url {} uses TLS, but the RPC client only speaks plain http://; reach the node through a SOCKS5 proxy or a tunnel instead", url),
+            SettingsError::InvalidProxy(proxy) => write!(f, "Invalid proxy {}, expected [socks5://][user:password@]host:port", proxy),
+            SettingsError::ConflictingPorts(url_port, rpc_port) => write!(f, "network_url already specifies port {} but rpc_port is set to {}", url_port, rpc_port),
+            SettingsError::InvalidTimeout => write!(f, "timeout_secs must be greater than zero"),
+            SettingsError::UnsupportedNetwork => write!(f, "Unsupported network"),
         }
     }
 }
@@ -297,6 +782,7 @@ impl Error for SettingsError {
             SettingsError::Io(err) => Some(err),
             SettingsError::TomlDe(err) => Some(err),
             SettingsError::TomlSer(err) => Some(err),
+            _ => None,
         }
     }
 }
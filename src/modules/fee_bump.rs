@@ -0,0 +1,534 @@
+use bitcoin::absolute::LockTime;
+use bitcoin::psbt::Psbt;
+use bitcoin::transaction::Version;
+use bitcoin::{Address, Amount, FeeRate, OutPoint, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Txid, Weight, Witness};
+use bitcoin::consensus::serialize;
+use bitcoincore_rpc::json::{AddressType, ListUnspentResultEntry};
+use bitcoincore_rpc::{RawTx, RpcApi};
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::settings::Settings;
+use crate::modules::client::get_tx;
+use crate::modules::wallet::Wallet;
+use crate::utils::fees::{fee_for_weight, input_weight, output_weight, TX_OVERHEAD_WEIGHT};
+
+use super::errors::FeeBumpError;
+
+/// How [`bump_fee`] builds the replacement transaction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BumpMethod {
+    /// The node's `bumpfee`, or `psbtbumpfee` for wallets without private keys
+    Node,
+    /// Keep the inputs and payments and take the extra fee out of the change output
+    Change,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BumpedTx {
+    pub replaced_txid: Txid,
+    /// Replacement already signed and broadcast, absent when a PSBT still has to be signed
+    pub txid: Option<Txid>,
+    /// Unsigned replacement for watch-only (multisig) wallets
+    pub psbt: Option<String>,
+    #[serde(with = "bitcoin::amount::serde::as_btc")]
+    pub original_fee: Amount,
+    #[serde(with = "bitcoin::amount::serde::as_btc")]
+    pub fee: Amount,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CpfpTx {
+    pub parent_txid: Txid,
+    /// Child transaction, absent when the parent and its ancestors already pay the fee rate
+    pub txid: Option<Txid>,
+    pub hex: Option<String>,
+    /// Fee paid by the child
+    #[serde(with = "bitcoin::amount::serde::as_btc")]
+    pub fee: Amount,
+    /// Child plus the parent and its unconfirmed ancestors
+    pub package_vsize: u64,
+    #[serde(with = "bitcoin::amount::serde::as_btc")]
+    pub package_fee: Amount,
+    /// In sat/vB
+    pub package_fee_rate: f64,
+    /// Both transactions went through `submitpackage` rather than the child alone
+    pub package_submitted: bool,
+}
+
+/// `bumpfee` and `psbtbumpfee` results.
+#[derive(Deserialize)]
+struct NodeBumpResult {
+    txid: Option<Txid>,
+    psbt: Option<String>,
+    #[serde(rename = "origfee", with = "bitcoin::amount::serde::as_btc")]
+    original_fee: Amount,
+    #[serde(with = "bitcoin::amount::serde::as_btc")]
+    fee: Amount,
+    #[serde(default)]
+    errors: Vec<String>,
+}
+
+/// Replace the unconfirmed wallet transaction `txid` with one paying `fee_rate`.
+///
+/// The BIP125 checks run before either method: the transaction must signal replaceability and
+/// have no descendants in the mempool, and `fee_rate` must beat its current fee rate by at least
+/// the node's incremental relay fee. Wallets with private keys sign and broadcast the
+/// replacement; watch-only wallets get a PSBT for their cosigners instead.
+pub fn bump_fee(wallet_name: &str, txid: &Txid, fee_rate: FeeRate, method: BumpMethod, settings: &Settings) -> Result<BumpedTx, FeeBumpError> {
+    let wallet: Wallet = Wallet::new(wallet_name, settings)?;
+    let wallet_tx = wallet.client().get_transaction(txid, Some(true))?;
+    if wallet_tx.info.confirmations > 0 {
+        return Err(FeeBumpError::AlreadyConfirmed(*txid));
+    }
+    let tx: Transaction = wallet_tx.transaction()?;
+    if !tx.is_explicitly_rbf() {
+        return Err(FeeBumpError::NotReplaceable(*txid));
+    }
+
+    let entry = wallet.client().get_mempool_entry(txid)?;
+    if entry.descendant_count > 1 {
+        return Err(FeeBumpError::HasDescendants(*txid, entry.descendant_count - 1));
+    }
+
+    let original_fee = entry.fees.base;
+    let original_rate = FeeRate::from_sat_per_kwu((original_fee.to_sat() * 1000).div_ceil(tx.weight().to_wu()));
+    let incremental = incremental_relay_fee(&wallet)?;
+    let min_rate = FeeRate::from_sat_per_kwu(original_rate.to_sat_per_kwu() + incremental.to_sat_per_kwu());
+    if fee_rate < min_rate {
+        return Err(FeeBumpError::FeeRateTooLow(fee_rate, min_rate));
+    }
+
+    let signer = wallet.get_wallet_info()?.private_keys_enabled;
+    let bumped = match method {
+        BumpMethod::Node => bump_with_node(&wallet, txid, fee_rate, signer)?,
+        BumpMethod::Change => bump_from_change(&wallet, &tx, original_fee, fee_rate, incremental, signer)?,
+    };
+
+    match bumped.txid {
+        Some(new_txid) => info!("Replaced {} with {}, fee {} -> {}", txid, new_txid, bumped.original_fee, bumped.fee),
+        None => info!("Created replacement PSBT for {}, fee {} -> {}", txid, bumped.original_fee, bumped.fee),
+    }
+    Ok(bumped)
+}
+
+fn bump_with_node(wallet: &Wallet, txid: &Txid, fee_rate: FeeRate, signer: bool) -> Result<BumpedTx, FeeBumpError> {
+    let command = if signer { "bumpfee" } else { "psbtbumpfee" };
+    let options = json!({ "fee_rate": sat_per_vb(fee_rate) });
+    let result: NodeBumpResult = wallet.client().call(command, &[json!(txid), options])?;
+
+    for error in &result.errors {
+        warn!("{}: {}", command, error);
+    }
+    if result.txid.is_none() && result.psbt.is_none() {
+        return Err(FeeBumpError::BumpFailed(result.errors.join("; ")));
+    }
+
+    Ok(BumpedTx {
+        replaced_txid: *txid,
+        txid: result.txid,
+        psbt: result.psbt,
+        original_fee: result.original_fee,
+        fee: result.fee,
+    })
+}
+
+/// Fee of a replacement that keeps the inputs and payments, and what is left of its change.
+#[derive(Debug, PartialEq, Eq)]
+struct ChangeBump {
+    fee: Amount,
+    /// New change value, `None` when the change output is dropped
+    change: Option<Amount>,
+}
+
+/// Take the extra fee for `fee_rate` out of output `change_vout` of `tx`. The new fee must also
+/// be at least the original fee plus the incremental relay fee for the replacement's size
+/// (BIP125 rules 3 and 4). Change that would fall below dust is dropped and goes to the fee,
+/// provided the transaction keeps another output and the fee still covers the smaller size.
+fn change_bump(tx: &Transaction, change_vout: usize, original_fee: Amount, fee_rate: FeeRate, incremental: FeeRate) -> Result<ChangeBump, FeeBumpError> {
+    let required = |weight: Weight| fee_for_weight(fee_rate, weight).max(original_fee + fee_for_weight(incremental, weight));
+
+    let change = &tx.output[change_vout];
+    let weight = tx.weight();
+    let fee = required(weight);
+    let extra = fee - original_fee;
+    if let Some(new_change) = change.value.checked_sub(extra).filter(|value| *value >= change.script_pubkey.dust_value()) {
+        return Ok(ChangeBump { fee, change: Some(new_change) });
+    }
+
+    let dropped_fee = original_fee + change.value;
+    if tx.output.len() > 1 && dropped_fee >= required(weight - output_weight(&change.script_pubkey)) {
+        return Ok(ChangeBump { fee: dropped_fee, change: None });
+    }
+    Err(FeeBumpError::InsufficientChange(change.value, extra))
+}
+
+/// Lower the change output so the same transaction pays `fee_rate`, see [`change_bump`].
+fn bump_from_change(wallet: &Wallet, tx: &Transaction, original_fee: Amount, fee_rate: FeeRate, incremental: FeeRate, signer: bool) -> Result<BumpedTx, FeeBumpError> {
+    let txid = tx.txid();
+    let change_vout = change_output(wallet, tx)?;
+    let ChangeBump { fee, change } = change_bump(tx, change_vout, original_fee, fee_rate, incremental)?;
+
+    let mut replacement = tx.clone();
+    match change {
+        Some(value) => replacement.output[change_vout].value = value,
+        None => {
+            info!("Change of {} would fall below dust, dropping it", tx.output[change_vout].value);
+            replacement.output.remove(change_vout);
+        }
+    }
+    for input in &mut replacement.input {
+        input.script_sig = ScriptBuf::new();
+        input.witness = Witness::default();
+    }
+
+    if !signer {
+        let psbt = Psbt::from_unsigned_tx(replacement).map_err(|e| FeeBumpError::BumpFailed(e.to_string()))?;
+        let updated = wallet.update_psbt(&psbt.to_string())?;
+        return Ok(BumpedTx {
+            replaced_txid: txid,
+            txid: None,
+            psbt: Some(updated.psbt),
+            original_fee,
+            fee,
+        });
+    }
+
+    let signed = wallet.sign_tx(&replacement)?;
+    let new_txid = wallet.client().send_raw_transaction(&signed)?;
+    Ok(BumpedTx {
+        replaced_txid: txid,
+        txid: Some(new_txid),
+        psbt: None,
+        original_fee,
+        fee,
+    })
+}
+
+/// The output paying back to the wallet. Addresses the wallet marks as change win; otherwise
+/// it must be the only output of several that belongs to the wallet.
+fn change_output(wallet: &Wallet, tx: &Transaction) -> Result<usize, FeeBumpError> {
+    let network = wallet.network();
+    let mut owned: Vec<(usize, bool)> = Vec::new();
+    for (vout, output) in tx.output.iter().enumerate() {
+        let Ok(address) = Address::from_script(&output.script_pubkey, network) else {
+            continue;
+        };
+        let info: Value = wallet.client().call("getaddressinfo", &[json!(address)])?;
+        if info["ismine"].as_bool().unwrap_or(false) {
+            owned.push((vout, info["ischange"].as_bool().unwrap_or(false)));
+        }
+    }
+
+    let marked: Vec<usize> = owned.iter().filter(|(_, is_change)| *is_change).map(|(vout, _)| *vout).collect();
+    match (marked.as_slice(), owned.as_slice()) {
+        ([vout], _) => Ok(*vout),
+        ([], [(vout, _)]) if tx.output.len() > 1 => Ok(*vout),
+        _ => Err(FeeBumpError::NoChangeOutput(tx.txid())),
+    }
+}
+
+/// The node's `-incrementalrelayfee`, the minimum fee rate increase of a replacement.
+fn incremental_relay_fee(wallet: &Wallet) -> Result<FeeRate, FeeBumpError> {
+    let network_info: Value = wallet.client().call("getnetworkinfo", &[])?;
+    // BTC/kvB, 1 sat/kvB is a quarter of a sat per 1000 weight units
+    let btc_per_kvb = network_info["incrementalfee"].as_f64().unwrap_or(0.00001);
+    Ok(FeeRate::from_sat_per_kwu((btc_per_kvb * 100_000_000.0 / 4.0).round() as u64))
+}
+
+fn sat_per_vb(fee_rate: FeeRate) -> f64 {
+    fee_rate.to_sat_per_kwu() as f64 / 250.0
+}
+
+/// Spend output `vout` of `parent_txid` (by default the first one paying the wallet) back to the
+/// wallet, paying enough fee for the child, the parent and the parent's unconfirmed ancestors
+/// to reach `fee_rate` together.
+///
+/// The package size and fee come from the parent's mempool entry. When the parent and its
+/// ancestors already pay `fee_rate` no child is needed, and none is built. With `submit_package` both
+/// transactions are sent with `submitpackage`, which also works for a parent the mempool
+/// rejected for its low fee; the wallet's copy of the parent is used then, and its ancestors
+/// are not counted.
+pub fn cpfp(wallet_name: &str, parent_txid: &Txid, vout: Option<u32>, fee_rate: FeeRate, submit_package: bool, settings: &Settings) -> Result<CpfpTx, FeeBumpError> {
+    let wallet: Wallet = Wallet::new(wallet_name, settings)?;
+    if !wallet.get_wallet_info()?.private_keys_enabled {
+        return Err(FeeBumpError::NoPrivateKeys(wallet_name.to_string()));
+    }
+
+    let (parent, in_mempool, ancestors_vsize, ancestors_fee) = match get_tx(&parent_txid.to_string(), settings) {
+        Ok(parent) => {
+            if parent.confirmations.unwrap_or(0) > 0 {
+                return Err(FeeBumpError::AlreadyConfirmed(*parent_txid));
+            }
+            let entry = wallet.client().get_mempool_entry(parent_txid)?;
+            (parent.transaction()?, true, entry.ancestor_size, entry.fees.ancestor)
+        }
+        Err(err) if submit_package => {
+            info!("Parent {} not found on the node ({}), using the wallet's copy", parent_txid, err);
+            let wallet_tx = wallet.client().get_transaction(parent_txid, Some(true))?;
+            let fee = wallet_tx.fee
+                .map(|fee| Amount::from_sat(fee.to_sat().unsigned_abs()))
+                .ok_or(FeeBumpError::ParentNotInMempool(*parent_txid))?;
+            let parent = wallet_tx.transaction()?;
+            let vsize = parent.vsize() as u64;
+            (parent, false, vsize, fee)
+        }
+        Err(err) => return Err(err.into()),
+    };
+
+    let ancestors_weight = Weight::from_vb_unchecked(ancestors_vsize);
+    if in_mempool && pays_fee_rate(ancestors_weight, ancestors_fee, fee_rate) {
+        info!("{} and its ancestors already pay {:.2} sat/vB, no child needed", parent_txid, sat_per_vb(fee_rate));
+        return Ok(CpfpTx {
+            parent_txid: *parent_txid,
+            txid: None,
+            hex: None,
+            fee: Amount::ZERO,
+            package_vsize: ancestors_vsize,
+            package_fee: ancestors_fee,
+            package_fee_rate: ancestors_fee.to_sat() as f64 / ancestors_vsize as f64,
+            package_submitted: false,
+        });
+    }
+
+    let utxo = parent_output(&wallet, &parent, in_mempool, vout)?;
+    let outpoint = OutPoint::new(*parent_txid, utxo.vout);
+    let destination = wallet.new_address(&AddressType::Bech32)?;
+
+    let child_weight = TX_OVERHEAD_WEIGHT + input_weight(&utxo) + output_weight(&destination.script_pubkey());
+    let package_weight = ancestors_weight + child_weight;
+    let fee = child_fee(ancestors_weight, ancestors_fee, child_weight, fee_rate);
+    let value = utxo.amount.checked_sub(fee)
+        .filter(|value| *value >= destination.script_pubkey().dust_value())
+        .ok_or(FeeBumpError::InsufficientChange(utxo.amount, fee))?;
+
+    let child = Transaction {
+        version: Version::TWO,
+        lock_time: LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: outpoint,
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+            witness: Witness::default(),
+        }],
+        output: vec![TxOut {
+            value,
+            script_pubkey: destination.script_pubkey(),
+        }],
+    };
+    let signed = wallet.sign_tx(&child)?;
+    let txid = signed.txid();
+
+    if submit_package {
+        let result: Value = wallet.client()
+            .call("submitpackage", &[json!([serialize(&parent).raw_hex(), serialize(&signed).raw_hex()])])?;
+        // older nodes report no package_msg, only per-transaction errors
+        let failed = result["package_msg"].as_str().is_some_and(|message| message != "success")
+            || result["tx-results"].as_object()
+                .is_some_and(|results| results.values().any(|tx_result| !tx_result["error"].is_null()));
+        if failed {
+            return Err(FeeBumpError::PackageRejected(result.to_string()));
+        }
+    } else {
+        wallet.client().send_raw_transaction(&signed)?;
+    }
+
+    let package_vsize = package_weight.to_vbytes_ceil();
+    let package_fee = ancestors_fee + fee;
+    info!("Child {} pays {} for {} vB package at {:.2} sat/vB", txid, fee, package_vsize, package_fee.to_sat() as f64 / package_vsize as f64);
+
+    Ok(CpfpTx {
+        parent_txid: *parent_txid,
+        txid: Some(txid),
+        hex: Some(serialize(&signed).raw_hex()),
+        fee,
+        package_vsize,
+        package_fee,
+        package_fee_rate: package_fee.to_sat() as f64 / package_vsize as f64,
+        package_submitted: submit_package,
+    })
+}
+
+/// Whether the parent and its unconfirmed ancestors already pay `fee_rate` on their own.
+fn pays_fee_rate(ancestors_weight: Weight, ancestors_fee: Amount, fee_rate: FeeRate) -> bool {
+    ancestors_fee >= fee_for_weight(fee_rate, ancestors_weight)
+}
+
+/// Fee a child of `child_weight` pays so that it, the parent and the parent's ancestors reach
+/// `fee_rate` together. It never pays less than its own share, even when the ancestors already
+/// pay the fee rate.
+fn child_fee(ancestors_weight: Weight, ancestors_fee: Amount, child_weight: Weight, fee_rate: FeeRate) -> Amount {
+    fee_for_weight(fee_rate, ancestors_weight + child_weight)
+        .checked_sub(ancestors_fee)
+        .unwrap_or(Amount::ZERO)
+        .max(fee_for_weight(fee_rate, child_weight))
+}
+
+/// The parent output the child spends: output `vout`, or the first one paying the wallet.
+///
+/// Outputs of a parent in the mempool are taken from `listunspent`, which leaves out spent ones.
+/// The wallet does not list outputs of a parent outside the mempool, so those are read from
+/// the parent itself.
+fn parent_output(wallet: &Wallet, parent: &Transaction, in_mempool: bool, vout: Option<u32>) -> Result<ListUnspentResultEntry, FeeBumpError> {
+    let txid = parent.txid();
+    let utxo = if in_mempool {
+        wallet.client()
+            .list_unspent(Some(0), None, None, Some(true), None)?
+            .into_iter()
+            .find(|utxo| utxo.txid == txid && vout.is_none_or(|vout| utxo.vout == vout))
+    } else {
+        owned_output(wallet, parent, vout)?
+    };
+
+    match (utxo, vout) {
+        (Some(utxo), _) => Ok(utxo),
+        (None, Some(vout)) => Err(FeeBumpError::NotWalletOutput(OutPoint::new(txid, vout))),
+        (None, None) => Err(FeeBumpError::NoWalletOutput(txid)),
+    }
+}
+
+fn owned_output(wallet: &Wallet, parent: &Transaction, vout: Option<u32>) -> Result<Option<ListUnspentResultEntry>, FeeBumpError> {
+    for (index, output) in parent.output.iter().enumerate() {
+        let index = index as u32;
+        if vout.is_some_and(|vout| vout != index) {
+            continue;
+        }
+        let Ok(address) = Address::from_script(&output.script_pubkey, wallet.network()) else {
+            continue;
+        };
+        let info: Value = wallet.client().call("getaddressinfo", &[json!(address)])?;
+        if !info["ismine"].as_bool().unwrap_or(false) {
+            continue;
+        }
+
+        return Ok(Some(ListUnspentResultEntry {
+            txid: parent.txid(),
+            vout: index,
+            address: Some(address.as_unchecked().clone()),
+            label: None,
+            redeem_script: None,
+            witness_script: None,
+            script_pub_key: output.script_pubkey.clone(),
+            amount: output.value,
+            confirmations: 0,
+            spendable: true,
+            solvable: true,
+            descriptor: info["desc"].as_str().map(str::to_string),
+            safe: false,
+        }));
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::hashes::Hash;
+
+    /// One P2WPKH input paying a P2WPKH recipient and P2WPKH change, 562 WU (140.5 vB).
+    fn payment(change: u64) -> Transaction {
+        let p2wpkh = |byte: u8| ScriptBuf::new_p2wpkh(&bitcoin::WPubkeyHash::from_byte_array([byte; 20]));
+        Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(Txid::all_zeros(), 0),
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                witness: Witness::from_slice(&[vec![0; 72], vec![2; 33]]),
+            }],
+            output: vec![
+                TxOut { value: Amount::from_sat(100_000), script_pubkey: p2wpkh(1) },
+                TxOut { value: Amount::from_sat(change), script_pubkey: p2wpkh(2) },
+            ],
+        }
+    }
+
+    fn sat_vb(rate: u64) -> FeeRate {
+        FeeRate::from_sat_per_vb_unchecked(rate)
+    }
+
+    /// Package fee rate in sat/vB.
+    fn package_rate(fees: &[Amount], vsizes: &[u64]) -> f64 {
+        fees.iter().map(|fee| fee.to_sat()).sum::<u64>() as f64 / vsizes.iter().sum::<u64>() as f64
+    }
+
+    #[test]
+    fn child_brings_the_package_to_the_fee_rate() {
+        // a 200 vB parent paying 1 sat/vB and a 110 vB child, bumped to 20 sat/vB
+        let (parent_vsize, parent_fee, child_vsize) = (200, Amount::from_sat(200), 110);
+        let (parent_weight, child_weight) = (Weight::from_vb_unchecked(parent_vsize), Weight::from_vb_unchecked(child_vsize));
+        assert!(!pays_fee_rate(parent_weight, parent_fee, sat_vb(20)));
+
+        let fee = child_fee(parent_weight, parent_fee, child_weight, sat_vb(20));
+        assert_eq!(fee, Amount::from_sat(20 * 310 - 200));
+        assert!(package_rate(&[parent_fee, fee], &[parent_vsize, child_vsize]) >= 20.0);
+
+        // odd weights round up to a whole satoshi rather than falling short
+        let fee = child_fee(Weight::from_wu(801), Amount::from_sat(201), Weight::from_wu(561), sat_vb(3));
+        assert_eq!(fee, Amount::from_sat(1_022 - 201));
+        assert!((201 + fee.to_sat()) as f64 / (1_362.0 / 4.0) >= 3.0);
+    }
+
+    #[test]
+    fn parent_already_paying_the_fee_rate_needs_no_child() {
+        let (parent_weight, parent_fee) = (Weight::from_vb_unchecked(200), Amount::from_sat(4_000));
+        assert!(pays_fee_rate(parent_weight, parent_fee, sat_vb(20)));
+        assert!(!pays_fee_rate(parent_weight, parent_fee, sat_vb(21)));
+
+        // a child built anyway, for a parent outside the mempool, still pays its own share
+        let fee = child_fee(parent_weight, parent_fee, Weight::from_vb_unchecked(110), sat_vb(10));
+        assert_eq!(fee, Amount::from_sat(1_100));
+    }
+
+    #[test]
+    fn change_absorbs_the_bump() {
+        let tx = payment(50_000);
+        assert_eq!(tx.weight(), Weight::from_wu(562));
+
+        // 10 sat/vB beats the original 141 sat plus 1 sat/vB for the replacement's size
+        let bump = change_bump(&tx, 1, Amount::from_sat(141), sat_vb(10), sat_vb(1)).unwrap();
+        assert_eq!(bump, ChangeBump { fee: Amount::from_sat(1_405), change: Some(Amount::from_sat(48_736)) });
+
+        // a small step up is held to rule 4: the 1546 sat of 11 sat/vB is not enough, the old
+        // fee plus 5 sat/vB of incremental relay fee is
+        let bump = change_bump(&tx, 1, Amount::from_sat(1_410), sat_vb(11), sat_vb(5)).unwrap();
+        assert_eq!(bump.fee, Amount::from_sat(1_410 + 703));
+        assert_eq!(bump.change, Some(Amount::from_sat(50_000 - 703)));
+        assert!(bump.fee >= Amount::from_sat(1_410) + fee_for_weight(sat_vb(5), tx.weight()));
+    }
+
+    #[test]
+    fn dust_change_is_dropped() {
+        // 140.5 vB at 10 sat/vB needs 1264 sat more, leaving 236 sat, below the 294 sat dust limit
+        let tx = payment(1_500);
+        let bump = change_bump(&tx, 1, Amount::from_sat(141), sat_vb(10), sat_vb(1)).unwrap();
+        assert_eq!(bump, ChangeBump { fee: Amount::from_sat(1_641), change: None });
+
+        // without the 31 vB change output the replacement is 109.5 vB and still pays 10 sat/vB
+        let dropped = tx.weight() - output_weight(&tx.output[1].script_pubkey);
+        assert!(bump.fee >= fee_for_weight(sat_vb(10), dropped));
+    }
+
+    #[test]
+    fn insufficient_change_is_an_error() {
+        // dropping 300 sat of change pays 441 sat, short of 109.5 vB at 10 sat/vB
+        let tx = payment(300);
+        assert!(matches!(
+            change_bump(&tx, 1, Amount::from_sat(141), sat_vb(10), sat_vb(1)),
+            Err(FeeBumpError::InsufficientChange(change, extra))
+                if change == Amount::from_sat(300) && extra == Amount::from_sat(1_264)
+        ));
+
+        // a lone change output cannot be dropped
+        let mut sweep = payment(1_500);
+        sweep.output.remove(0);
+        assert!(matches!(
+            change_bump(&sweep, 0, Amount::from_sat(110), sat_vb(20), sat_vb(1)),
+            Err(FeeBumpError::InsufficientChange(..))
+        ));
+    }
+}
@@ -1,6 +1,15 @@
 pub mod client;
 pub mod bitcoind;
+pub mod context;
 pub mod errors;
+pub mod fee_bump;
+pub mod multisig;
+pub mod payouts;
+pub mod policy;
+pub mod psbt_analysis;
+pub mod rpcauth;
+pub mod script_debug;
+pub mod simulation;
 pub mod wallet;
 pub mod wallet_ops;
-pub mod verification;
\ No newline at end of file
+pub mod verification;
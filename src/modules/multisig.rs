@@ -0,0 +1,512 @@
+use std::fmt;
+use std::str::FromStr;
+
+use bitcoin::base58;
+use bitcoin::bip32::{DerivationPath, Fingerprint, Xpub};
+use bitcoin::Network;
+use serde::{Serialize, Serializer};
+
+use crate::utils::utils::{MultisigScriptType, NUMS_INTERNAL_KEY};
+
+use super::errors::MultisigError;
+
+/// SLIP-132 version bytes of mainnet extended public keys (xpub, ypub, zpub, Ypub, Zpub).
+const MAINNET_XPUB_VERSIONS: [[u8; 4]; 5] = [
+    [0x04, 0x88, 0xb2, 0x1e],
+    [0x04, 0x9d, 0x7c, 0xb2],
+    [0x04, 0xb2, 0x47, 0x46],
+    [0x02, 0x95, 0xb4, 0x3f],
+    [0x02, 0xaa, 0x7e, 0xd3],
+];
+
+/// SLIP-132 version bytes of testnet extended public keys (tpub, upub, vpub, Upub, Vpub).
+const TESTNET_XPUB_VERSIONS: [[u8; 4]; 5] = [
+    [0x04, 0x35, 0x87, 0xcf],
+    [0x04, 0x4a, 0x52, 0x62],
+    [0x04, 0x5f, 0x1c, 0xf6],
+    [0x02, 0x42, 0x89, 0xef],
+    [0x02, 0x57, 0x54, 0x83],
+];
+
+const BSMS_VERSION: &str = "BSMS 1.0";
+const BSMS_PATH_RESTRICTIONS: &str = "/0/*,/1/*";
+
+/// File formats a multisig wallet can be exported to and imported from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WalletFileFormat {
+    /// BIP-129 descriptor record
+    Bsms,
+    /// The `Name:`/`Policy:`/`Format:` text file read by Coldcard and Sparrow
+    Coldcard,
+}
+
+/// A cosigner's account xpub together with its key origin, `[fingerprint/path]xpub`.
+///
+/// Keys without an origin are their own master: the fingerprint is the xpub's and the path is `m`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CosignerKey {
+    pub fingerprint: Fingerprint,
+    pub path: DerivationPath,
+    pub xpub: Xpub,
+}
+
+impl CosignerKey {
+    /// Fail unless the xpub was encoded for `network`; every test network shares the tpub prefix.
+    pub fn require_network(&self, network: Network) -> Result<(), MultisigError> {
+        let expected = match network {
+            Network::Bitcoin => Network::Bitcoin,
+            _ => Network::Testnet,
+        };
+        if self.xpub.network != expected {
+            return Err(MultisigError::KeyNetworkMismatch(self.xpub.to_string(), network));
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for CosignerKey {
+    type Err = MultisigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || MultisigError::InvalidKey(s.to_string());
+
+        let (origin, xpub) = match s.strip_prefix('[') {
+            Some(rest) => {
+                let (origin, xpub) = rest.split_once(']').ok_or_else(invalid)?;
+                (Some(origin), xpub)
+            }
+            None => (None, s),
+        };
+        if xpub.contains('/') {
+            return Err(MultisigError::UnsupportedKeyPath(s.to_string()));
+        }
+        let xpub = parse_xpub(xpub).ok_or_else(invalid)?;
+
+        let (fingerprint, path) = match origin {
+            Some(origin) => {
+                let (fingerprint, path) = origin.split_once('/').unwrap_or((origin, ""));
+                let fingerprint = Fingerprint::from_str(fingerprint).map_err(|_| invalid())?;
+                let path = match path {
+                    "" => DerivationPath::master(),
+                    path => DerivationPath::from_str(&format!("m/{}", path)).map_err(|_| invalid())?,
+                };
+                (fingerprint, path)
+            }
+            None => (xpub.fingerprint(), DerivationPath::master()),
+        };
+
+        Ok(CosignerKey { fingerprint, path, xpub })
+    }
+}
+
+impl fmt::Display for CosignerKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // `{:#}` prints hardened steps as `h`, which needs no quoting in a shell
+        let path = format!("{:#}", self.path);
+        write!(f, "[{}{}]{}", self.fingerprint, path.trim_start_matches('m'), self.xpub)
+    }
+}
+
+impl Serialize for CosignerKey {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Decode an xpub, accepting the SLIP-132 ypub/zpub/Ypub/Zpub variants some signers export
+/// and normalizing them to xpub/tpub, the only versions descriptors allow.
+fn parse_xpub(s: &str) -> Option<Xpub> {
+    let mut data = base58::decode_check(s).ok()?;
+    if data.len() != 78 {
+        return None;
+    }
+    let version: [u8; 4] = data[..4].try_into().ok()?;
+    if MAINNET_XPUB_VERSIONS.contains(&version) {
+        data[..4].copy_from_slice(&MAINNET_XPUB_VERSIONS[0]);
+    } else if TESTNET_XPUB_VERSIONS.contains(&version) {
+        data[..4].copy_from_slice(&TESTNET_XPUB_VERSIONS[0]);
+    }
+    Xpub::decode(&data).ok()
+}
+
+/// Threshold, script type and cosigner keys of a sorted multisig wallet.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct MultisigConfig {
+    pub nrequired: u32,
+    pub script_type: MultisigScriptType,
+    pub keys: Vec<CosignerKey>,
+}
+
+impl MultisigConfig {
+    /// Receive (`change == false`) or change descriptor, without checksum.
+    pub fn descriptor(&self, change: bool) -> String {
+        let branch = if change { "/1/*" } else { "/0/*" };
+        self.descriptor_with_suffix(branch)
+    }
+
+    fn descriptor_with_suffix(&self, suffix: &str) -> String {
+        let keys: Vec<String> = self.keys.iter().map(|key| format!("{}{}", key, suffix)).collect();
+        self.script_type.descriptor(self.nrequired, &keys)
+    }
+
+    /// Parse a sorted multisig descriptor as listed by `listdescriptors` or found in a BSMS record.
+    /// Keys may end in `/0/*`, `/1/*`, `/<0;1>/*` or `/**`; the branch is dropped.
+    pub fn from_descriptor(descriptor: &str) -> Result<Self, MultisigError> {
+        let unsupported = || MultisigError::UnsupportedDescriptor(descriptor.to_string());
+
+        let desc = descriptor.split('#').next().unwrap_or_default().trim();
+        let (script_type, inner) = split_multisig_descriptor(desc).ok_or_else(unsupported)?;
+
+        let mut parts = inner.split(',');
+        let nrequired: u32 = parts.next()
+            .and_then(|n| n.parse().ok())
+            .ok_or_else(unsupported)?;
+
+        let keys = parts
+            .map(|key| {
+                let key = ["/**", "/0/*", "/1/*", "/<0;1>/*"].iter()
+                    .find_map(|suffix| key.strip_suffix(suffix))
+                    .unwrap_or(key);
+                CosignerKey::from_str(key)
+            })
+            .collect::<Result<Vec<CosignerKey>, MultisigError>>()?;
+
+        Ok(MultisigConfig { nrequired, script_type, keys })
+    }
+
+    /// Fail unless every key belongs to `network`.
+    pub fn require_network(&self, network: Network) -> Result<(), MultisigError> {
+        self.keys.iter().try_for_each(|key| key.require_network(network))
+    }
+
+    /// BIP-129 descriptor record: version, descriptor template, path restrictions and first address.
+    pub fn to_bsms(&self, first_address: &str) -> String {
+        let template = self.descriptor_with_suffix("/**");
+        let checksum = descriptor_checksum(&template).unwrap_or_default();
+        format!("{}\n{}#{}\n{}\n{}\n", BSMS_VERSION, template, checksum, BSMS_PATH_RESTRICTIONS, first_address)
+    }
+
+    /// Coldcard/Sparrow multisig setup file. A shared derivation goes in the header,
+    /// otherwise each key is preceded by its own `Derivation:` line.
+    pub fn to_coldcard(&self, name: &str) -> Result<String, MultisigError> {
+        let format = match self.script_type {
+            MultisigScriptType::Wsh => "P2WSH",
+            MultisigScriptType::ShWsh => "P2SH-P2WSH",
+            MultisigScriptType::Sh => "P2SH",
+            MultisigScriptType::Tr => return Err(MultisigError::UnsupportedScriptType(self.script_type, WalletFileFormat::Coldcard)),
+        };
+
+        let shared_path = self.keys.first()
+            .map(|first| &first.path)
+            .filter(|path| self.keys.iter().all(|key| &key.path == *path));
+
+        let mut file = String::new();
+        file.push_str("# Multisig setup file exported by btc-dev-utils\n#\n");
+        file.push_str(&format!("Name: {}\n", name));
+        file.push_str(&format!("Policy: {} of {}\n", self.nrequired, self.keys.len()));
+        if let Some(path) = shared_path {
+            file.push_str(&format!("Derivation: {}\n", path));
+        }
+        file.push_str(&format!("Format: {}\n", format));
+        for key in &self.keys {
+            file.push('\n');
+            if shared_path.is_none() {
+                file.push_str(&format!("Derivation: {}\n", key.path));
+            }
+            file.push_str(&format!("{}: {}\n", key.fingerprint.to_string().to_uppercase(), key.xpub));
+        }
+        Ok(file)
+    }
+}
+
+fn split_multisig_descriptor(desc: &str) -> Option<(MultisigScriptType, &str)> {
+    let wrapped = [
+        (MultisigScriptType::ShWsh, "sh(wsh(sortedmulti(", ")))"),
+        (MultisigScriptType::Wsh, "wsh(sortedmulti(", "))"),
+        (MultisigScriptType::Sh, "sh(sortedmulti(", "))"),
+    ];
+    if let Some(found) = wrapped.iter().find_map(|(script_type, prefix, suffix)| {
+        desc.strip_prefix(prefix)?.strip_suffix(suffix).map(|inner| (*script_type, inner))
+    }) {
+        return Some(found);
+    }
+
+    // tr(<internal key>,sortedmulti_a(...)), only spendable through the script path
+    let tree = desc.strip_prefix("tr(")?.strip_prefix(NUMS_INTERNAL_KEY)?.strip_prefix(',')?;
+    let inner = tree.strip_prefix("sortedmulti_a(")?.strip_suffix("))")?;
+    Some((MultisigScriptType::Tr, inner))
+}
+
+/// A multisig wallet read from a BSMS record or a Coldcard/Sparrow setup file.
+#[derive(Debug)]
+pub struct WalletFile {
+    pub format: WalletFileFormat,
+    /// Wallet name, only present in Coldcard files
+    pub name: Option<String>,
+    pub config: MultisigConfig,
+    /// First receive address, only present in BSMS records
+    pub first_address: Option<String>,
+}
+
+/// Parse a wallet file, telling the formats apart by the `BSMS` header line.
+pub fn parse_wallet_file(contents: &str) -> Result<WalletFile, MultisigError> {
+    let is_bsms = contents.lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .is_some_and(|line| line.starts_with("BSMS"));
+    if is_bsms {
+        parse_bsms(contents)
+    } else {
+        parse_coldcard(contents)
+    }
+}
+
+fn parse_bsms(contents: &str) -> Result<WalletFile, MultisigError> {
+    let mut lines = contents.lines().map(str::trim).filter(|line| !line.is_empty());
+    let mut next_line = |what: &str| lines.next().ok_or_else(|| MultisigError::MalformedFile(format!("BSMS record is missing the {}", what)));
+
+    let version = next_line("version")?;
+    if version != BSMS_VERSION {
+        return Err(MultisigError::MalformedFile(format!("unsupported BSMS version {}", version)));
+    }
+
+    let descriptor = next_line("descriptor template")?;
+    if let Some((template, checksum)) = descriptor.split_once('#') {
+        if descriptor_checksum(template).as_deref() != Some(checksum) {
+            return Err(MultisigError::ChecksumMismatch(descriptor.to_string()));
+        }
+    }
+
+    let restrictions = next_line("path restrictions")?;
+    if restrictions != BSMS_PATH_RESTRICTIONS && restrictions != "No path restrictions" {
+        return Err(MultisigError::MalformedFile(format!("unsupported path restrictions {}", restrictions)));
+    }
+
+    let first_address = next_line("first address")?.to_string();
+
+    Ok(WalletFile {
+        format: WalletFileFormat::Bsms,
+        name: None,
+        config: MultisigConfig::from_descriptor(descriptor)?,
+        first_address: Some(first_address),
+    })
+}
+
+fn parse_coldcard(contents: &str) -> Result<WalletFile, MultisigError> {
+    let mut name = None;
+    let mut policy = None;
+    let mut script_type = MultisigScriptType::Sh;
+    let mut path: Option<DerivationPath> = None;
+    let mut keys = Vec::new();
+
+    for line in contents.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (label, value) = line.split_once(':')
+            .ok_or_else(|| MultisigError::MalformedFile(format!("unexpected line {}", line)))?;
+        let value = value.trim();
+
+        match label.trim().to_lowercase().as_str() {
+            "name" => name = Some(value.to_string()),
+            "policy" => policy = Some(parse_policy(value)?),
+            "derivation" => {
+                let parsed = DerivationPath::from_str(value)
+                    .map_err(|_| MultisigError::MalformedFile(format!("invalid derivation {}", value)))?;
+                path = Some(parsed);
+            }
+            "format" => {
+                script_type = match value.to_uppercase().as_str() {
+                    "P2WSH" => MultisigScriptType::Wsh,
+                    "P2SH-P2WSH" | "P2WSH-P2SH" => MultisigScriptType::ShWsh,
+                    "P2SH" => MultisigScriptType::Sh,
+                    _ => return Err(MultisigError::MalformedFile(format!("unsupported format {}", value))),
+                };
+            }
+            label if label.len() == 8 && label.chars().all(|c| c.is_ascii_hexdigit()) => {
+                let path = path.clone()
+                    .ok_or_else(|| MultisigError::MalformedFile(format!("no derivation given for key {}", label)))?;
+                let key = CosignerKey::from_str(value)?;
+                let fingerprint = Fingerprint::from_str(label)
+                    .map_err(|_| MultisigError::InvalidKey(line.to_string()))?;
+                keys.push(CosignerKey { fingerprint, path, xpub: key.xpub });
+            }
+            // other headers (e.g. comments from the exporting software) carry nothing we need
+            _ => {}
+        }
+    }
+
+    let (nrequired, total) = policy.ok_or_else(|| MultisigError::MalformedFile("missing Policy line".to_string()))?;
+    if total != keys.len() {
+        return Err(MultisigError::MalformedFile(format!("policy lists {} keys but the file has {}", total, keys.len())));
+    }
+
+    Ok(WalletFile {
+        format: WalletFileFormat::Coldcard,
+        name,
+        config: MultisigConfig { nrequired, script_type, keys },
+        first_address: None,
+    })
+}
+
+/// Parse an `M of N` policy.
+fn parse_policy(policy: &str) -> Result<(u32, usize), MultisigError> {
+    let invalid = || MultisigError::MalformedFile(format!("invalid policy {}", policy));
+    let (m, n) = policy.split_once(" of ").ok_or_else(invalid)?;
+    let m = m.trim().parse().map_err(|_| invalid())?;
+    let n = n.trim().parse().map_err(|_| invalid())?;
+    Ok((m, n))
+}
+
+// Descriptor checksum, BIP-380
+
+const INPUT_CHARSET: &str = "0123456789()[],'/*abcdefgh@:$%{}IJKLMNOPQRSTUVWXYZ&+-.;<=>?!^_|~ijklmnopqrstuvwxyzABCDEFGH`#\"\\ ";
+const CHECKSUM_CHARSET: &[u8] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+fn poly_mod(mut c: u64, val: u64) -> u64 {
+    let c0 = c >> 35;
+    c = ((c & 0x7ffffffff) << 5) ^ val;
+    if c0 & 1 != 0 { c ^= 0xf5dee51989; }
+    if c0 & 2 != 0 { c ^= 0xa9fdca3312; }
+    if c0 & 4 != 0 { c ^= 0x1bab10e32d; }
+    if c0 & 8 != 0 { c ^= 0x3706b1677a; }
+    if c0 & 16 != 0 { c ^= 0x644d626ffd; }
+    c
+}
+
+/// Checksum of a descriptor without its `#` suffix. Computed locally because Bitcoin Core
+/// rejects the `/**` keys of BSMS templates and miniscript 9 keeps its `desc_checksum` private.
+/// `None` if the descriptor has invalid characters.
+pub fn descriptor_checksum(desc: &str) -> Option<String> {
+    let mut c = 1u64;
+    let mut cls = 0u64;
+    let mut clscount = 0;
+    for ch in desc.chars() {
+        let pos = INPUT_CHARSET.find(ch)? as u64;
+        c = poly_mod(c, pos & 31);
+        cls = cls * 3 + (pos >> 5);
+        clscount += 1;
+        if clscount == 3 {
+            c = poly_mod(c, cls);
+            cls = 0;
+            clscount = 0;
+        }
+    }
+    if clscount > 0 {
+        c = poly_mod(c, cls);
+    }
+    for _ in 0..8 {
+        c = poly_mod(c, 0);
+    }
+    c ^= 1;
+
+    Some((0..8).map(|j| CHECKSUM_CHARSET[((c >> (5 * (7 - j))) & 31) as usize] as char).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::bip32::Xpriv;
+    use bitcoin::secp256k1::Secp256k1;
+    use miniscript::Descriptor;
+
+    use super::*;
+
+    fn cosigner(seed: u8, path: &str) -> CosignerKey {
+        let secp = Secp256k1::new();
+        let master = Xpriv::new_master(Network::Testnet, &[seed; 32]).unwrap();
+        let path = DerivationPath::from_str(path).unwrap();
+        let account = master.derive_priv(&secp, &path).unwrap();
+        CosignerKey { fingerprint: master.fingerprint(&secp), path, xpub: Xpub::from_priv(&secp, &account) }
+    }
+
+    fn config(script_type: MultisigScriptType, paths: [&str; 3]) -> MultisigConfig {
+        let keys = paths.iter().enumerate().map(|(i, path)| cosigner(i as u8 + 1, path)).collect();
+        MultisigConfig { nrequired: 2, script_type, keys }
+    }
+
+    #[test]
+    fn checksum_matches_published_vectors() {
+        // BIP-380 and rust-miniscript test vectors
+        assert_eq!(descriptor_checksum("raw(deadbeef)").as_deref(), Some("89f8spxm"));
+        assert_eq!(
+            descriptor_checksum("sh(multi(2,[00000000/111'/222]xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL,xpub68NZiKmJWnxxS6aaHmn81bvJeTESw724CRDs6HbuccFQN9Ku14VQrADWgqbhhTHBaohPX4CjNLf9fq9MYo6oDaPPLPxSb7gwQN3ih19Zm4Y/0))").as_deref(),
+            Some("tjg09x5t"),
+        );
+        assert_eq!(descriptor_checksum("wsh(pk(\u{e9}))"), None);
+    }
+
+    #[test]
+    fn checksum_agrees_with_miniscript() {
+        // miniscript verifies the checksum when parsing and appends its own when printing
+        for script_type in [MultisigScriptType::Wsh, MultisigScriptType::ShWsh, MultisigScriptType::Sh] {
+            let config = config(script_type, ["m/48'/1'/0'/2'"; 3]);
+            for template in [config.descriptor_with_suffix("/**"), config.descriptor(false), config.descriptor(true)] {
+                let checksum = descriptor_checksum(&template).unwrap();
+                let parsed = Descriptor::<String>::from_str(&format!("{}#{}", template, checksum)).unwrap();
+                assert_eq!(format!("{}", parsed), format!("{}#{}", template, checksum));
+            }
+        }
+    }
+
+    #[test]
+    fn bsms_round_trip() {
+        for script_type in [MultisigScriptType::Wsh, MultisigScriptType::ShWsh, MultisigScriptType::Sh, MultisigScriptType::Tr] {
+            let config = config(script_type, ["m/48'/1'/0'/2'", "m/48'/1'/0'/2'", "m/87'/1'/0'"]);
+            let record = config.to_bsms("tb1qfirstaddress");
+
+            let file = parse_wallet_file(&record).unwrap();
+            assert_eq!(file.format, WalletFileFormat::Bsms);
+            assert_eq!(file.config, config);
+            assert_eq!(file.first_address.as_deref(), Some("tb1qfirstaddress"));
+            assert_eq!(file.name, None);
+        }
+    }
+
+    #[test]
+    fn bsms_rejects_bad_checksum() {
+        let config = config(MultisigScriptType::Wsh, ["m/48'/1'/0'/2'"; 3]);
+        let record = config.to_bsms("tb1qfirstaddress");
+        let (template, checksum) = record.lines().nth(1).unwrap().split_once('#').unwrap();
+        let tampered = record.replace(checksum, &descriptor_checksum(&template.replace("2,", "3,")).unwrap());
+
+        assert!(matches!(parse_wallet_file(&tampered), Err(MultisigError::ChecksumMismatch(_))));
+    }
+
+    #[test]
+    fn coldcard_round_trip_shared_derivation() {
+        for script_type in [MultisigScriptType::Wsh, MultisigScriptType::ShWsh, MultisigScriptType::Sh] {
+            let config = config(script_type, ["m/48'/1'/0'/2'"; 3]);
+            let exported = config.to_coldcard("vault").unwrap();
+            assert_eq!(exported.matches("Derivation:").count(), 1);
+
+            let file = parse_wallet_file(&exported).unwrap();
+            assert_eq!(file.format, WalletFileFormat::Coldcard);
+            assert_eq!(file.name.as_deref(), Some("vault"));
+            assert_eq!(file.config, config);
+            assert_eq!(file.first_address, None);
+        }
+    }
+
+    #[test]
+    fn coldcard_round_trip_per_key_derivation() {
+        let config = config(MultisigScriptType::Wsh, ["m/48'/1'/0'/2'", "m/48'/1'/1'/2'", "m/45'"]);
+        let exported = config.to_coldcard("vault").unwrap();
+        assert_eq!(exported.matches("Derivation:").count(), 3);
+
+        assert_eq!(parse_wallet_file(&exported).unwrap().config, config);
+    }
+
+    #[test]
+    fn coldcard_rejects_taproot() {
+        let config = config(MultisigScriptType::Tr, ["m/87'/1'/0'"; 3]);
+        assert!(matches!(config.to_coldcard("vault"), Err(MultisigError::UnsupportedScriptType(MultisigScriptType::Tr, WalletFileFormat::Coldcard))));
+    }
+
+    #[test]
+    fn coldcard_policy_must_match_keys() {
+        let config = config(MultisigScriptType::Wsh, ["m/48'/1'/0'/2'"; 3]);
+        let exported = config.to_coldcard("vault").unwrap().replace("Policy: 2 of 3", "Policy: 2 of 4");
+        assert!(matches!(parse_wallet_file(&exported), Err(MultisigError::MalformedFile(_))));
+    }
+}
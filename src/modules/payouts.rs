@@ -0,0 +1,219 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use bitcoin::address::NetworkUnchecked;
+use bitcoin::amount::Denomination::Bitcoin;
+use bitcoin::{Address, Amount, Network};
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use super::errors::PayoutError;
+
+/// One output of a batch payment.
+#[derive(Clone, Debug, Serialize)]
+pub struct Payout {
+    pub address: Address,
+    #[serde(with = "bitcoin::amount::serde::as_btc")]
+    pub amount: Amount,
+    pub label: Option<String>,
+}
+
+impl Payout {
+    pub fn new(address: Address, amount: Amount) -> Self {
+        Payout { address, amount, label: None }
+    }
+}
+
+#[derive(Deserialize)]
+struct PayoutEntry {
+    address: String,
+    #[serde(with = "bitcoin::amount::serde::as_btc")]
+    amount: Amount,
+    label: Option<String>,
+}
+
+/// Read a payout file: a JSON array of `{"address", "amount", "label"}` objects when the file
+/// ends in `.json`, otherwise CSV lines of `address,amount[,label]` with an optional header.
+/// Amounts are in BTC.
+///
+/// Every entry is checked before anything is spent: addresses must be valid on `network` and
+/// appear once, amounts must be above the dust limit of their output.
+pub fn load_payouts(path: &Path, network: Network) -> Result<Vec<Payout>, PayoutError> {
+    let contents = fs::read_to_string(path)?;
+    let is_json = path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("json"));
+    let payouts = parse_payouts(&contents, is_json, network)?;
+
+    info!(
+        "Loaded {} payouts totalling {} BTC from {}",
+        payouts.len(),
+        payouts.iter().map(|payout| payout.amount).sum::<Amount>().to_btc(),
+        path.display(),
+    );
+    Ok(payouts)
+}
+
+fn parse_payouts(contents: &str, is_json: bool, network: Network) -> Result<Vec<Payout>, PayoutError> {
+    let entries = match is_json {
+        true => parse_json(contents)?,
+        false => parse_csv(contents)?,
+    };
+    if entries.is_empty() {
+        return Err(PayoutError::NoPayouts);
+    }
+
+    // entry numbers are CSV line numbers, or positions in the JSON array
+    let mut seen: HashMap<Address, usize> = HashMap::new();
+    let mut payouts: Vec<Payout> = Vec::with_capacity(entries.len());
+    for (entry, PayoutEntry { address, amount, label }) in entries {
+        let unchecked = Address::<NetworkUnchecked>::from_str(&address)
+            .map_err(|_| PayoutError::InvalidAddress(entry, address.clone()))?;
+        let address = unchecked.require_network(network)
+            .map_err(|_| PayoutError::WrongNetwork(entry, address.clone(), network))?;
+        if let Some(first) = seen.insert(address.clone(), entry) {
+            return Err(PayoutError::DuplicateAddress(entry, address.to_string(), first));
+        }
+
+        let dust = address.script_pubkey().dust_value();
+        if amount < dust {
+            return Err(PayoutError::BelowDust(entry, amount, dust));
+        }
+
+        let label = label.map(|label| label.trim().to_string()).filter(|label| !label.is_empty());
+        payouts.push(Payout { address, amount, label });
+    }
+    Ok(payouts)
+}
+
+fn parse_json(contents: &str) -> Result<Vec<(usize, PayoutEntry)>, PayoutError> {
+    let entries: Vec<PayoutEntry> = serde_json::from_str(contents)?;
+    Ok(entries.into_iter().enumerate().map(|(i, entry)| (i + 1, entry)).collect())
+}
+
+/// Blank lines and lines starting with `#` are skipped. Anything after the second comma is
+/// the label, so labels may contain commas.
+fn parse_csv(contents: &str) -> Result<Vec<(usize, PayoutEntry)>, PayoutError> {
+    let mut entries = Vec::new();
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut fields = line.splitn(3, ',').map(str::trim);
+        let address = fields.next().unwrap_or_default();
+        let amount = fields.next().ok_or(PayoutError::MissingAmount(i + 1))?;
+        if entries.is_empty() && address.eq_ignore_ascii_case("address") {
+            continue;
+        }
+
+        let amount = Amount::from_str_in(amount, Bitcoin)
+            .map_err(|_| PayoutError::InvalidAmount(i + 1, amount.to_string()))?;
+        let label = fields.next().map(|label| label.trim_matches('"').to_string());
+        entries.push((i + 1, PayoutEntry { address: address.to_string(), amount, label }));
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::opcodes::OP_TRUE;
+    use bitcoin::script::Builder;
+
+    use super::*;
+
+    fn address(i: i64, network: Network) -> Address {
+        Address::p2wsh(&Builder::new().push_int(i).push_opcode(OP_TRUE).into_script(), network)
+    }
+
+    fn csv(contents: &str) -> Result<Vec<Payout>, PayoutError> {
+        parse_payouts(contents, false, Network::Regtest)
+    }
+
+    #[test]
+    fn csv_with_header_comments_and_labels() {
+        let (first, second, third) = (address(1, Network::Regtest), address(2, Network::Regtest), address(3, Network::Regtest));
+        let contents = format!(
+            "address,amount,label\n# team payouts\n\n{},0.5,alice\n{}, 0.00001 , \"bob, carol\"\n{},1\n",
+            first, second, third,
+        );
+
+        let payouts = csv(&contents).unwrap();
+        assert_eq!(payouts.len(), 3);
+        assert_eq!((&payouts[0].address, payouts[0].amount, payouts[0].label.as_deref()), (&first, Amount::from_sat(50_000_000), Some("alice")));
+        assert_eq!((&payouts[1].address, payouts[1].amount, payouts[1].label.as_deref()), (&second, Amount::from_sat(1_000), Some("bob, carol")));
+        assert_eq!((&payouts[2].address, payouts[2].amount, payouts[2].label.as_deref()), (&third, Amount::ONE_BTC, None));
+    }
+
+    #[test]
+    fn json_array() {
+        let contents = format!(
+            r#"[{{"address": "{}", "amount": 0.25, "label": " rent "}}, {{"address": "{}", "amount": 0.0001}}]"#,
+            address(1, Network::Regtest), address(2, Network::Regtest),
+        );
+
+        let payouts = parse_payouts(&contents, true, Network::Regtest).unwrap();
+        assert_eq!(payouts.iter().map(|payout| payout.amount.to_sat()).collect::<Vec<_>>(), [25_000_000, 10_000]);
+        assert_eq!(payouts[0].label.as_deref(), Some("rent"));
+        assert_eq!(payouts[1].label, None);
+    }
+
+    #[test]
+    fn entries_are_numbered_by_line() {
+        let contents = format!("address,amount\n{},0.1\n\n{},lots\n", address(1, Network::Regtest), address(2, Network::Regtest));
+        assert!(matches!(csv(&contents), Err(PayoutError::InvalidAmount(4, amount)) if amount == "lots"));
+
+        let contents = format!("{}\n", address(1, Network::Regtest));
+        assert!(matches!(csv(&contents), Err(PayoutError::MissingAmount(1))));
+    }
+
+    #[test]
+    fn rejects_bad_addresses() {
+        assert!(matches!(csv("not-an-address,0.1"), Err(PayoutError::InvalidAddress(1, _))));
+
+        let mainnet = address(1, Network::Bitcoin);
+        assert!(matches!(csv(&format!("{},0.1", mainnet)), Err(PayoutError::WrongNetwork(1, _, Network::Regtest))));
+    }
+
+    #[test]
+    fn rejects_duplicate_addresses() {
+        let (first, second) = (address(1, Network::Regtest), address(2, Network::Regtest));
+        let contents = format!("{},0.1\n{},0.2\n{},0.3\n", first, second, first);
+        assert!(matches!(csv(&contents), Err(PayoutError::DuplicateAddress(3, address, 1)) if address == first.to_string()));
+    }
+
+    #[test]
+    fn rejects_dust() {
+        // P2WSH: (43 byte output + 67 byte spend) * 3 sat/vB
+        let address = address(1, Network::Regtest);
+        assert!(csv(&format!("{},0.0000033", address)).is_ok());
+        assert!(matches!(
+            csv(&format!("{},0.00000329", address)),
+            Err(PayoutError::BelowDust(1, amount, dust)) if amount.to_sat() == 329 && dust.to_sat() == 330
+        ));
+    }
+
+    #[test]
+    fn rejects_empty_files() {
+        assert!(matches!(csv("address,amount\n# nothing yet\n"), Err(PayoutError::NoPayouts)));
+        assert!(matches!(parse_payouts("[]", true, Network::Regtest), Err(PayoutError::NoPayouts)));
+    }
+
+    #[test]
+    fn format_follows_the_file_extension() {
+        let dir = std::env::temp_dir().join(format!("btc-dev-utils-payouts-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let address = address(1, Network::Regtest);
+
+        let json = dir.join("payouts.JSON");
+        fs::write(&json, format!(r#"[{{"address": "{}", "amount": 0.1}}]"#, address)).unwrap();
+        assert_eq!(load_payouts(&json, Network::Regtest).unwrap()[0].amount, Amount::from_sat(10_000_000));
+
+        let csv = dir.join("payouts.csv");
+        fs::write(&csv, format!("{},0.2", address)).unwrap();
+        assert_eq!(load_payouts(&csv, Network::Regtest).unwrap()[0].amount, Amount::from_sat(20_000_000));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,669 @@
+use log::{info, warn};
+
+use serde::Serialize;
+
+use bitcoin::blockdata::opcodes::{Class, ClassifyContext};
+use bitcoin::policy::{get_virtual_tx_size, DUST_RELAY_TX_FEE, MAX_STANDARD_TX_SIGOPS_COST, MAX_STANDARD_TX_WEIGHT};
+use bitcoin::script::Instruction;
+use bitcoin::taproot::{TAPROOT_ANNEX_PREFIX, TAPROOT_LEAF_MASK, TAPROOT_LEAF_TAPSCRIPT};
+use bitcoin::{consensus::deserialize, Amount, Script, ScriptBuf, Transaction, TxIn, TxOut, Txid};
+use bitcoincore_rpc::{Client, RpcApi};
+
+use crate::settings::Settings;
+use crate::modules::bitcoind::create_rpc_client;
+use crate::modules::client::{broadcast_tx, test_mempool_accept, BroadcastedTx};
+
+use super::errors::PolicyError;
+use super::verification::{fetch_prevouts, UtxoStatus};
+
+// Bitcoin Core v28.0 policy defaults (src/policy/policy.h), named as there. The weight,
+// sigop and dust limits imported from rust-bitcoin have the same values in that release.
+const TX_MIN_STANDARD_VERSION: i32 = 1;
+const TX_MAX_STANDARD_VERSION: i32 = 3;
+const MIN_STANDARD_TX_NONWITNESS_SIZE: usize = 65;
+const MAX_STANDARD_SCRIPTSIG_SIZE: usize = 1650;
+const MAX_OP_RETURN_RELAY: usize = 83;
+const MAX_BARE_MULTISIG_KEYS: i32 = 3;
+const MAX_P2SH_SIGOPS: usize = 15;
+const MAX_STANDARD_P2WSH_SCRIPT_SIZE: usize = 3600;
+const MAX_STANDARD_P2WSH_STACK_ITEMS: usize = 100;
+const MAX_STANDARD_P2WSH_STACK_ITEM_SIZE: usize = 80;
+const MAX_STANDARD_TAPSCRIPT_STACK_ITEM_SIZE: usize = 80;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Severity {
+    /// Nodes with default policy refuse to relay the transaction
+    Reject,
+    /// Relayed, but something to know before broadcasting
+    Warning,
+}
+
+/// One problem found with a transaction, in plain language.
+#[derive(Debug, Serialize)]
+pub struct PolicyIssue {
+    pub check: &'static str,
+    pub severity: Severity,
+    pub message: String,
+}
+
+/// Local standardness checks and the node's `testmempoolaccept` verdict for a transaction.
+#[derive(Debug, Serialize)]
+pub struct PolicyReport {
+    pub txid: Txid,
+    pub weight: u64,
+    /// Virtual size, sigop-adjusted as the mempool counts it
+    pub vsize: u64,
+    pub sigop_cost: usize,
+    #[serde(with = "bitcoin::amount::serde::as_btc::opt")]
+    pub fee: Option<Amount>,
+    /// sat/vB
+    pub fee_rate: Option<f64>,
+    pub replaceable: bool,
+    pub allowed: bool,
+    pub reject_reason: Option<String>,
+    pub issues: Vec<PolicyIssue>,
+}
+
+impl PolicyReport {
+    /// Whether nodes with default policy would relay the transaction.
+    pub fn accepted(&self) -> bool {
+        self.allowed && !self.issues.iter().any(|issue| issue.severity == Severity::Reject)
+    }
+}
+
+/// Check a raw transaction against the node's mempool policy without broadcasting it: run
+/// `testmempoolaccept`, then check locally for dust, non-standard scripts, oversize OP_RETURN
+/// outputs, weight and sigop limits, a fee rate below the node's minimum and BIP125 signalling.
+pub fn check_policy(tx_hex: &str, max_fee_rate: f64, settings: &Settings) -> Result<PolicyReport, PolicyError> {
+    let client: Client = create_rpc_client(settings, None)?;
+    check_policy_with_client(&client, tx_hex, max_fee_rate, settings)
+}
+
+fn check_policy_with_client(client: &Client, tx_hex: &str, max_fee_rate: f64, settings: &Settings) -> Result<PolicyReport, PolicyError> {
+    let tx: Transaction = deserialize(&hex::decode(tx_hex)?)?;
+    info!("Checking {} against mempool policy", tx.txid());
+
+    let accept = test_mempool_accept(client, tx_hex, Some(max_fee_rate))?;
+    let (spent_outputs, statuses) = fetch_prevouts(&tx, settings)?;
+    let spent: Vec<Option<&TxOut>> = tx.input.iter().map(|input| spent_outputs.get(&input.previous_output)).collect();
+
+    let mut issues = Vec::new();
+    if let Some(reason) = &accept.reject_reason {
+        issues.push(reject("testmempoolaccept", explain_reject_reason(reason)));
+    }
+    check_size(&tx, &mut issues);
+    check_outputs(&tx, &mut issues);
+    for (index, (input, (spent, (status, _)))) in tx.input.iter().zip(spent.iter().zip(&statuses)).enumerate() {
+        match (spent, status) {
+            (Some(spent), status) if *status != UtxoStatus::Spent => check_input(index, input, spent, &mut issues),
+            _ => issues.push(reject("inputs", format!(
+                "Input {} spends {}, which is already spent or does not exist",
+                index, input.previous_output,
+            ))),
+        }
+    }
+
+    let sigop_cost = tx.total_sigop_cost(|outpoint| spent_outputs.get(outpoint).cloned());
+    check_sigops(sigop_cost, &mut issues);
+
+    let weight = tx.weight().to_wu();
+    let vsize = get_virtual_tx_size(weight as i64, sigop_cost as i64) as u64;
+    let fee = spent.iter().copied().collect::<Option<Vec<&TxOut>>>().and_then(|spent| {
+        let input_value: Amount = spent.iter().map(|output| output.value).sum();
+        let output_value: Amount = tx.output.iter().map(|output| output.value).sum();
+        input_value.checked_sub(output_value)
+    });
+    let fee_rate = fee.map(|fee| fee.to_sat() as f64 / vsize as f64);
+    check_fee_rate(client, fee_rate, &mut issues)?;
+
+    let replaceable = tx.is_explicitly_rbf();
+    if !replaceable {
+        issues.push(warning("bip125", "No input signals BIP125 replaceability (every sequence is 0xfffffffe or higher). \
+            Bitcoin Core 28.0 and later replace it anyway, as -mempoolfullrbf is on by default, but older nodes and \
+            bump-fee need the signal; CPFP works either way".to_string()));
+    }
+
+    for issue in &issues {
+        match issue.severity {
+            Severity::Reject => warn!("{}: {}", issue.check, issue.message),
+            Severity::Warning => info!("{}: {}", issue.check, issue.message),
+        }
+    }
+
+    Ok(PolicyReport {
+        txid: tx.txid(),
+        weight,
+        vsize,
+        sigop_cost,
+        fee,
+        fee_rate,
+        replaceable,
+        allowed: accept.allowed,
+        reject_reason: accept.reject_reason,
+        issues,
+    })
+}
+
+/// [`check_policy`], then broadcast with `sendrawtransaction` only if nothing would be rejected.
+pub fn broadcast_checked(tx_hex: &str, max_fee_rate: f64, settings: &Settings) -> Result<BroadcastedTx, PolicyError> {
+    let client: Client = create_rpc_client(settings, None)?;
+    let report = check_policy_with_client(&client, tx_hex, max_fee_rate, settings)?;
+    if !report.accepted() {
+        let reasons: Vec<String> = report.issues.iter()
+            .filter(|issue| issue.severity == Severity::Reject)
+            .map(|issue| issue.message.clone())
+            .collect();
+        return Err(PolicyError::Rejected(reasons.join("; ")));
+    }
+    let txid = broadcast_tx(&client, tx_hex, Some(max_fee_rate))?;
+    Ok(BroadcastedTx { txid })
+}
+
+fn reject(check: &'static str, message: String) -> PolicyIssue {
+    PolicyIssue { check, severity: Severity::Reject, message }
+}
+
+fn warning(check: &'static str, message: String) -> PolicyIssue {
+    PolicyIssue { check, severity: Severity::Warning, message }
+}
+
+fn check_size(tx: &Transaction, issues: &mut Vec<PolicyIssue>) {
+    if !(TX_MIN_STANDARD_VERSION..=TX_MAX_STANDARD_VERSION).contains(&tx.version.0) {
+        issues.push(reject("version", format!(
+            "Version {} is non-standard, only versions {} to {} are relayed",
+            tx.version.0, TX_MIN_STANDARD_VERSION, TX_MAX_STANDARD_VERSION,
+        )));
+    }
+    let weight = tx.weight().to_wu();
+    if weight > MAX_STANDARD_TX_WEIGHT as u64 {
+        issues.push(reject("weight", format!(
+            "Weight {} WU is above the standard limit of {} WU; split it into smaller transactions",
+            weight, MAX_STANDARD_TX_WEIGHT,
+        )));
+    }
+    let base_size = tx.base_size();
+    if base_size < MIN_STANDARD_TX_NONWITNESS_SIZE {
+        issues.push(reject("tx-size-small", format!(
+            "Size without witness is {} bytes, below the minimum of {}; add an output or make the OP_RETURN longer",
+            base_size, MIN_STANDARD_TX_NONWITNESS_SIZE,
+        )));
+    }
+}
+
+fn check_outputs(tx: &Transaction, issues: &mut Vec<PolicyIssue>) {
+    let mut op_returns = 0;
+    for (vout, output) in tx.output.iter().enumerate() {
+        let script = &output.script_pubkey;
+        if script.is_op_return() {
+            op_returns += 1;
+            if script.len() > MAX_OP_RETURN_RELAY {
+                issues.push(reject("op-return", format!(
+                    "Output {} is an OP_RETURN of {} bytes, above the default -datacarriersize of {}",
+                    vout, script.len(), MAX_OP_RETURN_RELAY,
+                )));
+            }
+            continue;
+        }
+        match bare_multisig_keys(script) {
+            Some(keys) if keys > MAX_BARE_MULTISIG_KEYS => issues.push(reject("output-script", format!(
+                "Output {} is bare multisig with {} keys, at most {} are standard; pay to P2WSH instead",
+                vout, keys, MAX_BARE_MULTISIG_KEYS,
+            ))),
+            Some(_) => {}
+            None if !is_standard_output(script) => issues.push(reject("output-script", format!(
+                "Output {} pays to a non-standard script {}",
+                vout, script.to_asm_string(),
+            ))),
+            None => {}
+        }
+
+        let dust = script.dust_value();
+        if output.value < dust {
+            issues.push(reject("dust", format!(
+                "Output {} pays {} sat, below the dust limit of {} sat for its script at {} sat/vB",
+                vout, output.value.to_sat(), dust.to_sat(), DUST_RELAY_TX_FEE / 1000,
+            )));
+        }
+    }
+    if op_returns > 1 {
+        issues.push(reject("op-return", format!("{} OP_RETURN outputs, only one is standard", op_returns)));
+    }
+}
+
+/// Bitcoin Core's `AreInputsStandard` and `IsWitnessStandard` for one input.
+fn check_input(index: usize, input: &TxIn, spent: &TxOut, issues: &mut Vec<PolicyIssue>) {
+    if input.script_sig.len() > MAX_STANDARD_SCRIPTSIG_SIZE {
+        issues.push(reject("input-script", format!(
+            "Input {} has a {} byte scriptSig, above the standard limit of {}",
+            index, input.script_sig.len(), MAX_STANDARD_SCRIPTSIG_SIZE,
+        )));
+    }
+    if !input.script_sig.is_push_only() {
+        issues.push(reject("input-script", format!("Input {} has a scriptSig that does more than push data", index)));
+    }
+
+    let script_pubkey = &spent.script_pubkey;
+    if !is_standard_output(script_pubkey) || is_unknown_witness_program(script_pubkey) {
+        issues.push(reject("input-script", format!(
+            "Input {} spends a non-standard script {}",
+            index, script_pubkey.to_asm_string(),
+        )));
+        return;
+    }
+
+    // the program a witness spend runs, from the output or the P2SH redeem script
+    let mut program = script_pubkey.clone();
+    if script_pubkey.is_p2sh() {
+        let Some(redeem_script) = last_push(&input.script_sig) else {
+            return;
+        };
+        let sigops = redeem_script.count_sigops();
+        if sigops > MAX_P2SH_SIGOPS {
+            issues.push(reject("sigops", format!(
+                "Input {} has a redeem script with {} sigops, above the standard limit of {}",
+                index, sigops, MAX_P2SH_SIGOPS,
+            )));
+        }
+        program = redeem_script;
+    }
+
+    let stack: Vec<&[u8]> = input.witness.iter().collect();
+    if program.is_p2wsh() {
+        let Some((witness_script, items)) = stack.split_last() else {
+            return;
+        };
+        if witness_script.len() > MAX_STANDARD_P2WSH_SCRIPT_SIZE {
+            issues.push(reject("witness", format!(
+                "Input {} has a {} byte witness script, above the standard limit of {}",
+                index, witness_script.len(), MAX_STANDARD_P2WSH_SCRIPT_SIZE,
+            )));
+        }
+        if items.len() > MAX_STANDARD_P2WSH_STACK_ITEMS {
+            issues.push(reject("witness", format!(
+                "Input {} has {} witness items, above the standard limit of {}",
+                index, items.len(), MAX_STANDARD_P2WSH_STACK_ITEMS,
+            )));
+        }
+        check_witness_items(index, items, MAX_STANDARD_P2WSH_STACK_ITEM_SIZE, issues);
+    } else if program.is_p2tr() && !script_pubkey.is_p2sh() {
+        let mut items = stack.as_slice();
+        if items.len() >= 2 && items.last().is_some_and(|last| last.first() == Some(&TAPROOT_ANNEX_PREFIX)) {
+            issues.push(reject("witness", format!("Input {} has a taproot annex, which is not relayed yet", index)));
+            items = &items[..items.len() - 1];
+        }
+        // script path: the arguments, then the script and control block; only tapscript
+        // leaves limit the size of their arguments
+        if let [arguments @ .., _, control_block] = items {
+            if control_block.first().is_some_and(|leaf| leaf & TAPROOT_LEAF_MASK == TAPROOT_LEAF_TAPSCRIPT) {
+                check_witness_items(index, arguments, MAX_STANDARD_TAPSCRIPT_STACK_ITEM_SIZE, issues);
+            }
+        }
+    }
+}
+
+fn check_witness_items(index: usize, items: &[&[u8]], max_size: usize, issues: &mut Vec<PolicyIssue>) {
+    if let Some(largest) = items.iter().map(|item| item.len()).max().filter(|len| *len > max_size) {
+        issues.push(reject("witness", format!(
+            "Input {} has a {} byte witness item, above the standard limit of {} for script arguments",
+            index, largest, max_size,
+        )));
+    }
+}
+
+fn check_sigops(sigop_cost: usize, issues: &mut Vec<PolicyIssue>) {
+    if sigop_cost > MAX_STANDARD_TX_SIGOPS_COST as usize {
+        issues.push(reject("sigops", format!(
+            "Sigop cost {} is above the standard limit of {}; split the transaction or use fewer multisig inputs",
+            sigop_cost, MAX_STANDARD_TX_SIGOPS_COST,
+        )));
+    }
+}
+
+/// Compare the fee rate with the higher of the node's relay and mempool minimums.
+fn check_fee_rate(client: &Client, fee_rate: Option<f64>, issues: &mut Vec<PolicyIssue>) -> Result<(), PolicyError> {
+    let Some(fee_rate) = fee_rate else {
+        issues.push(warning("fee-rate", "Some spent outputs are unknown, so the fee rate could not be checked".to_string()));
+        return Ok(());
+    };
+    let mempool = client.get_mempool_info().map_err(|e| PolicyError::Bitcoind(e.into()))?;
+    // BTC/kvB to sat/vB
+    let minimum = mempool.mempool_min_fee.max(mempool.min_relay_tx_fee).to_sat() as f64 / 1000.0;
+    check_min_fee_rate(fee_rate, minimum, issues);
+    Ok(())
+}
+
+fn check_min_fee_rate(fee_rate: f64, minimum: f64, issues: &mut Vec<PolicyIssue>) {
+    if fee_rate < minimum {
+        issues.push(reject("fee-rate", format!(
+            "Fee rate {:.2} sat/vB is below the node's minimum of {:.2} sat/vB; raise the fee or it will not be relayed",
+            fee_rate, minimum,
+        )));
+    }
+}
+
+/// The types Bitcoin Core relays outputs to: P2PK, P2PKH, P2SH, bare multisig, OP_RETURN and
+/// witness programs of any version.
+fn is_standard_output(script: &Script) -> bool {
+    script.is_p2pk() || script.is_p2pkh() || script.is_p2sh() || script.is_witness_program()
+        || script.is_op_return() || bare_multisig_keys(script).is_some()
+}
+
+/// Witness programs with no rules yet, which can be paid to but not spent under policy.
+/// Pay-to-anchor (`OP_1 <0x4e73>`) is the exception.
+fn is_unknown_witness_program(script: &Script) -> bool {
+    script.is_witness_program() && !(script.is_p2wpkh() || script.is_p2wsh() || script.is_p2tr())
+        && script.as_bytes() != [0x51, 0x02, 0x4e, 0x73]
+}
+
+/// Number of keys of an `m <keys> n OP_CHECKMULTISIG` script.
+fn bare_multisig_keys(script: &Script) -> Option<i32> {
+    if !script.is_multisig() {
+        return None;
+    }
+    let instructions: Vec<Instruction> = script.instructions().collect::<Result<_, _>>().ok()?;
+    match instructions.iter().rev().nth(1)? {
+        Instruction::Op(op) => match op.classify(ClassifyContext::Legacy) {
+            Class::PushNum(keys) => Some(keys),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn last_push(script_sig: &Script) -> Option<ScriptBuf> {
+    match script_sig.instructions().last()?.ok()? {
+        Instruction::PushBytes(data) => Some(ScriptBuf::from(data.as_bytes().to_vec())),
+        _ => None,
+    }
+}
+
+/// What a `testmempoolaccept` or `sendrawtransaction` reject reason means, and what to do about it.
+fn explain_reject_reason(reason: &str) -> String {
+    let explanation = match reason.split(',').next().unwrap_or(reason).trim() {
+        "dust" => "an output is below the dust limit; raise its amount or drop it",
+        "scriptpubkey" => "an output pays to a non-standard script",
+        "bare-multisig" => "an output is bare multisig, which this node does not relay",
+        "multi-op-return" => "more than one OP_RETURN output",
+        "datacarrier" => "the OP_RETURN output is larger than the node's -datacarriersize",
+        "tx-size" => "the transaction is heavier than the 400000 WU standard limit",
+        "tx-size-small" => "the transaction is smaller than 65 bytes without its witness",
+        "version" => "the transaction version is non-standard",
+        "scriptsig-size" => "a scriptSig is larger than 1650 bytes",
+        "scriptsig-not-pushonly" => "a scriptSig does more than push data",
+        "bad-txns-nonstandard-inputs" => "an input spends a non-standard script or has too many P2SH sigops",
+        "bad-witness-nonstandard" => "a witness breaks the standard size limits or carries an annex",
+        "bad-txns-too-many-sigops" => "the transaction has too many signature operations",
+        "min relay fee not met" => "the fee rate is below the node's minimum relay fee",
+        "mempool min fee not met" => "the mempool is full and the fee rate is below its current minimum; raise the fee",
+        "insufficient fee" => "a replacement must pay more fee, and a higher fee rate, than what it replaces",
+        "txn-mempool-conflict" => "an input is already spent by a mempool transaction that does not signal BIP125",
+        "too many potential replacements" => "the replacement would evict more than 100 mempool transactions",
+        "replacement-adds-unconfirmed" => "a replacement may not add unconfirmed inputs",
+        "missing-inputs" | "bad-txns-inputs-missingorspent" => "an input is already spent or does not exist",
+        "bad-txns-inputs-duplicate" => "the same output is spent twice",
+        "bad-txns-in-belowout" => "the outputs are worth more than the inputs",
+        "non-final" => "the locktime has not been reached yet",
+        "non-BIP68-final" => "an input's relative locktime (BIP68) has not been reached yet",
+        "too-long-mempool-chain" => "too many unconfirmed ancestors or descendants",
+        "txn-already-in-mempool" | "txn-already-known" => "the node already has this transaction",
+        "txn-same-nonwitness-data-in-mempool" => "the node has this transaction with a different witness",
+        "max-fee-exceeded" => "the fee rate is above the max fee rate given; pass a higher -u, or 0 to disable the check",
+        reason if reason.starts_with("mandatory-script-verify-flag-failed") => "a script or signature is invalid under consensus rules; see debug-script",
+        reason if reason.starts_with("non-mandatory-script-verify-flag") => "a script breaks a policy rule beyond consensus, e.g. a high-S or non-minimal encoding",
+        _ => return format!("Rejected by the node: {}", reason),
+    };
+    format!("Rejected by the node ({}): {}", reason, explanation)
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::absolute::LockTime;
+    use bitcoin::opcodes::all::{OP_CHECKMULTISIG, OP_CHECKSIG, OP_NOP};
+    use bitcoin::opcodes::OP_TRUE;
+    use bitcoin::script::{Builder, PushBytesBuf};
+    use bitcoin::secp256k1::{PublicKey, Secp256k1, SecretKey};
+    use bitcoin::transaction::Version;
+    use bitcoin::{OutPoint, Sequence, Witness};
+
+    use super::*;
+
+    fn p2wpkh() -> ScriptBuf {
+        ScriptBuf::from_bytes([vec![0x00, 0x14], vec![1; 20]].concat())
+    }
+
+    fn p2tr() -> ScriptBuf {
+        ScriptBuf::from_bytes([vec![0x51, 0x20], vec![1; 32]].concat())
+    }
+
+    fn push(bytes: Vec<u8>) -> PushBytesBuf {
+        PushBytesBuf::try_from(bytes).unwrap()
+    }
+
+    fn output(sats: u64, script_pubkey: ScriptBuf) -> TxOut {
+        TxOut { value: Amount::from_sat(sats), script_pubkey }
+    }
+
+    fn input(script_sig: ScriptBuf, witness: Vec<Vec<u8>>) -> TxIn {
+        TxIn {
+            previous_output: OutPoint::null(),
+            script_sig,
+            sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+            witness: Witness::from_slice(&witness),
+        }
+    }
+
+    fn tx(version: i32, outputs: Vec<TxOut>) -> Transaction {
+        Transaction {
+            version: Version(version),
+            lock_time: LockTime::ZERO,
+            input: vec![input(ScriptBuf::new(), vec![])],
+            output: outputs,
+        }
+    }
+
+    fn bare_multisig(keys: u8) -> ScriptBuf {
+        let secp = Secp256k1::new();
+        (1..=keys)
+            .fold(Builder::new().push_int(1), |builder, i| {
+                let key = PublicKey::from_secret_key(&secp, &SecretKey::from_slice(&[i; 32]).unwrap());
+                builder.push_slice(key.serialize())
+            })
+            .push_int(keys as i64)
+            .push_opcode(OP_CHECKMULTISIG)
+            .into_script()
+    }
+
+    fn checks(issues: &[PolicyIssue]) -> Vec<&'static str> {
+        issues.iter().map(|issue| issue.check).collect()
+    }
+
+    fn size_checks(tx: &Transaction) -> Vec<&'static str> {
+        let mut issues = Vec::new();
+        check_size(tx, &mut issues);
+        checks(&issues)
+    }
+
+    fn output_checks(outputs: Vec<TxOut>) -> Vec<&'static str> {
+        let mut issues = Vec::new();
+        check_outputs(&tx(2, outputs), &mut issues);
+        checks(&issues)
+    }
+
+    fn input_checks(input: TxIn, spent: ScriptBuf) -> Vec<&'static str> {
+        let mut issues = Vec::new();
+        check_input(0, &input, &output(10_000, spent), &mut issues);
+        checks(&issues)
+    }
+
+    #[test]
+    fn rust_bitcoin_limits_match_core_28() {
+        assert_eq!(MAX_STANDARD_TX_WEIGHT, 400_000);
+        assert_eq!(MAX_STANDARD_TX_SIGOPS_COST, 16_000);
+        assert_eq!(DUST_RELAY_TX_FEE, 3_000);
+    }
+
+    #[test]
+    fn version_must_be_1_to_3() {
+        for version in [1, 2, 3] {
+            assert!(size_checks(&tx(version, vec![output(10_000, p2wpkh())])).is_empty());
+        }
+        for version in [0, 4, -1] {
+            assert_eq!(size_checks(&tx(version, vec![output(10_000, p2wpkh())])), ["version"]);
+        }
+    }
+
+    #[test]
+    fn size_limits() {
+        // 61 bytes without witness
+        let small = tx(2, vec![output(0, ScriptBuf::new_op_return([]))]);
+        assert_eq!(size_checks(&small), ["tx-size-small"]);
+
+        let heavy = tx(2, vec![output(10_000, ScriptBuf::from_bytes(vec![0x51; 100_000]))]);
+        assert_eq!(size_checks(&heavy), ["weight"]);
+    }
+
+    #[test]
+    fn op_return_size_and_count() {
+        let data = |len: usize| ScriptBuf::new_op_return(push(vec![0; len]));
+        assert_eq!(data(80).len(), MAX_OP_RETURN_RELAY);
+        assert!(output_checks(vec![output(0, data(80))]).is_empty());
+        assert_eq!(output_checks(vec![output(0, data(81))]), ["op-return"]);
+        assert_eq!(output_checks(vec![output(0, data(1)), output(0, data(1))]), ["op-return"]);
+    }
+
+    #[test]
+    fn bare_multisig_key_limit() {
+        assert_eq!(bare_multisig_keys(&bare_multisig(3)), Some(3));
+        assert!(output_checks(vec![output(10_000, bare_multisig(3))]).is_empty());
+        assert_eq!(output_checks(vec![output(10_000, bare_multisig(4))]), ["output-script"]);
+    }
+
+    #[test]
+    fn non_standard_output_script() {
+        let script = Builder::new().push_opcode(OP_TRUE).into_script();
+        assert!(!is_standard_output(&script));
+        assert_eq!(output_checks(vec![output(10_000, script)]), ["output-script"]);
+    }
+
+    #[test]
+    fn dust_limit() {
+        // (31 byte output + 67 byte spend) * 3 sat/vB
+        assert!(output_checks(vec![output(294, p2wpkh())]).is_empty());
+        assert_eq!(output_checks(vec![output(293, p2wpkh())]), ["dust"]);
+    }
+
+    #[test]
+    fn script_sig_size_and_push_only() {
+        let p2pkh = ScriptBuf::from_bytes([vec![0x76, 0xa9, 0x14], vec![1; 20], vec![0x88, 0xac]].concat());
+        let large = Builder::new().push_slice(push(vec![0; 520])).push_slice(push(vec![0; 520]))
+            .push_slice(push(vec![0; 520])).push_slice(push(vec![0; 80])).into_script();
+        assert!(large.len() > MAX_STANDARD_SCRIPTSIG_SIZE);
+        assert_eq!(input_checks(input(large, vec![]), p2pkh.clone()), ["input-script"]);
+
+        let not_push_only = Builder::new().push_slice([1]).push_opcode(OP_NOP).into_script();
+        assert_eq!(input_checks(input(not_push_only, vec![]), p2pkh), ["input-script"]);
+    }
+
+    #[test]
+    fn spent_script_must_be_standard() {
+        let op_true = Builder::new().push_opcode(OP_TRUE).into_script();
+        assert_eq!(input_checks(input(ScriptBuf::new(), vec![]), op_true), ["input-script"]);
+
+        let witness_v2 = ScriptBuf::from_bytes([vec![0x52, 0x20], vec![1; 32]].concat());
+        assert_eq!(input_checks(input(ScriptBuf::new(), vec![]), witness_v2), ["input-script"]);
+
+        let pay_to_anchor = ScriptBuf::from_bytes(vec![0x51, 0x02, 0x4e, 0x73]);
+        assert!(input_checks(input(ScriptBuf::new(), vec![]), pay_to_anchor).is_empty());
+    }
+
+    #[test]
+    fn p2sh_sigop_limit() {
+        let redeem = |sigops: usize| (0..sigops)
+            .fold(Builder::new(), |builder, _| builder.push_opcode(OP_CHECKSIG))
+            .into_script();
+        let spend = |redeem: &ScriptBuf| input(Builder::new().push_slice(push(redeem.to_bytes())).into_script(), vec![]);
+
+        let allowed = redeem(MAX_P2SH_SIGOPS);
+        assert!(input_checks(spend(&allowed), allowed.to_p2sh()).is_empty());
+        let too_many = redeem(MAX_P2SH_SIGOPS + 1);
+        assert_eq!(input_checks(spend(&too_many), too_many.to_p2sh()), ["sigops"]);
+    }
+
+    #[test]
+    fn p2wsh_witness_limits() {
+        let witness_script = Builder::new().push_opcode(OP_TRUE).into_script();
+        let spend = |mut items: Vec<Vec<u8>>, script: &ScriptBuf| {
+            items.push(script.to_bytes());
+            input_checks(input(ScriptBuf::new(), items), script.to_p2wsh())
+        };
+
+        assert!(spend(vec![vec![0; 80]; MAX_STANDARD_P2WSH_STACK_ITEMS], &witness_script).is_empty());
+        assert_eq!(spend(vec![vec![0; 81]], &witness_script), ["witness"]);
+        assert_eq!(spend(vec![vec![0]; MAX_STANDARD_P2WSH_STACK_ITEMS + 1], &witness_script), ["witness"]);
+
+        let large_script = ScriptBuf::from_bytes(vec![0x51; MAX_STANDARD_P2WSH_SCRIPT_SIZE + 1]);
+        assert_eq!(spend(vec![], &large_script), ["witness"]);
+    }
+
+    #[test]
+    fn p2sh_wrapped_p2wsh_witness_limits() {
+        let witness_script = Builder::new().push_opcode(OP_TRUE).into_script();
+        let program = witness_script.to_p2wsh();
+        let script_sig = Builder::new().push_slice(push(program.to_bytes())).into_script();
+        let spent = program.to_p2sh();
+
+        let spend = input(script_sig.clone(), vec![vec![0; 80], witness_script.to_bytes()]);
+        assert!(input_checks(spend, spent.clone()).is_empty());
+        let spend = input(script_sig, vec![vec![0; 81], witness_script.to_bytes()]);
+        assert_eq!(input_checks(spend, spent), ["witness"]);
+    }
+
+    #[test]
+    fn taproot_witness_limits() {
+        let script = vec![0x51];
+        let control_block = |leaf: u8| [vec![leaf], vec![1; 32]].concat();
+
+        assert!(input_checks(input(ScriptBuf::new(), vec![vec![0; 64]]), p2tr()).is_empty());
+
+        let annex = input(ScriptBuf::new(), vec![vec![0; 64], vec![TAPROOT_ANNEX_PREFIX]]);
+        assert_eq!(input_checks(annex, p2tr()), ["witness"]);
+
+        let tapscript = |argument: usize, leaf: u8| input(ScriptBuf::new(), vec![vec![0; argument], script.clone(), control_block(leaf)]);
+        assert!(input_checks(tapscript(80, 0xc0), p2tr()).is_empty());
+        assert_eq!(input_checks(tapscript(81, 0xc0), p2tr()), ["witness"]);
+        assert_eq!(input_checks(tapscript(81, 0xc1), p2tr()), ["witness"]);
+        // unknown leaf versions have no argument size limit
+        assert!(input_checks(tapscript(81, 0xc2), p2tr()).is_empty());
+    }
+
+    #[test]
+    fn sigop_cost_limit() {
+        let mut issues = Vec::new();
+        check_sigops(MAX_STANDARD_TX_SIGOPS_COST as usize, &mut issues);
+        assert!(issues.is_empty());
+        check_sigops(MAX_STANDARD_TX_SIGOPS_COST as usize + 1, &mut issues);
+        assert_eq!(checks(&issues), ["sigops"]);
+    }
+
+    #[test]
+    fn fee_rate_minimum() {
+        let mut issues = Vec::new();
+        check_min_fee_rate(1.0, 1.0, &mut issues);
+        assert!(issues.is_empty());
+        check_min_fee_rate(0.99, 1.0, &mut issues);
+        assert_eq!(checks(&issues), ["fee-rate"]);
+        assert_eq!(issues[0].severity, Severity::Reject);
+    }
+
+    #[test]
+    fn reject_reasons_are_explained() {
+        assert_eq!(
+            explain_reject_reason("dust"),
+            "Rejected by the node (dust): an output is below the dust limit; raise its amount or drop it",
+        );
+        assert!(explain_reject_reason("mandatory-script-verify-flag-failed (Signature must be zero for failed CHECK(MULTI)SIG operation)")
+            .contains("invalid under consensus rules"));
+        assert_eq!(explain_reject_reason("something-new"), "Rejected by the node: something-new");
+    }
+}
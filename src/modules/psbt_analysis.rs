@@ -0,0 +1,602 @@
+use std::collections::BTreeSet;
+use std::fmt;
+use std::str::FromStr;
+
+use log::info;
+
+use serde::Serialize;
+
+use bitcoin::bip32::{DerivationPath, Fingerprint};
+use bitcoin::psbt::{Input, Psbt};
+use bitcoin::script::Instruction;
+use bitcoin::taproot::{self, TapLeafHash, TAPROOT_ANNEX_PREFIX};
+use bitcoin::{ecdsa, Address, Amount, Network, OutPoint, Script, TxOut, Txid, Weight};
+
+use crate::utils::fees::{output_weight, psbt_input_weight, script_multisig_counts, TX_OVERHEAD_WEIGHT};
+
+use super::errors::PsbtError;
+use super::verification::script_type;
+
+/// The BIP174 role a PSBT, or one of its inputs, is waiting for, in the order they act.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PsbtRole {
+    /// Add the spent output, redeem script or witness script
+    Updater,
+    Signer,
+    Finalizer,
+    Extractor,
+}
+
+impl fmt::Display for PsbtRole {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let role = match self {
+            PsbtRole::Updater => "updater",
+            PsbtRole::Signer => "signer",
+            PsbtRole::Finalizer => "finalizer",
+            PsbtRole::Extractor => "extractor",
+        };
+        write!(f, "{}", role)
+    }
+}
+
+/// A key's BIP32 origin, as recorded in a PSBT.
+#[derive(Debug, Serialize)]
+pub struct KeyOrigin {
+    pub pubkey: String,
+    pub fingerprint: Fingerprint,
+    pub path: DerivationPath,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PsbtInputDetail {
+    pub index: usize,
+    pub outpoint: OutPoint,
+    #[serde(with = "bitcoin::amount::serde::as_btc::opt")]
+    pub value: Option<Amount>,
+    /// Type of the spent output, `unknown` without it
+    pub script_type: &'static str,
+    /// `witness`, `non-witness` or `missing`
+    pub utxo: &'static str,
+    pub sighash_type: Option<String>,
+    pub key_origins: Vec<KeyOrigin>,
+    pub signatures: usize,
+    /// `None` when the scripts do not say, e.g. a custom witness script
+    pub signatures_required: Option<usize>,
+    /// Keys with a known origin or in the script that have not signed yet
+    pub missing_signatures: Vec<String>,
+    pub is_final: bool,
+    pub next: PsbtRole,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PsbtOutputDetail {
+    pub index: usize,
+    #[serde(with = "bitcoin::amount::serde::as_btc")]
+    pub value: Amount,
+    pub script_type: &'static str,
+    pub address: Option<Address>,
+    /// Outputs with key origins are usually change back to a signer
+    pub key_origins: Vec<KeyOrigin>,
+}
+
+/// Everything in a PSBT, read natively without a node.
+#[derive(Debug, Serialize)]
+pub struct DecodedPsbt {
+    pub txid: Txid,
+    pub version: i32,
+    pub locktime: u32,
+    pub inputs: Vec<PsbtInputDetail>,
+    pub outputs: Vec<PsbtOutputDetail>,
+    /// Known once every input has its spent output
+    #[serde(with = "bitcoin::amount::serde::as_btc::opt")]
+    pub fee: Option<Amount>,
+    /// vsize once every input is finalized, worst case signatures until then
+    pub estimated_vsize: Option<u64>,
+    /// sat/vB
+    pub estimated_fee_rate: Option<f64>,
+    pub next: PsbtRole,
+}
+
+#[derive(Debug, Serialize)]
+pub struct InputAnalysis {
+    pub index: usize,
+    pub has_utxo: bool,
+    pub is_final: bool,
+    pub signatures: usize,
+    pub signatures_required: Option<usize>,
+    pub missing_signatures: Vec<String>,
+    pub next: PsbtRole,
+}
+
+/// What a PSBT still needs, in the shape of `analyzepsbt`.
+#[derive(Debug, Serialize)]
+pub struct PsbtAnalysis {
+    pub inputs: Vec<InputAnalysis>,
+    #[serde(with = "bitcoin::amount::serde::as_btc::opt")]
+    pub fee: Option<Amount>,
+    pub estimated_vsize: Option<u64>,
+    /// sat/vB
+    pub estimated_fee_rate: Option<f64>,
+    pub next: PsbtRole,
+}
+
+/// Decode a base64 PSBT: per input the spent output, key origins, signatures present and
+/// required, sighash type and next role; per output the address and key origins; then the fee
+/// and fee rate. Addresses are shown for `network`.
+pub fn decode_psbt(psbt: &str, network: Network) -> Result<DecodedPsbt, PsbtError> {
+    let psbt = Psbt::from_str(psbt)?;
+    let tx = &psbt.unsigned_tx;
+
+    let inputs: Vec<PsbtInputDetail> = psbt.inputs.iter().zip(&tx.input).enumerate()
+        .map(|(index, (input, txin))| input_detail(index, input, txin.previous_output))
+        .collect();
+    let outputs: Vec<PsbtOutputDetail> = psbt.outputs.iter().zip(&tx.output).enumerate()
+        .map(|(index, (output, txout))| PsbtOutputDetail {
+            index,
+            value: txout.value,
+            script_type: script_type(&txout.script_pubkey),
+            address: Address::from_script(&txout.script_pubkey, network).ok(),
+            key_origins: output.bip32_derivation.iter()
+                .map(|(pubkey, (fingerprint, path))| key_origin(pubkey.to_string(), fingerprint, path))
+                .chain(output.tap_key_origins.iter()
+                    .map(|(pubkey, (_, (fingerprint, path)))| key_origin(pubkey.to_string(), fingerprint, path)))
+                .collect(),
+        })
+        .collect();
+
+    let fee = psbt.fee().ok();
+    let estimated_vsize = estimated_vsize(&psbt);
+    let estimated_fee_rate = fee.zip(estimated_vsize).map(|(fee, vsize)| fee.to_sat() as f64 / vsize as f64);
+    let next = inputs.iter().map(|input| input.next).min().unwrap_or(PsbtRole::Extractor);
+    info!("Decoded PSBT {} with {} inputs and {} outputs, next role: {}", tx.txid(), inputs.len(), outputs.len(), next);
+
+    Ok(DecodedPsbt {
+        txid: tx.txid(),
+        version: tx.version.0,
+        locktime: tx.lock_time.to_consensus_u32(),
+        inputs,
+        outputs,
+        fee,
+        estimated_vsize,
+        estimated_fee_rate,
+        next,
+    })
+}
+
+/// Report what a base64 PSBT still needs before it can be broadcast, without a node.
+pub fn analyze_psbt(psbt: &str, network: Network) -> Result<PsbtAnalysis, PsbtError> {
+    let decoded = decode_psbt(psbt, network)?;
+    Ok(PsbtAnalysis {
+        inputs: decoded.inputs.into_iter()
+            .map(|input| InputAnalysis {
+                index: input.index,
+                has_utxo: input.utxo != "missing",
+                is_final: input.is_final,
+                signatures: input.signatures,
+                signatures_required: input.signatures_required,
+                missing_signatures: input.missing_signatures,
+                next: input.next,
+            })
+            .collect(),
+        fee: decoded.fee,
+        estimated_vsize: decoded.estimated_vsize,
+        estimated_fee_rate: decoded.estimated_fee_rate,
+        next: decoded.next,
+    })
+}
+
+fn key_origin(pubkey: String, fingerprint: &Fingerprint, path: &DerivationPath) -> KeyOrigin {
+    KeyOrigin { pubkey, fingerprint: *fingerprint, path: path.clone() }
+}
+
+fn spent_output(input: &Input, outpoint: OutPoint) -> Option<TxOut> {
+    input.witness_utxo.clone().or_else(|| {
+        input.non_witness_utxo.as_ref()
+            .filter(|prev_tx| prev_tx.txid() == outpoint.txid)
+            .and_then(|prev_tx| prev_tx.output.get(outpoint.vout as usize).cloned())
+    })
+}
+
+fn input_detail(index: usize, input: &Input, outpoint: OutPoint) -> PsbtInputDetail {
+    let spent = spent_output(input, outpoint);
+    let utxo = match (&input.witness_utxo, &spent) {
+        (Some(_), _) => "witness",
+        (None, Some(_)) => "non-witness",
+        (None, None) => "missing",
+    };
+    let is_final = input.final_script_sig.is_some() || input.final_script_witness.is_some();
+    let key_origins: Vec<KeyOrigin> = input.bip32_derivation.iter()
+        .map(|(pubkey, (fingerprint, path))| key_origin(pubkey.to_string(), fingerprint, path))
+        .chain(input.tap_key_origins.iter()
+            .map(|(pubkey, (_, (fingerprint, path)))| key_origin(pubkey.to_string(), fingerprint, path)))
+        .collect();
+
+    let (signatures, signatures_required, next) = match &spent {
+        _ if is_final => (final_signatures(input, spent.as_ref().map(|spent| spent.script_pubkey.as_script())), None, PsbtRole::Extractor),
+        None => (0, None, PsbtRole::Updater),
+        Some(spent) => match signing_requirement(input, &spent.script_pubkey) {
+            None => (0, None, PsbtRole::Updater),
+            Some((signatures, required)) => {
+                let next = match required {
+                    Some(required) if signatures >= required => PsbtRole::Finalizer,
+                    None if signatures > 0 => PsbtRole::Finalizer,
+                    _ => PsbtRole::Signer,
+                };
+                (signatures, required, next)
+            }
+        },
+    };
+
+    let missing_signatures = match next {
+        PsbtRole::Signer => missing_signatures(input, spent.as_ref().map(|spent| spent.script_pubkey.as_script())),
+        _ => Vec::new(),
+    };
+
+    PsbtInputDetail {
+        index,
+        outpoint,
+        value: spent.as_ref().map(|spent| spent.value),
+        script_type: spent.as_ref().map_or("unknown", |spent| script_type(&spent.script_pubkey)),
+        utxo,
+        sighash_type: input.sighash_type.map(|sighash_type| sighash_type.to_string()),
+        key_origins,
+        signatures,
+        signatures_required,
+        missing_signatures,
+        is_final,
+        next,
+    }
+}
+
+/// Signatures present and required for an input that is not final yet, from the scripts the
+/// PSBT carries. `None` when the updater still has to add a redeem or witness script.
+fn signing_requirement(input: &Input, script_pubkey: &Script) -> Option<(usize, Option<usize>)> {
+    let ecdsa_signatures = input.partial_sigs.len();
+    let threshold = |script: &Script| script_multisig_counts(script).map(|(m, _)| m as usize);
+
+    if script_pubkey.is_p2pkh() || script_pubkey.is_p2wpkh() || script_pubkey.is_p2pk() {
+        return Some((ecdsa_signatures, Some(1)));
+    }
+    if script_pubkey.is_p2wsh() {
+        return Some((ecdsa_signatures, threshold(input.witness_script.as_ref()?)));
+    }
+    if script_pubkey.is_p2sh() {
+        let redeem_script = input.redeem_script.as_ref()?;
+        return match () {
+            _ if redeem_script.is_p2wpkh() => Some((ecdsa_signatures, Some(1))),
+            _ if redeem_script.is_p2wsh() => Some((ecdsa_signatures, threshold(input.witness_script.as_ref()?))),
+            _ => Some((ecdsa_signatures, threshold(redeem_script))),
+        };
+    }
+    if script_pubkey.is_p2tr() {
+        if input.tap_key_sig.is_some() || input.tap_scripts.is_empty() {
+            return Some((usize::from(input.tap_key_sig.is_some()), Some(1)));
+        }
+        // the leaf closest to being satisfied
+        return input.tap_scripts.values()
+            .map(|(leaf_script, leaf_version)| {
+                let leaf_hash = TapLeafHash::from_script(leaf_script, *leaf_version);
+                let signatures = input.tap_script_sigs.keys().filter(|(_, hash)| *hash == leaf_hash).count();
+                (signatures, threshold(leaf_script))
+            })
+            .max_by_key(|(signatures, required)| {
+                let outstanding = required.map_or(usize::MAX, |required| required.saturating_sub(*signatures));
+                std::cmp::Reverse(outstanding)
+            });
+    }
+    Some((ecdsa_signatures, None))
+}
+
+/// Signatures in the final scriptSig and witness, since finalizing clears the partial ones: DER
+/// signatures, or for a P2TR output the key path signature or the signatures passed to the leaf.
+fn final_signatures(input: &Input, script_pubkey: Option<&Script>) -> usize {
+    let witness: Vec<&[u8]> = input.final_script_witness.iter().flat_map(|witness| witness.iter()).collect();
+    if script_pubkey.is_some_and(|script| script.is_p2tr()) {
+        let items = match witness.as_slice() {
+            [rest @ .., annex] if !rest.is_empty() && annex.first() == Some(&TAPROOT_ANNEX_PREFIX) => rest,
+            items => items,
+        };
+        // a script path spend ends with the leaf script and control block
+        let arguments = match items.len() {
+            0 | 1 => items,
+            len => &items[..len - 2],
+        };
+        return arguments.iter().filter(|item| taproot::Signature::from_slice(item).is_ok()).count();
+    }
+
+    let script_sig_pushes = input.final_script_sig.iter()
+        .flat_map(|script_sig| script_sig.instructions().flatten())
+        .filter_map(|instruction| match instruction {
+            Instruction::PushBytes(data) => Some(data.as_bytes()),
+            _ => None,
+        });
+    witness.into_iter().chain(script_sig_pushes)
+        .filter(|item| ecdsa::Signature::from_slice(item).is_ok())
+        .count()
+}
+
+/// Keys that could sign but have not: those with a key origin, and those in the input's scripts.
+fn missing_signatures(input: &Input, script_pubkey: Option<&Script>) -> Vec<String> {
+    let mut keys: BTreeSet<String> = input.bip32_derivation.keys().map(|pubkey| pubkey.to_string())
+        .chain(input.tap_key_origins.keys().map(|pubkey| pubkey.to_string()))
+        .collect();
+    let scripts = [input.redeem_script.as_deref(), input.witness_script.as_deref(), script_pubkey];
+    let leaf_scripts = input.tap_scripts.values().map(|(leaf_script, _)| leaf_script.as_script());
+    for script in scripts.into_iter().flatten().chain(leaf_scripts) {
+        keys.extend(script.instructions().flatten().filter_map(|instruction| match instruction {
+            Instruction::PushBytes(data) if matches!(data.len(), 32 | 33 | 65) && script_multisig_counts(script).is_some() => {
+                Some(hex::encode(data.as_bytes()))
+            }
+            _ => None,
+        }));
+    }
+
+    let signed: BTreeSet<String> = input.partial_sigs.keys().map(|pubkey| pubkey.to_string())
+        .chain(input.tap_script_sigs.keys().map(|(pubkey, _)| pubkey.to_string()))
+        .collect();
+    keys.into_iter().filter(|key| !signed.contains(key)).collect()
+}
+
+/// vsize of the finalized transaction: exact for final inputs, worst case signatures otherwise.
+fn estimated_vsize(psbt: &Psbt) -> Option<u64> {
+    let inputs = psbt.inputs.iter().zip(&psbt.unsigned_tx.input)
+        .map(|(input, txin)| {
+            let spent = spent_output(input, txin.previous_output)?;
+            psbt_input_weight(input, &spent.script_pubkey)
+        })
+        .sum::<Option<Weight>>()?;
+    let outputs: Weight = psbt.unsigned_tx.output.iter().map(|output| output_weight(&output.script_pubkey)).sum();
+    Some((TX_OVERHEAD_WEIGHT + inputs + outputs).to_vbytes_ceil())
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::absolute::LockTime;
+    use bitcoin::blockdata::opcodes::all::{OP_CHECKMULTISIG, OP_CHECKSIG, OP_CHECKSIGADD, OP_NUMEQUAL};
+    use bitcoin::hashes::Hash;
+    use bitcoin::key::{Keypair, XOnlyPublicKey};
+    use bitcoin::script::{Builder, PushBytesBuf};
+    use bitcoin::secp256k1::{All, Message, Secp256k1, SecretKey};
+    use bitcoin::sighash::{EcdsaSighashType, Prevouts, SighashCache, TapSighashType};
+    use bitcoin::taproot::{LeafVersion, TaprootBuilder};
+    use bitcoin::transaction::Version;
+    use bitcoin::{PublicKey, ScriptBuf, Sequence, Transaction, TxIn, Witness};
+
+    use crate::utils::utils::NUMS_INTERNAL_KEY;
+
+    use super::*;
+
+    fn keypair(secp: &Secp256k1<All>, i: u8) -> Keypair {
+        Keypair::from_secret_key(secp, &SecretKey::from_slice(&[i; 32]).unwrap())
+    }
+
+    fn pubkey(secp: &Secp256k1<All>, i: u8) -> PublicKey {
+        PublicKey::new(keypair(secp, i).public_key())
+    }
+
+    fn p2wpkh(secp: &Secp256k1<All>, i: u8) -> ScriptBuf {
+        ScriptBuf::new_p2wpkh(&pubkey(secp, i).wpubkey_hash().unwrap())
+    }
+
+    /// An unsigned PSBT spending 100000 sat from `script_pubkey` to a 90000 sat P2WPKH output.
+    fn psbt_spending(secp: &Secp256k1<All>, script_pubkey: ScriptBuf) -> Psbt {
+        let tx = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(Txid::all_zeros(), 0),
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                witness: Witness::new(),
+            }],
+            output: vec![TxOut { value: Amount::from_sat(90_000), script_pubkey: p2wpkh(secp, 9) }],
+        };
+        let mut psbt = Psbt::from_unsigned_tx(tx).unwrap();
+        psbt.inputs[0].witness_utxo = Some(TxOut { value: Amount::from_sat(100_000), script_pubkey });
+        psbt
+    }
+
+    fn analysis(psbt: &Psbt) -> PsbtAnalysis {
+        analyze_psbt(&psbt.to_string(), Network::Regtest).unwrap()
+    }
+
+    /// Signatures, required signatures and next role of the only input.
+    fn signing_state(psbt: &Psbt) -> (usize, Option<usize>, PsbtRole) {
+        let input = &analysis(psbt).inputs[0];
+        (input.signatures, input.signatures_required, input.next)
+    }
+
+    /// vsize of the transaction the finalized PSBT extracts to.
+    fn extracted_vsize(psbt: &Psbt) -> u64 {
+        psbt.clone().extract_tx_unchecked_fee_rate().vsize() as u64
+    }
+
+    fn ecdsa_signature(secp: &Secp256k1<All>, sighash: [u8; 32], i: u8) -> ecdsa::Signature {
+        let sig = secp.sign_ecdsa(&Message::from_digest(sighash), &keypair(secp, i).secret_key());
+        ecdsa::Signature { sig, hash_ty: EcdsaSighashType::All }
+    }
+
+    fn multisig_script(secp: &Secp256k1<All>, m: i64, keys: &[u8]) -> ScriptBuf {
+        keys.iter()
+            .fold(Builder::new().push_int(m), |builder, i| builder.push_key(&pubkey(secp, *i)))
+            .push_int(keys.len() as i64)
+            .push_opcode(OP_CHECKMULTISIG)
+            .into_script()
+    }
+
+    /// Sign the only input of a P2WSH multisig PSBT with each of `keys`.
+    fn sign_p2wsh(secp: &Secp256k1<All>, psbt: &mut Psbt, keys: &[u8]) {
+        let witness_script = psbt.inputs[0].witness_script.clone().unwrap();
+        let sighash = SighashCache::new(&psbt.unsigned_tx)
+            .p2wsh_signature_hash(0, &witness_script, Amount::from_sat(100_000), EcdsaSighashType::All)
+            .unwrap();
+        for i in keys {
+            psbt.inputs[0].partial_sigs.insert(pubkey(secp, *i), ecdsa_signature(secp, sighash.to_byte_array(), *i));
+        }
+    }
+
+    /// Move the partial signatures of a P2WSH multisig input into its final witness, in script order.
+    fn finalize_p2wsh(secp: &Secp256k1<All>, psbt: &mut Psbt, keys: &[u8]) {
+        let input = &mut psbt.inputs[0];
+        let mut witness = Witness::new();
+        witness.push([]);
+        for i in keys {
+            if let Some(signature) = input.partial_sigs.get(&pubkey(secp, *i)) {
+                witness.push(signature.to_vec());
+            }
+        }
+        witness.push(input.witness_script.take().unwrap().as_bytes());
+        input.final_script_witness = Some(witness);
+        input.partial_sigs.clear();
+    }
+
+    #[test]
+    fn unsigned_p2wpkh_input() {
+        let secp = Secp256k1::new();
+        let mut psbt = psbt_spending(&secp, p2wpkh(&secp, 1));
+
+        let unsigned = analysis(&psbt);
+        assert_eq!(signing_state(&psbt), (0, Some(1), PsbtRole::Signer));
+        assert!(unsigned.inputs[0].has_utxo && !unsigned.inputs[0].is_final);
+        assert_eq!(unsigned.inputs[0].missing_signatures, Vec::<String>::new());
+        assert_eq!(unsigned.fee, Some(Amount::from_sat(10_000)));
+        // 10.5 vB overhead, 68 vB worst case P2WPKH input, 31 vB P2WPKH output
+        assert_eq!(unsigned.estimated_vsize, Some(110));
+        assert_eq!(unsigned.next, PsbtRole::Signer);
+
+        let sighash = SighashCache::new(&psbt.unsigned_tx)
+            .p2wpkh_signature_hash(0, &p2wpkh(&secp, 1), Amount::from_sat(100_000), EcdsaSighashType::All)
+            .unwrap();
+        let signature = ecdsa_signature(&secp, sighash.to_byte_array(), 1);
+        psbt.inputs[0].partial_sigs.insert(pubkey(&secp, 1), signature);
+        assert_eq!(signing_state(&psbt), (1, Some(1), PsbtRole::Finalizer));
+
+        psbt.inputs[0].final_script_witness = Some(Witness::from_slice(&[signature.to_vec(), pubkey(&secp, 1).to_bytes()]));
+        psbt.inputs[0].partial_sigs.clear();
+        let finalized = analysis(&psbt);
+        assert_eq!(signing_state(&psbt), (1, None, PsbtRole::Extractor));
+        assert_eq!(finalized.estimated_vsize, Some(extracted_vsize(&psbt)));
+        assert!(unsigned.estimated_vsize >= finalized.estimated_vsize);
+        assert_eq!(finalized.next, PsbtRole::Extractor);
+    }
+
+    #[test]
+    fn p2wsh_1_of_2_input() {
+        let secp = Secp256k1::new();
+        let witness_script = multisig_script(&secp, 1, &[1, 2]);
+        let mut psbt = psbt_spending(&secp, ScriptBuf::new_p2wsh(&witness_script.wscript_hash()));
+        assert_eq!(signing_state(&psbt), (0, None, PsbtRole::Updater));
+        assert_eq!(analysis(&psbt).estimated_vsize, None);
+
+        psbt.inputs[0].witness_script = Some(witness_script);
+        assert_eq!(signing_state(&psbt), (0, Some(1), PsbtRole::Signer));
+        assert_eq!(analysis(&psbt).inputs[0].missing_signatures.len(), 2);
+        let unsigned_vsize = analysis(&psbt).estimated_vsize.unwrap();
+
+        sign_p2wsh(&secp, &mut psbt, &[2]);
+        assert_eq!(signing_state(&psbt), (1, Some(1), PsbtRole::Finalizer));
+
+        finalize_p2wsh(&secp, &mut psbt, &[1, 2]);
+        assert_eq!(signing_state(&psbt), (1, None, PsbtRole::Extractor));
+        let vsize = extracted_vsize(&psbt);
+        assert_eq!(analysis(&psbt).estimated_vsize, Some(vsize));
+        assert!(unsigned_vsize >= vsize && unsigned_vsize - vsize <= 1);
+    }
+
+    #[test]
+    fn p2wsh_2_of_2_input() {
+        let secp = Secp256k1::new();
+        let witness_script = multisig_script(&secp, 2, &[1, 2]);
+        let mut psbt = psbt_spending(&secp, ScriptBuf::new_p2wsh(&witness_script.wscript_hash()));
+        psbt.inputs[0].witness_script = Some(witness_script);
+        let unsigned_vsize = analysis(&psbt).estimated_vsize.unwrap();
+
+        sign_p2wsh(&secp, &mut psbt, &[1]);
+        assert_eq!(signing_state(&psbt), (1, Some(2), PsbtRole::Signer));
+        assert_eq!(analysis(&psbt).inputs[0].missing_signatures, [pubkey(&secp, 2).to_string()]);
+
+        sign_p2wsh(&secp, &mut psbt, &[2]);
+        assert_eq!(signing_state(&psbt), (2, Some(2), PsbtRole::Finalizer));
+        assert!(analysis(&psbt).inputs[0].missing_signatures.is_empty());
+
+        finalize_p2wsh(&secp, &mut psbt, &[1, 2]);
+        assert_eq!(signing_state(&psbt), (2, None, PsbtRole::Extractor));
+        let vsize = extracted_vsize(&psbt);
+        assert_eq!(analysis(&psbt).estimated_vsize, Some(vsize));
+        // one byte per signature shorter than the worst case DER encoding, at most
+        assert!(unsigned_vsize >= vsize && unsigned_vsize - vsize <= 1);
+    }
+
+    #[test]
+    fn taproot_multi_a_leaf_input() {
+        let secp = Secp256k1::new();
+        let keys: Vec<XOnlyPublicKey> = (1..=3).map(|i| keypair(&secp, i).x_only_public_key().0).collect();
+        // multi_a(2,K1,K2,K3)
+        let leaf_script = Builder::new()
+            .push_x_only_key(&keys[0]).push_opcode(OP_CHECKSIG)
+            .push_x_only_key(&keys[1]).push_opcode(OP_CHECKSIGADD)
+            .push_x_only_key(&keys[2]).push_opcode(OP_CHECKSIGADD)
+            .push_int(2).push_opcode(OP_NUMEQUAL)
+            .into_script();
+        assert_eq!(script_multisig_counts(&leaf_script), Some((2, 3)));
+
+        let internal_key = XOnlyPublicKey::from_str(NUMS_INTERNAL_KEY).unwrap();
+        let spend_info = TaprootBuilder::new().add_leaf(0, leaf_script.clone()).unwrap().finalize(&secp, internal_key).unwrap();
+        let control_block = spend_info.control_block(&(leaf_script.clone(), LeafVersion::TapScript)).unwrap();
+        let mut psbt = psbt_spending(&secp, ScriptBuf::new_p2tr_tweaked(spend_info.output_key()));
+        psbt.inputs[0].tap_internal_key = Some(internal_key);
+        psbt.inputs[0].tap_scripts.insert(control_block.clone(), (leaf_script.clone(), LeafVersion::TapScript));
+        assert_eq!(signing_state(&psbt), (0, Some(2), PsbtRole::Signer));
+        assert_eq!(analysis(&psbt).inputs[0].missing_signatures.len(), 3);
+        let unsigned_vsize = analysis(&psbt).estimated_vsize;
+
+        let leaf_hash = TapLeafHash::from_script(&leaf_script, LeafVersion::TapScript);
+        let spent = psbt.inputs[0].witness_utxo.clone().unwrap();
+        let sighash = SighashCache::new(&psbt.unsigned_tx)
+            .taproot_script_spend_signature_hash(0, &Prevouts::All(&[spent]), leaf_hash, TapSighashType::Default)
+            .unwrap();
+        let signature = |i: u8| taproot::Signature {
+            sig: secp.sign_schnorr_no_aux_rand(&Message::from_digest(sighash.to_byte_array()), &keypair(&secp, i)),
+            hash_ty: TapSighashType::Default,
+        };
+
+        psbt.inputs[0].tap_script_sigs.insert((keys[0], leaf_hash), signature(1));
+        assert_eq!(signing_state(&psbt), (1, Some(2), PsbtRole::Signer));
+        assert_eq!(analysis(&psbt).inputs[0].missing_signatures, [keys[1].to_string(), keys[2].to_string()]);
+        psbt.inputs[0].tap_script_sigs.insert((keys[2], leaf_hash), signature(3));
+        assert_eq!(signing_state(&psbt), (2, Some(2), PsbtRole::Finalizer));
+
+        // the first key's signature is consumed first, so it goes on top of the stack
+        let witness: Vec<Vec<u8>> = vec![signature(3).to_vec(), Vec::new(), signature(1).to_vec(), leaf_script.to_bytes(), control_block.serialize()];
+        psbt.inputs[0].final_script_witness = Some(Witness::from_slice(&witness));
+        psbt.inputs[0].tap_script_sigs.clear();
+        psbt.inputs[0].tap_scripts.clear();
+        assert_eq!(signing_state(&psbt), (2, None, PsbtRole::Extractor));
+        // 64 byte schnorr signatures make the estimate exact
+        assert_eq!(analysis(&psbt).estimated_vsize, Some(extracted_vsize(&psbt)));
+        assert_eq!(unsigned_vsize, Some(extracted_vsize(&psbt)));
+    }
+
+    #[test]
+    fn finalized_inputs_count_final_signatures() {
+        let secp = Secp256k1::new();
+
+        // P2PKH, signed in the scriptSig
+        let script_pubkey = ScriptBuf::new_p2pkh(&pubkey(&secp, 1).pubkey_hash());
+        let mut psbt = psbt_spending(&secp, script_pubkey.clone());
+        let sighash = SighashCache::new(&psbt.unsigned_tx)
+            .legacy_signature_hash(0, &script_pubkey, EcdsaSighashType::All.to_u32())
+            .unwrap();
+        let signature = PushBytesBuf::try_from(ecdsa_signature(&secp, sighash.to_byte_array(), 1).to_vec()).unwrap();
+        psbt.inputs[0].final_script_sig = Some(Builder::new().push_slice(signature).push_key(&pubkey(&secp, 1)).into_script());
+        assert_eq!(signing_state(&psbt), (1, None, PsbtRole::Extractor));
+        assert!(analysis(&psbt).inputs[0].is_final);
+
+        // taproot key path
+        let mut psbt = psbt_spending(&secp, ScriptBuf::new_p2tr(&secp, keypair(&secp, 1).x_only_public_key().0, None));
+        psbt.inputs[0].final_script_witness = Some(Witness::from_slice(&[[7; 64]]));
+        assert_eq!(signing_state(&psbt), (1, None, PsbtRole::Extractor));
+        assert_eq!(analysis(&psbt).estimated_vsize, Some(extracted_vsize(&psbt)));
+    }
+}
@@ -0,0 +1,79 @@
+use bitcoin::hashes::hmac::{Hmac, HmacEngine};
+use bitcoin::hashes::{sha256, Hash, HashEngine};
+use rand::RngCore;
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+pub struct RpcAuthCredentials {
+    pub username: String,
+    pub password: String,
+    /// Line to add to `bitcoin.conf`.
+    pub rpcauth: String,
+}
+
+/// Generate an `rpcauth=` line the same way Bitcoin Core's `share/rpcauth/rpcauth.py` does:
+/// a random 16 byte hex salt and `HMAC-SHA256(key = salt, msg = password)`. A random password
+/// is generated when none is given.
+pub fn generate_rpcauth(username: &str, password: Option<&str>) -> RpcAuthCredentials {
+    let password = match password {
+        Some(password) => password.to_string(),
+        None => hex::encode(random_bytes::<32>()),
+    };
+    let salt = hex::encode(random_bytes::<16>());
+
+    RpcAuthCredentials {
+        username: username.to_string(),
+        rpcauth: rpcauth_line(username, &salt, &password),
+        password,
+    }
+}
+
+fn rpcauth_line(username: &str, salt: &str, password: &str) -> String {
+    let mut engine = HmacEngine::<sha256::Hash>::new(salt.as_bytes());
+    engine.input(password.as_bytes());
+    let password_hmac = Hmac::<sha256::Hash>::from_engine(engine);
+    format!("rpcauth={}:{}${}", username, salt, hex::encode(&password_hmac[..]))
+}
+
+fn random_bytes<const N: usize>() -> [u8; N] {
+    let mut bytes = [0u8; N];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_bitcoin_core_rpcauth() {
+        // credentials from Bitcoin Core's test/functional/rpc_users.py
+        assert_eq!(
+            rpcauth_line("rt", "93648e835a54c573682c2eb19f882535", "cA773lm788buwYe4g4WT+05pKyNruVKjQ25x3n0DQcM="),
+            "rpcauth=rt:93648e835a54c573682c2eb19f882535$7681e9c5b74bdd85e78166031d2058e1069b3ed7ed967c93fc63abba06f31144",
+        );
+        assert_eq!(
+            rpcauth_line("rt2", "f8607b1a88861fac29dfccf9b52ff9f", "8/F3uMDw4KSEbw96U3CA1C4X05dkHDN2BPFjTgZW4KI="),
+            "rpcauth=rt2:f8607b1a88861fac29dfccf9b52ff9f$ff36a0c23c8c62b4846112e50fa888416e94c17bfd4c42f88fd8f55ec6a3137e",
+        );
+    }
+
+    #[test]
+    fn generated_line_hashes_the_password() {
+        let credentials = generate_rpcauth("alice", Some("hunter2"));
+        assert_eq!(credentials.password, "hunter2");
+
+        let (salt, _) = credentials.rpcauth.strip_prefix("rpcauth=alice:").unwrap().split_once('$').unwrap();
+        assert_eq!(salt.len(), 32);
+        assert_eq!(credentials.rpcauth, rpcauth_line("alice", salt, "hunter2"));
+    }
+
+    #[test]
+    fn random_password_when_none_given() {
+        let first = generate_rpcauth("alice", None);
+        let second = generate_rpcauth("alice", None);
+        assert_eq!(first.password.len(), 64);
+        assert_ne!(first.password, second.password);
+        assert_ne!(first.rpcauth, second.rpcauth);
+    }
+}
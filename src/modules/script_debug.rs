@@ -0,0 +1,1412 @@
+use std::collections::HashMap;
+
+use log::info;
+
+use serde::Serialize;
+
+use bitcoin::bitcoinconsensus::{self, VERIFY_ALL};
+use bitcoin::blockdata::opcodes::all::*;
+use bitcoin::blockdata::opcodes::{Class, ClassifyContext};
+use bitcoin::hashes::{hash160, ripemd160, sha1, sha256, sha256d, Hash};
+use bitcoin::key::XOnlyPublicKey;
+use bitcoin::script::{Builder, Instruction, PushBytesBuf};
+use bitcoin::secp256k1::{ecdsa, schnorr, Message, PublicKey, Secp256k1, VerifyOnly};
+use bitcoin::sighash::{Annex, Prevouts, SighashCache, TapSighashType};
+use bitcoin::taproot::{ControlBlock, LeafVersion, TapLeafHash, TAPROOT_ANNEX_PREFIX};
+use bitcoin::{consensus::{deserialize, serialize}, OutPoint, Script, ScriptBuf, Transaction, TxOut, Txid, Witness};
+
+use crate::settings::Settings;
+
+use super::errors::VerificationError;
+use super::verification::{fetch_prevouts, load_prevouts, script_type, PrevoutSource};
+
+const MAX_SCRIPT_SIZE: usize = 10_000;
+const MAX_ELEMENT_SIZE: usize = 520;
+const MAX_OPS_PER_SCRIPT: usize = 201;
+const MAX_STACK_SIZE: usize = 1000;
+const MAX_PUBKEYS_PER_MULTISIG: i64 = 20;
+const LOCKTIME_THRESHOLD: i64 = 500_000_000;
+const VALIDATION_WEIGHT_PER_SIGOP: i64 = 50;
+
+/// Where the debugger stops: after a step number, counted across all scripts of the input from 1,
+/// or after the first executed opcode of a name, e.g. `OP_CHECKSIG`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Breakpoint {
+    Step(usize),
+    Opcode(String),
+}
+
+/// The rules a script runs under.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum SigVersion {
+    Base,
+    WitnessV0,
+    Tapscript,
+}
+
+/// One opcode of an input's scripts and the stacks after it.
+#[derive(Debug, Serialize)]
+pub struct TraceStep {
+    pub step: usize,
+    pub phase: &'static str,
+    /// Position of the opcode in its script
+    pub position: usize,
+    pub opcode: String,
+    /// False inside a branch not taken
+    pub executed: bool,
+    /// Hex stack elements, top last
+    pub stack: Vec<String>,
+    pub alt_stack: Vec<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DebugResult {
+    Pass,
+    Fail,
+    /// Stopped at a breakpoint
+    Halted,
+}
+
+/// Execution of one input's scripts, with the libbitcoinconsensus verdict alongside.
+#[derive(Debug, Serialize)]
+pub struct ScriptDebugReport {
+    pub txid: Txid,
+    pub input: usize,
+    pub outpoint: OutPoint,
+    pub script_type: &'static str,
+    pub result: DebugResult,
+    pub error: Option<String>,
+    pub halted_at: Option<usize>,
+    /// libbitcoinconsensus with every flag it supports; `None` for taproot, which it predates
+    pub consensus: Option<bool>,
+    /// Whether the interpreter and libbitcoinconsensus agree, once the run is complete
+    pub agrees: Option<bool>,
+    pub steps: Vec<TraceStep>,
+}
+
+/// Why execution ended before the scripts ran out.
+enum Stop {
+    Fail(String),
+    Halt(usize),
+    /// OP_SUCCESSx, or a witness version or leaf version with no rules yet
+    Success,
+}
+
+fn fail<T>(reason: impl Into<String>) -> Result<T, Stop> {
+    Err(Stop::Fail(reason.into()))
+}
+
+/// Run input `input` of a signed transaction through the native interpreter, spent outputs
+/// coming from the node.
+pub fn debug_script(tx_hex: &str, input: usize, breakpoints: &[Breakpoint], settings: &Settings) -> Result<ScriptDebugReport, VerificationError> {
+    let tx: Transaction = deserialize(&hex::decode(tx_hex)?)?;
+    let (spent_outputs, _) = fetch_prevouts(&tx, settings)?;
+    run_debugger(&tx, input, &spent_outputs, breakpoints)
+}
+
+/// [`debug_script`] with the spent outputs taken from `prevouts`.
+pub fn debug_script_offline(tx_hex: &str, input: usize, prevouts: &PrevoutSource, breakpoints: &[Breakpoint]) -> Result<ScriptDebugReport, VerificationError> {
+    let tx: Transaction = deserialize(&hex::decode(tx_hex)?)?;
+    let spent_outputs = load_prevouts(prevouts)?;
+    run_debugger(&tx, input, &spent_outputs, breakpoints)
+}
+
+fn run_debugger(tx: &Transaction, input: usize, spent_outputs: &HashMap<OutPoint, TxOut>, breakpoints: &[Breakpoint]) -> Result<ScriptDebugReport, VerificationError> {
+    let txin = tx.input.get(input).ok_or(VerificationError::InputOutOfRange(input, tx.input.len()))?;
+    let spent = spent_outputs.get(&txin.previous_output)
+        .ok_or(VerificationError::MissingPrevout(input, txin.previous_output))?;
+    info!("Debugging input {} of {}, spending {}", input, tx.txid(), txin.previous_output);
+
+    let mut interpreter = Interpreter::new(tx, input, spent, spent_outputs, breakpoints);
+    let outcome = interpreter.verify_input();
+    let (result, error, halted_at) = match outcome {
+        Ok(()) | Err(Stop::Success) => (DebugResult::Pass, None, None),
+        Err(Stop::Fail(reason)) => (DebugResult::Fail, Some(reason), None),
+        Err(Stop::Halt(step)) => (DebugResult::Halted, None, Some(step)),
+    };
+
+    let consensus = (!spent.script_pubkey.is_p2tr()).then(|| {
+        bitcoinconsensus::verify_with_flags(spent.script_pubkey.as_bytes(), spent.value.to_sat(), &serialize(tx), input, VERIFY_ALL).is_ok()
+    });
+    let agrees = match result {
+        DebugResult::Halted => None,
+        _ => consensus.map(|consensus| consensus == (result == DebugResult::Pass)),
+    };
+    match (result, agrees) {
+        (DebugResult::Halted, _) => info!("Halted after step {}", halted_at.unwrap_or_default()),
+        (_, Some(false)) => info!("Interpreter says {:?} but libbitcoinconsensus disagrees", result),
+        _ => info!("Script {:?}", result),
+    }
+
+    Ok(ScriptDebugReport {
+        txid: tx.txid(),
+        input,
+        outpoint: txin.previous_output,
+        script_type: script_type(&spent.script_pubkey),
+        result,
+        error,
+        halted_at,
+        consensus,
+        agrees,
+        steps: interpreter.steps,
+    })
+}
+
+/// Run input `input` through the native interpreter without tracing it, for checks
+/// libbitcoinconsensus cannot make. The error is the reason the scripts failed.
+pub(crate) fn verify_natively(tx: &Transaction, input: usize, spent: &TxOut, spent_outputs: &HashMap<OutPoint, TxOut>) -> Result<(), String> {
+    match Interpreter::new(tx, input, spent, spent_outputs, &[]).verify_input() {
+        Ok(()) | Err(Stop::Success) | Err(Stop::Halt(_)) => Ok(()),
+        Err(Stop::Fail(reason)) => Err(reason),
+    }
+}
+
+/// Stacks and position of one script being executed.
+struct Frame<'s> {
+    script: &'s Script,
+    sig_version: SigVersion,
+    stack: Vec<Vec<u8>>,
+    alt_stack: Vec<Vec<u8>>,
+    /// Whether each enclosing IF branch is taken
+    conditions: Vec<bool>,
+    op_count: usize,
+    /// Byte offset the script code of legacy and segwit v0 signatures starts at
+    code_separator: usize,
+    /// Opcode position of the last OP_CODESEPARATOR, for tapscript signatures
+    code_separator_position: u32,
+}
+
+impl Frame<'_> {
+    fn pop(&mut self) -> Result<Vec<u8>, Stop> {
+        match self.stack.pop() {
+            Some(element) => Ok(element),
+            None => fail("INVALID_STACK_OPERATION: stack is empty"),
+        }
+    }
+
+    fn pop_num(&mut self) -> Result<i64, Stop> {
+        decode_num(&self.pop()?, 4)
+    }
+
+    fn push_num(&mut self, n: i64) {
+        self.stack.push(encode_num(n));
+    }
+
+    fn push_bool(&mut self, b: bool) {
+        self.stack.push(if b { vec![1] } else { vec![] });
+    }
+
+    /// The element `depth` from the top, 0 being the top.
+    fn peek(&self, depth: usize) -> Result<&Vec<u8>, Stop> {
+        match self.stack.len().checked_sub(depth + 1) {
+            Some(i) => Ok(&self.stack[i]),
+            None => fail("INVALID_STACK_OPERATION: stack too short"),
+        }
+    }
+
+    fn context(&self) -> ClassifyContext {
+        match self.sig_version {
+            SigVersion::Tapscript => ClassifyContext::TapScript,
+            _ => ClassifyContext::Legacy,
+        }
+    }
+
+    /// The script a legacy or segwit v0 signature commits to: everything after the last
+    /// OP_CODESEPARATOR, with legacy scripts dropping every push of the `signatures` checked.
+    fn script_code(&self, signatures: &[Vec<u8>]) -> ScriptBuf {
+        let script_code = ScriptBuf::from(self.script.as_bytes()[self.code_separator..].to_vec());
+        match self.sig_version {
+            SigVersion::Base => signatures.iter().fold(script_code, |script_code, signature| find_and_delete(&script_code, signature)),
+            _ => script_code,
+        }
+    }
+
+    fn require(&self, n: usize) -> Result<(), Stop> {
+        match self.stack.len() >= n {
+            true => Ok(()),
+            false => fail("INVALID_STACK_OPERATION: stack too short"),
+        }
+    }
+}
+
+struct Interpreter<'a> {
+    tx: &'a Transaction,
+    input: usize,
+    spent: &'a TxOut,
+    spent_outputs: &'a HashMap<OutPoint, TxOut>,
+    breakpoints: &'a [Breakpoint],
+    secp: Secp256k1<VerifyOnly>,
+    steps: Vec<TraceStep>,
+    // tapscript state
+    annex: Option<Vec<u8>>,
+    tapleaf_hash: Option<TapLeafHash>,
+    validation_budget: i64,
+}
+
+impl<'a> Interpreter<'a> {
+    fn new(tx: &'a Transaction, input: usize, spent: &'a TxOut, spent_outputs: &'a HashMap<OutPoint, TxOut>, breakpoints: &'a [Breakpoint]) -> Self {
+        Interpreter {
+            tx,
+            input,
+            spent,
+            spent_outputs,
+            breakpoints,
+            secp: Secp256k1::verification_only(),
+            steps: Vec::new(),
+            annex: None,
+            tapleaf_hash: None,
+            validation_budget: 0,
+        }
+    }
+
+    /// Bitcoin Core's `VerifyScript` with every consensus rule: scriptSig, scriptPubKey, then the
+    /// P2SH redeem script and witness program when the scriptPubKey calls for them.
+    fn verify_input(&mut self) -> Result<(), Stop> {
+        let txin = &self.tx.input[self.input];
+        let script_sig = txin.script_sig.clone();
+        let script_pubkey = self.spent.script_pubkey.clone();
+        let witness = txin.witness.clone();
+
+        let stack = self.execute(&script_sig, Vec::new(), SigVersion::Base, "scriptSig")?;
+        let p2sh_stack = stack.clone();
+        let stack = self.execute(&script_pubkey, stack, SigVersion::Base, "scriptPubKey")?;
+        if !stack.last().is_some_and(|top| cast_to_bool(top)) {
+            return fail("EVAL_FALSE: scriptPubKey left false on the stack");
+        }
+
+        let mut witness_used = false;
+        if let Some((version, program)) = witness_program(&script_pubkey) {
+            if !script_sig.is_empty() {
+                return fail("WITNESS_MALLEATED: scriptSig must be empty when spending a witness program");
+            }
+            witness_used = true;
+            self.verify_witness_program(version, &program, &witness, false)?;
+        } else if script_pubkey.is_p2sh() {
+            if !script_sig.is_push_only() {
+                return fail("SIG_PUSHONLY: a P2SH scriptSig may only push data");
+            }
+            let mut stack = p2sh_stack;
+            let redeem_script = match stack.pop() {
+                Some(redeem_script) => ScriptBuf::from(redeem_script),
+                None => return fail("EVAL_FALSE: no redeem script"),
+            };
+            let stack = self.execute(&redeem_script, stack, SigVersion::Base, "redeemScript")?;
+            if !stack.last().is_some_and(|top| cast_to_bool(top)) {
+                return fail("EVAL_FALSE: redeem script left false on the stack");
+            }
+
+            if let Some((version, program)) = witness_program(&redeem_script) {
+                if script_sig != push_script(redeem_script.as_bytes()) {
+                    return fail("WITNESS_MALLEATED_P2SH: scriptSig must only push the redeem script");
+                }
+                witness_used = true;
+                self.verify_witness_program(version, &program, &witness, true)?;
+            }
+        }
+
+        if !witness_used && !witness.is_empty() {
+            return fail("WITNESS_UNEXPECTED: witness given for a non-witness output");
+        }
+        Ok(())
+    }
+
+    fn verify_witness_program(&mut self, version: u8, program: &[u8], witness: &Witness, is_p2sh: bool) -> Result<(), Stop> {
+        let mut stack: Vec<Vec<u8>> = witness.iter().map(<[u8]>::to_vec).collect();
+
+        match (version, program.len()) {
+            (0, 20) => {
+                if stack.len() != 2 {
+                    return fail("WITNESS_PROGRAM_MISMATCH: P2WPKH needs a signature and a public key");
+                }
+                let script_code = Builder::new()
+                    .push_opcode(OP_DUP)
+                    .push_opcode(OP_HASH160)
+                    .push_slice(<&[u8; 20]>::try_from(program).expect("20 byte program"))
+                    .push_opcode(OP_EQUALVERIFY)
+                    .push_opcode(OP_CHECKSIG)
+                    .into_script();
+                self.execute_witness_script(&script_code, stack, SigVersion::WitnessV0, "p2wpkh")
+            }
+            (0, 32) => {
+                let witness_script = match stack.pop() {
+                    Some(witness_script) => ScriptBuf::from(witness_script),
+                    None => return fail("WITNESS_PROGRAM_WITNESS_EMPTY: no witness script"),
+                };
+                if sha256::Hash::hash(witness_script.as_bytes()).as_byte_array() != program {
+                    return fail("WITNESS_PROGRAM_MISMATCH: witness script does not hash to the program");
+                }
+                self.execute_witness_script(&witness_script, stack, SigVersion::WitnessV0, "witnessScript")
+            }
+            (0, _) => fail("WITNESS_PROGRAM_WRONG_LENGTH: segwit v0 programs are 20 or 32 bytes"),
+            (1, 32) if !is_p2sh => {
+                if stack.is_empty() {
+                    return fail("WITNESS_PROGRAM_WITNESS_EMPTY: no taproot witness");
+                }
+                if stack.len() >= 2 && stack.last().is_some_and(|last| last.first() == Some(&TAPROOT_ANNEX_PREFIX)) {
+                    self.annex = stack.pop();
+                }
+                let output_key = XOnlyPublicKey::from_slice(program)
+                    .or_else(|_| fail("WITNESS_PROGRAM_MISMATCH: program is not a valid x-only key"))?;
+
+                if stack.len() == 1 {
+                    return self.verify_key_path(&stack[0], &output_key);
+                }
+
+                let control_block = ControlBlock::decode(&stack.pop().unwrap_or_default())
+                    .or_else(|e| fail(format!("TAPROOT_WRONG_CONTROL_SIZE: {}", e)))?;
+                let script = ScriptBuf::from(stack.pop().unwrap_or_default());
+                if !control_block.verify_taproot_commitment(&self.secp, output_key, &script) {
+                    return fail("WITNESS_PROGRAM_MISMATCH: control block does not commit to the script");
+                }
+                if control_block.leaf_version != LeafVersion::TapScript {
+                    return Err(Stop::Success);
+                }
+
+                self.tapleaf_hash = Some(TapLeafHash::from_script(&script, LeafVersion::TapScript));
+                self.validation_budget = witness.size() as i64 + VALIDATION_WEIGHT_PER_SIGOP;
+                // any OP_SUCCESSx makes the script succeed, unless it fails to decode first
+                for instruction in script.instructions() {
+                    match instruction {
+                        Ok(Instruction::Op(op)) if op.classify(ClassifyContext::TapScript) == Class::SuccessOp => return Err(Stop::Success),
+                        Ok(_) => {}
+                        Err(e) => return fail(format!("BAD_OPCODE: {}", e)),
+                    }
+                }
+                self.execute_witness_script(&script, stack, SigVersion::Tapscript, "tapscript")
+            }
+            // future witness versions, and taproot inside P2SH, are anyone-can-spend for now
+            _ => Err(Stop::Success),
+        }
+    }
+
+    /// Run a witness script, which must leave exactly one true element.
+    fn execute_witness_script(&mut self, script: &Script, stack: Vec<Vec<u8>>, sig_version: SigVersion, phase: &'static str) -> Result<(), Stop> {
+        if stack.iter().any(|element| element.len() > MAX_ELEMENT_SIZE) {
+            return fail("PUSH_SIZE: witness element larger than 520 bytes");
+        }
+        let stack = self.execute(script, stack, sig_version, phase)?;
+        if stack.len() != 1 {
+            return fail(format!("CLEANSTACK: witness script left {} elements, expected 1", stack.len()));
+        }
+        if !cast_to_bool(&stack[0]) {
+            return fail("EVAL_FALSE: witness script left false on the stack");
+        }
+        Ok(())
+    }
+
+    fn verify_key_path(&mut self, signature: &[u8], output_key: &XOnlyPublicKey) -> Result<(), Stop> {
+        let valid = self.check_schnorr(signature, output_key)?;
+        self.record("keypath", 0, "schnorr signature".to_string(), true, &[vec![u8::from(valid)]], &[])?;
+        match valid {
+            true => Ok(()),
+            false => fail("SCHNORR_SIG: invalid key path signature"),
+        }
+    }
+
+    /// Bitcoin Core's `EvalScript`, recording every opcode.
+    fn execute(&mut self, script: &Script, stack: Vec<Vec<u8>>, sig_version: SigVersion, phase: &'static str) -> Result<Vec<Vec<u8>>, Stop> {
+        if sig_version != SigVersion::Tapscript && script.len() > MAX_SCRIPT_SIZE {
+            return fail("SCRIPT_SIZE: script larger than 10000 bytes");
+        }
+        let mut frame = Frame {
+            script,
+            sig_version,
+            stack,
+            alt_stack: Vec::new(),
+            conditions: Vec::new(),
+            op_count: 0,
+            code_separator: 0,
+            code_separator_position: u32::MAX,
+        };
+
+        for (position, instruction) in script.instruction_indices().enumerate() {
+            let (offset, instruction) = instruction.or_else(|e| fail(format!("BAD_OPCODE: {}", e)))?;
+            let executing = frame.conditions.iter().all(|taken| *taken);
+            let opcode_name = instruction_name(script, offset, &instruction);
+
+            // the step that fails is recorded too, with the stacks as the failure left them
+            let outcome = self.execute_instruction(&mut frame, instruction, executing, offset, position);
+            let recorded = self.record(phase, position, opcode_name, executing, &frame.stack, &frame.alt_stack);
+            outcome?;
+            recorded?;
+        }
+
+        if !frame.conditions.is_empty() {
+            return fail("UNBALANCED_CONDITIONAL: OP_IF without OP_ENDIF");
+        }
+        Ok(frame.stack)
+    }
+
+    fn execute_instruction(&mut self, frame: &mut Frame, instruction: Instruction, executing: bool, offset: usize, position: usize) -> Result<(), Stop> {
+        match instruction {
+            Instruction::PushBytes(data) => {
+                if data.len() > MAX_ELEMENT_SIZE {
+                    return fail("PUSH_SIZE: push larger than 520 bytes");
+                }
+                if executing {
+                    frame.stack.push(data.as_bytes().to_vec());
+                }
+            }
+            Instruction::Op(op) => {
+                if frame.sig_version != SigVersion::Tapscript && op.to_u8() > OP_PUSHNUM_16.to_u8() {
+                    frame.op_count += 1;
+                    if frame.op_count > MAX_OPS_PER_SCRIPT {
+                        return fail("OP_COUNT: more than 201 opcodes");
+                    }
+                }
+                if op.classify(frame.context()) == Class::IllegalOp {
+                    return fail(format!("DISABLED_OPCODE: {}", op));
+                }
+                let is_conditional = (OP_IF.to_u8()..=OP_ENDIF.to_u8()).contains(&op.to_u8());
+                if executing || is_conditional {
+                    self.execute_opcode(frame, op, executing, offset, position)?;
+                }
+            }
+        }
+
+        if frame.stack.len() + frame.alt_stack.len() > MAX_STACK_SIZE {
+            return fail("STACK_SIZE: more than 1000 stack elements");
+        }
+        Ok(())
+    }
+
+    fn execute_opcode(&mut self, frame: &mut Frame, op: bitcoin::Opcode, executing: bool, offset: usize, position: usize) -> Result<(), Stop> {
+        // opcodes that end the script when executed, which differ between legacy and tapscript
+        if op.classify(frame.context()) == Class::ReturnOp {
+            return match op {
+                OP_RETURN => fail("OP_RETURN: script returned early"),
+                OP_CHECKMULTISIG | OP_CHECKMULTISIGVERIFY => fail("TAPSCRIPT_CHECKMULTISIG: OP_CHECKMULTISIG is disabled in tapscript"),
+                _ => fail(format!("BAD_OPCODE: {} executed", op)),
+            };
+        }
+
+        match op {
+            OP_IF | OP_NOTIF => {
+                let mut taken = false;
+                if executing {
+                    let condition = frame.pop()?;
+                    if frame.sig_version == SigVersion::Tapscript && !(condition.is_empty() || condition == [1]) {
+                        return fail("MINIMALIF: tapscript OP_IF argument must be empty or 0x01");
+                    }
+                    taken = cast_to_bool(&condition) == (op == OP_IF);
+                }
+                frame.conditions.push(taken);
+            }
+            OP_ELSE => match frame.conditions.last_mut() {
+                Some(taken) => *taken = !*taken,
+                None => return fail("UNBALANCED_CONDITIONAL: OP_ELSE without OP_IF"),
+            },
+            OP_ENDIF => {
+                if frame.conditions.pop().is_none() {
+                    return fail("UNBALANCED_CONDITIONAL: OP_ENDIF without OP_IF");
+                }
+            }
+            OP_VERIFY => {
+                if !cast_to_bool(&frame.pop()?) {
+                    return fail("VERIFY: OP_VERIFY on false");
+                }
+            }
+            OP_CLTV => self.check_lock_time(frame)?,
+            OP_CSV => self.check_sequence(frame)?,
+
+            OP_TOALTSTACK => {
+                let element = frame.pop()?;
+                frame.alt_stack.push(element);
+            }
+            OP_FROMALTSTACK => match frame.alt_stack.pop() {
+                Some(element) => frame.stack.push(element),
+                None => return fail("INVALID_ALTSTACK_OPERATION: alt stack is empty"),
+            },
+            OP_2DROP => {
+                frame.require(2)?;
+                frame.stack.truncate(frame.stack.len() - 2);
+            }
+            OP_2DUP => {
+                let (a, b) = (frame.peek(1)?.clone(), frame.peek(0)?.clone());
+                frame.stack.extend([a, b]);
+            }
+            OP_3DUP => {
+                let (a, b, c) = (frame.peek(2)?.clone(), frame.peek(1)?.clone(), frame.peek(0)?.clone());
+                frame.stack.extend([a, b, c]);
+            }
+            OP_2OVER => {
+                let (a, b) = (frame.peek(3)?.clone(), frame.peek(2)?.clone());
+                frame.stack.extend([a, b]);
+            }
+            OP_2ROT => {
+                frame.require(6)?;
+                let at = frame.stack.len() - 6;
+                let moved: Vec<Vec<u8>> = frame.stack.drain(at..at + 2).collect();
+                frame.stack.extend(moved);
+            }
+            OP_2SWAP => {
+                frame.require(4)?;
+                let len = frame.stack.len();
+                frame.stack[len - 4..].rotate_left(2);
+            }
+            OP_IFDUP => {
+                let top = frame.peek(0)?.clone();
+                if cast_to_bool(&top) {
+                    frame.stack.push(top);
+                }
+            }
+            OP_DEPTH => frame.push_num(frame.stack.len() as i64),
+            OP_DROP => {
+                frame.pop()?;
+            }
+            OP_DUP => {
+                let top = frame.peek(0)?.clone();
+                frame.stack.push(top);
+            }
+            OP_NIP => {
+                frame.require(2)?;
+                let at = frame.stack.len() - 2;
+                frame.stack.remove(at);
+            }
+            OP_OVER => {
+                let second = frame.peek(1)?.clone();
+                frame.stack.push(second);
+            }
+            OP_PICK | OP_ROLL => {
+                let n = frame.pop_num()?;
+                if n < 0 || n as usize >= frame.stack.len() {
+                    return fail("INVALID_STACK_OPERATION: OP_PICK/OP_ROLL index out of range");
+                }
+                let at = frame.stack.len() - 1 - n as usize;
+                let element = match op == OP_ROLL {
+                    true => frame.stack.remove(at),
+                    false => frame.stack[at].clone(),
+                };
+                frame.stack.push(element);
+            }
+            OP_ROT => {
+                frame.require(3)?;
+                let len = frame.stack.len();
+                frame.stack[len - 3..].rotate_left(1);
+            }
+            OP_SWAP => {
+                frame.require(2)?;
+                let len = frame.stack.len();
+                frame.stack.swap(len - 2, len - 1);
+            }
+            OP_TUCK => {
+                frame.require(2)?;
+                let top = frame.peek(0)?.clone();
+                let at = frame.stack.len() - 2;
+                frame.stack.insert(at, top);
+            }
+            OP_SIZE => {
+                let size = frame.peek(0)?.len();
+                frame.push_num(size as i64);
+            }
+
+            OP_EQUAL | OP_EQUALVERIFY => {
+                let (b, a) = (frame.pop()?, frame.pop()?);
+                frame.push_bool(a == b);
+                if op == OP_EQUALVERIFY {
+                    Self::verify_top(frame, "EQUALVERIFY: elements differ")?;
+                }
+            }
+
+            OP_1ADD | OP_1SUB | OP_NEGATE | OP_ABS | OP_NOT | OP_0NOTEQUAL => {
+                let n = frame.pop_num()?;
+                let result = match op {
+                    OP_1ADD => n + 1,
+                    OP_1SUB => n - 1,
+                    OP_NEGATE => -n,
+                    OP_ABS => n.abs(),
+                    OP_NOT => i64::from(n == 0),
+                    _ => i64::from(n != 0),
+                };
+                frame.push_num(result);
+            }
+            OP_ADD | OP_SUB | OP_BOOLAND | OP_BOOLOR | OP_NUMEQUAL | OP_NUMEQUALVERIFY | OP_NUMNOTEQUAL
+            | OP_LESSTHAN | OP_GREATERTHAN | OP_LESSTHANOREQUAL | OP_GREATERTHANOREQUAL | OP_MIN | OP_MAX => {
+                let (b, a) = (frame.pop_num()?, frame.pop_num()?);
+                let result = match op {
+                    OP_ADD => a + b,
+                    OP_SUB => a - b,
+                    OP_BOOLAND => i64::from(a != 0 && b != 0),
+                    OP_BOOLOR => i64::from(a != 0 || b != 0),
+                    OP_NUMEQUAL | OP_NUMEQUALVERIFY => i64::from(a == b),
+                    OP_NUMNOTEQUAL => i64::from(a != b),
+                    OP_LESSTHAN => i64::from(a < b),
+                    OP_GREATERTHAN => i64::from(a > b),
+                    OP_LESSTHANOREQUAL => i64::from(a <= b),
+                    OP_GREATERTHANOREQUAL => i64::from(a >= b),
+                    OP_MIN => a.min(b),
+                    _ => a.max(b),
+                };
+                frame.push_num(result);
+                if op == OP_NUMEQUALVERIFY {
+                    Self::verify_top(frame, "NUMEQUALVERIFY: numbers differ")?;
+                }
+            }
+            OP_WITHIN => {
+                let (max, min, x) = (frame.pop_num()?, frame.pop_num()?, frame.pop_num()?);
+                frame.push_bool(min <= x && x < max);
+            }
+
+            OP_RIPEMD160 | OP_SHA1 | OP_SHA256 | OP_HASH160 | OP_HASH256 => {
+                let data = frame.pop()?;
+                let digest = match op {
+                    OP_RIPEMD160 => ripemd160::Hash::hash(&data).to_byte_array().to_vec(),
+                    OP_SHA1 => sha1::Hash::hash(&data).to_byte_array().to_vec(),
+                    OP_SHA256 => sha256::Hash::hash(&data).to_byte_array().to_vec(),
+                    OP_HASH160 => hash160::Hash::hash(&data).to_byte_array().to_vec(),
+                    _ => sha256d::Hash::hash(&data).to_byte_array().to_vec(),
+                };
+                frame.stack.push(digest);
+            }
+            OP_CODESEPARATOR => {
+                frame.code_separator = offset + 1;
+                frame.code_separator_position = position as u32;
+            }
+            OP_CHECKSIG | OP_CHECKSIGVERIFY => {
+                let (pubkey, signature) = (frame.pop()?, frame.pop()?);
+                let script_code = frame.script_code(std::slice::from_ref(&signature));
+                let valid = self.check_signature(frame, &script_code, &signature, &pubkey)?;
+                frame.push_bool(valid);
+                if op == OP_CHECKSIGVERIFY {
+                    Self::verify_top(frame, "CHECKSIGVERIFY: signature check failed")?;
+                }
+            }
+            OP_CHECKSIGADD => {
+                let pubkey = frame.pop()?;
+                let n = frame.pop_num()?;
+                let signature = frame.pop()?;
+                let valid = self.check_signature(frame, frame.script, &signature, &pubkey)?;
+                frame.push_num(n + i64::from(valid));
+            }
+            OP_CHECKMULTISIG | OP_CHECKMULTISIGVERIFY => {
+                let valid = self.check_multisig(frame)?;
+                frame.push_bool(valid);
+                if op == OP_CHECKMULTISIGVERIFY {
+                    Self::verify_top(frame, "CHECKMULTISIGVERIFY: signature check failed")?;
+                }
+            }
+
+            _ => match op.classify(frame.context()) {
+                Class::PushNum(n) => frame.push_num(n as i64),
+                Class::NoOp => {}
+                _ => return fail(format!("BAD_OPCODE: {} executed", op)),
+            },
+        }
+        Ok(())
+    }
+
+    /// Pop the result an ...VERIFY opcode just pushed, failing if it is false.
+    fn verify_top(frame: &mut Frame, reason: &str) -> Result<(), Stop> {
+        match cast_to_bool(&frame.pop()?) {
+            true => Ok(()),
+            false => fail(reason),
+        }
+    }
+
+    /// BIP65: the top number is a locktime the transaction's own locktime must have reached.
+    fn check_lock_time(&self, frame: &Frame) -> Result<(), Stop> {
+        let lock_time = decode_num(frame.peek(0)?, 5)?;
+        if lock_time < 0 {
+            return fail("NEGATIVE_LOCKTIME: OP_CHECKLOCKTIMEVERIFY on a negative number");
+        }
+        let tx_lock_time = self.tx.lock_time.to_consensus_u32() as i64;
+        if (tx_lock_time < LOCKTIME_THRESHOLD) != (lock_time < LOCKTIME_THRESHOLD) || lock_time > tx_lock_time {
+            return fail(format!("UNSATISFIED_LOCKTIME: needs locktime {}, transaction has {}", lock_time, tx_lock_time));
+        }
+        if self.tx.input[self.input].sequence.is_final() {
+            return fail("UNSATISFIED_LOCKTIME: input sequence is final, so locktime is ignored");
+        }
+        Ok(())
+    }
+
+    /// BIP112: the top number is a relative locktime the input's sequence must have reached.
+    fn check_sequence(&self, frame: &Frame) -> Result<(), Stop> {
+        const DISABLE_FLAG: i64 = 1 << 31;
+        const TYPE_FLAG: i64 = 1 << 22;
+        const MASK: i64 = TYPE_FLAG | 0xffff;
+
+        let sequence = decode_num(frame.peek(0)?, 5)?;
+        if sequence < 0 {
+            return fail("NEGATIVE_LOCKTIME: OP_CHECKSEQUENCEVERIFY on a negative number");
+        }
+        if sequence & DISABLE_FLAG != 0 {
+            return Ok(());
+        }
+        if self.tx.version.0 < 2 {
+            return fail("UNSATISFIED_LOCKTIME: OP_CHECKSEQUENCEVERIFY needs transaction version 2");
+        }
+        let tx_sequence = self.tx.input[self.input].sequence.0 as i64;
+        if tx_sequence & DISABLE_FLAG != 0 {
+            return fail("UNSATISFIED_LOCKTIME: input sequence disables relative locktime");
+        }
+        let (required, actual) = (sequence & MASK, tx_sequence & MASK);
+        if (required & TYPE_FLAG) != (actual & TYPE_FLAG) || required > actual {
+            return fail(format!("UNSATISFIED_LOCKTIME: needs sequence {:#x}, input has {:#x}", required, actual));
+        }
+        Ok(())
+    }
+
+    /// Bitcoin Core's OP_CHECKMULTISIG: signatures and keys are walked in the same order as Core
+    /// does, from the top of the stack, so the first malformed signature it reaches is ours too.
+    fn check_multisig(&mut self, frame: &mut Frame) -> Result<bool, Stop> {
+        let key_count = frame.pop_num()?;
+        if !(0..=MAX_PUBKEYS_PER_MULTISIG).contains(&key_count) {
+            return fail("PUBKEY_COUNT: between 0 and 20 keys allowed");
+        }
+        frame.op_count += key_count as usize;
+        if frame.op_count > MAX_OPS_PER_SCRIPT {
+            return fail("OP_COUNT: more than 201 opcodes");
+        }
+        let pubkeys: Vec<Vec<u8>> = (0..key_count).map(|_| frame.pop()).collect::<Result<_, _>>()?;
+        let sig_count = frame.pop_num()?;
+        if sig_count < 0 || sig_count > key_count {
+            return fail("SIG_COUNT: more signatures than keys");
+        }
+        let signatures: Vec<Vec<u8>> = (0..sig_count).map(|_| frame.pop()).collect::<Result<_, _>>()?;
+        // the extra element the original implementation pops by mistake must be empty (BIP147)
+        if !frame.pop()?.is_empty() {
+            return fail("SIG_NULLDUMMY: CHECKMULTISIG dummy element must be empty");
+        }
+
+        // no signature can sign another, so all are removed before any is checked
+        let script_code = frame.script_code(&signatures);
+        let (mut isig, mut ikey) = (0, 0);
+        while isig < signatures.len() {
+            if self.check_signature(frame, &script_code, &signatures[isig], &pubkeys[ikey])? {
+                isig += 1;
+            }
+            ikey += 1;
+            // more signatures left than keys to match them
+            if signatures.len() - isig > pubkeys.len() - ikey {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// CHECKSIG for the frame's rules. Returns false for a signature that does not verify where
+    /// the rules allow it, and fails the script where they do not.
+    fn check_signature(&mut self, frame: &Frame, script_code: &Script, signature: &[u8], pubkey: &[u8]) -> Result<bool, Stop> {
+        if frame.sig_version == SigVersion::Tapscript {
+            if pubkey.is_empty() {
+                return fail("PUBKEYTYPE: empty public key in tapscript");
+            }
+            if signature.is_empty() {
+                return Ok(false);
+            }
+            self.validation_budget -= VALIDATION_WEIGHT_PER_SIGOP;
+            if self.validation_budget < 0 {
+                return fail("TAPSCRIPT_VALIDATION_WEIGHT: too many signature checks for the witness size");
+            }
+            if pubkey.len() != 32 {
+                // unknown public key types succeed, reserved for upgrades
+                return Ok(true);
+            }
+            let key = XOnlyPublicKey::from_slice(pubkey).or_else(|_| fail("PUBKEYTYPE: invalid x-only public key"))?;
+            return match self.check_schnorr_in_script(signature, &key, frame.code_separator_position)? {
+                true => Ok(true),
+                false => fail("SCHNORR_SIG: invalid tapscript signature"),
+            };
+        }
+
+        if signature.is_empty() {
+            return Ok(false);
+        }
+        let (sighash_byte, der) = signature.split_last().expect("signature is not empty");
+        let mut ecdsa_signature = ecdsa::Signature::from_der(der)
+            .or_else(|_| fail("SIG_DER: signature is not strict DER (BIP66)"))?;
+        let Ok(pubkey) = PublicKey::from_slice(pubkey) else {
+            return Ok(false);
+        };
+
+        let sighash_type = u32::from(*sighash_byte);
+        let digest = match frame.sig_version {
+            SigVersion::Base => SighashCache::new(self.tx).legacy_signature_hash(self.input, script_code, sighash_type)
+                .map(|sighash| sighash.to_byte_array())
+                .map_err(|e| e.to_string()),
+            _ => Ok(self.segwit_v0_sighash(script_code, sighash_type)),
+        };
+        let digest = digest.or_else(|e| fail(format!("SIGHASH: {}", e)))?;
+
+        // consensus accepts high-S signatures, libsecp256k1 only verifies normalized ones
+        ecdsa_signature.normalize_s();
+        Ok(self.secp.verify_ecdsa(&Message::from_digest(digest), &ecdsa_signature, &pubkey).is_ok())
+    }
+
+    /// BIP143 signature hash with the sighash type exactly as the signature carries it, since
+    /// consensus commits to non-standard types byte for byte.
+    fn segwit_v0_sighash(&self, script_code: &Script, sighash_type: u32) -> [u8; 32] {
+        const SIGHASH_NONE: u32 = 0x02;
+        const SIGHASH_SINGLE: u32 = 0x03;
+        const SIGHASH_ANYONECANPAY: u32 = 0x80;
+
+        let anyone_can_pay = sighash_type & SIGHASH_ANYONECANPAY != 0;
+        let base_type = sighash_type & 0x1f;
+        let hash_all = |data: Vec<u8>| sha256d::Hash::hash(&data).to_byte_array();
+
+        let hash_prevouts = match anyone_can_pay {
+            true => [0; 32],
+            false => hash_all(self.tx.input.iter().flat_map(|input| serialize(&input.previous_output)).collect()),
+        };
+        let hash_sequence = match anyone_can_pay || base_type == SIGHASH_SINGLE || base_type == SIGHASH_NONE {
+            true => [0; 32],
+            false => hash_all(self.tx.input.iter().flat_map(|input| serialize(&input.sequence)).collect()),
+        };
+        let hash_outputs = match base_type {
+            SIGHASH_NONE => [0; 32],
+            SIGHASH_SINGLE => match self.tx.output.get(self.input) {
+                Some(output) => hash_all(serialize(output)),
+                None => [0; 32],
+            },
+            _ => hash_all(self.tx.output.iter().flat_map(serialize).collect()),
+        };
+
+        let txin = &self.tx.input[self.input];
+        let mut preimage = serialize(&self.tx.version);
+        preimage.extend(hash_prevouts);
+        preimage.extend(hash_sequence);
+        preimage.extend(serialize(&txin.previous_output));
+        preimage.extend(serialize(script_code));
+        preimage.extend(serialize(&self.spent.value));
+        preimage.extend(serialize(&txin.sequence));
+        preimage.extend(hash_outputs);
+        preimage.extend(serialize(&self.tx.lock_time));
+        preimage.extend(sighash_type.to_le_bytes());
+        hash_all(preimage)
+    }
+
+    fn check_schnorr(&mut self, signature: &[u8], key: &XOnlyPublicKey) -> Result<bool, Stop> {
+        self.verify_schnorr(signature, key, None)
+    }
+
+    fn check_schnorr_in_script(&mut self, signature: &[u8], key: &XOnlyPublicKey, code_separator_position: u32) -> Result<bool, Stop> {
+        let leaf_hash = self.tapleaf_hash.expect("tapscript runs with a leaf hash");
+        self.verify_schnorr(signature, key, Some((leaf_hash, code_separator_position)))
+    }
+
+    /// BIP341 signature check, for the key path without a leaf hash.
+    fn verify_schnorr(&mut self, signature: &[u8], key: &XOnlyPublicKey, leaf: Option<(TapLeafHash, u32)>) -> Result<bool, Stop> {
+        let (signature, sighash_type) = match signature.len() {
+            64 => (signature, TapSighashType::Default),
+            65 => match TapSighashType::from_consensus_u8(signature[64]) {
+                Ok(TapSighashType::Default) | Err(_) => return fail("SCHNORR_SIG_HASHTYPE: invalid sighash type"),
+                Ok(sighash_type) => (&signature[..64], sighash_type),
+            },
+            _ => return fail("SCHNORR_SIG_SIZE: schnorr signatures are 64 or 65 bytes"),
+        };
+        let Ok(signature) = schnorr::Signature::from_slice(signature) else {
+            return Ok(false);
+        };
+
+        // every output spent is part of a taproot signature hash
+        let prevouts: Vec<TxOut> = self.tx.input.iter()
+            .map(|input| self.spent_outputs.get(&input.previous_output).cloned())
+            .collect::<Option<_>>()
+            .map_or_else(|| fail("taproot signature hashes need the outputs spent by every input"), Ok)?;
+        let annex = match &self.annex {
+            Some(annex) => Some(Annex::new(annex).or_else(|e| fail(format!("SIGHASH: {}", e)))?),
+            None => None,
+        };
+        let digest = SighashCache::new(self.tx)
+            .taproot_signature_hash(self.input, &Prevouts::All(&prevouts), annex, leaf, sighash_type)
+            .or_else(|e| fail(format!("SIGHASH: {}", e)))?;
+
+        Ok(self.secp.verify_schnorr(&signature, &Message::from_digest(digest.to_byte_array()), key).is_ok())
+    }
+
+    /// Add a step to the trace, stopping if a breakpoint matches it.
+    fn record(&mut self, phase: &'static str, position: usize, opcode: String, executed: bool, stack: &[Vec<u8>], alt_stack: &[Vec<u8>]) -> Result<(), Stop> {
+        let step = self.steps.len() + 1;
+        let hit = self.breakpoints.iter().any(|breakpoint| match breakpoint {
+            Breakpoint::Step(n) => *n == step,
+            Breakpoint::Opcode(name) => executed && opcode.split(' ').next() == Some(name.as_str()),
+        });
+        self.steps.push(TraceStep {
+            step,
+            phase,
+            position,
+            opcode,
+            executed,
+            stack: stack.iter().map(hex::encode).collect(),
+            alt_stack: alt_stack.iter().map(hex::encode).collect(),
+        });
+        match hit {
+            true => Err(Stop::Halt(step)),
+            false => Ok(()),
+        }
+    }
+}
+
+/// The opcode as rust-bitcoin names it, followed by the data it pushes.
+fn instruction_name(script: &Script, offset: usize, instruction: &Instruction) -> String {
+    let opcode = bitcoin::Opcode::from(script.as_bytes()[offset]);
+    match instruction {
+        Instruction::PushBytes(data) if !data.is_empty() => format!("{} {}", opcode, hex::encode(data.as_bytes())),
+        _ => opcode.to_string(),
+    }
+}
+
+/// Version and program of a witness program script.
+fn witness_program(script: &Script) -> Option<(u8, Vec<u8>)> {
+    if !script.is_witness_program() {
+        return None;
+    }
+    let version = script.witness_version()?.to_num();
+    Some((version, script.as_bytes()[2..].to_vec()))
+}
+
+fn push_script(data: &[u8]) -> ScriptBuf {
+    let data = PushBytesBuf::try_from(data.to_vec()).unwrap_or_default();
+    Builder::new().push_slice(data).into_script()
+}
+
+/// Legacy script code drops every push of the signature being checked, as Bitcoin Core's
+/// `FindAndDelete` does.
+fn find_and_delete(script: &Script, signature: &[u8]) -> ScriptBuf {
+    let pattern = push_script(signature);
+    let bytes = script.as_bytes();
+    let mut offsets: Vec<usize> = script.instruction_indices()
+        .map_while(|instruction| instruction.ok().map(|(offset, _)| offset))
+        .collect();
+    offsets.push(bytes.len());
+
+    let kept: Vec<u8> = offsets.windows(2)
+        .filter(|window| bytes[window[0]..window[1]] != *pattern.as_bytes())
+        .flat_map(|window| bytes[window[0]..window[1]].iter().copied())
+        .collect();
+    ScriptBuf::from(kept)
+}
+
+/// Bitcoin Core's `CScriptNum`: little-endian magnitude with the sign in the top bit.
+fn decode_num(bytes: &[u8], max_len: usize) -> Result<i64, Stop> {
+    if bytes.len() > max_len {
+        return fail(format!("SCRIPTNUM_OVERFLOW: number longer than {} bytes", max_len));
+    }
+    let Some(last) = bytes.last() else {
+        return Ok(0);
+    };
+    let mut n = bytes.iter().enumerate().fold(0i64, |n, (i, byte)| n | (i64::from(*byte) << (8 * i)));
+    if last & 0x80 != 0 {
+        n &= !(0x80i64 << (8 * (bytes.len() - 1)));
+        n = -n;
+    }
+    Ok(n)
+}
+
+fn encode_num(n: i64) -> Vec<u8> {
+    let mut magnitude = n.unsigned_abs();
+    let mut bytes = Vec::new();
+    while magnitude > 0 {
+        bytes.push((magnitude & 0xff) as u8);
+        magnitude >>= 8;
+    }
+    match bytes.last_mut() {
+        Some(last) if *last & 0x80 != 0 => bytes.push(if n < 0 { 0x80 } else { 0 }),
+        Some(last) if n < 0 => *last |= 0x80,
+        _ => {}
+    }
+    bytes
+}
+
+/// False for empty elements, zeros and negative zero.
+fn cast_to_bool(bytes: &[u8]) -> bool {
+    bytes.iter().enumerate().any(|(i, byte)| *byte != 0 && !(i == bytes.len() - 1 && *byte == 0x80))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use bitcoin::absolute::LockTime;
+    use bitcoin::key::{Keypair, TapTweak};
+    use bitcoin::secp256k1::{All, SecretKey};
+    use bitcoin::sighash::EcdsaSighashType;
+    use bitcoin::taproot::TaprootBuilder;
+    use bitcoin::transaction::Version;
+    use bitcoin::{Amount, PublicKey, Sequence, TxIn};
+
+    use super::*;
+
+    const VALUE: Amount = Amount::from_sat(100_000);
+
+    fn secret_key(i: u8) -> SecretKey {
+        SecretKey::from_slice(&[i; 32]).unwrap()
+    }
+
+    fn public_key(secp: &Secp256k1<All>, i: u8) -> PublicKey {
+        PublicKey::new(secret_key(i).public_key(secp))
+    }
+
+    /// A transaction spending `script_pubkey` and the outputs it spends.
+    fn spending(script_pubkey: ScriptBuf) -> (Transaction, HashMap<OutPoint, TxOut>) {
+        let outpoint = OutPoint::new(Txid::from_str("8b9913592db63aaae7b5e6a55194507cbfde7d293d42d49aa81fa6eb195692fc").unwrap(), 1);
+        let tx = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn { previous_output: outpoint, script_sig: ScriptBuf::new(), sequence: Sequence::ENABLE_RBF_NO_LOCKTIME, witness: Witness::new() }],
+            output: vec![TxOut { value: Amount::from_sat(90_000), script_pubkey: ScriptBuf::new_op_return([0; 4]) }],
+        };
+        (tx, HashMap::from([(outpoint, TxOut { value: VALUE, script_pubkey })]))
+    }
+
+    fn pushes(elements: &[&[u8]]) -> ScriptBuf {
+        elements.iter().fold(Builder::new(), |builder, element| builder.push_slice(PushBytesBuf::try_from(element.to_vec()).unwrap()))
+            .into_script()
+    }
+
+    fn ecdsa_sign(secp: &Secp256k1<All>, key: u8, digest: [u8; 32], sighash_type: u8) -> Vec<u8> {
+        let mut signature = secp.sign_ecdsa(&Message::from_digest(digest), &secret_key(key)).serialize_der().to_vec();
+        signature.push(sighash_type);
+        signature
+    }
+
+    fn legacy_sign(secp: &Secp256k1<All>, tx: &Transaction, script_code: &Script, key: u8) -> Vec<u8> {
+        let sighash = SighashCache::new(tx).legacy_signature_hash(0, script_code, 1).unwrap();
+        ecdsa_sign(secp, key, sighash.to_byte_array(), 1)
+    }
+
+    fn segwit_sign(secp: &Secp256k1<All>, tx: &Transaction, script_code: &Script, key: u8) -> Vec<u8> {
+        let sighash = SighashCache::new(tx).p2wsh_signature_hash(0, script_code, VALUE, EcdsaSighashType::All).unwrap();
+        ecdsa_sign(secp, key, sighash.to_byte_array(), 1)
+    }
+
+    fn run(tx: &Transaction, spent_outputs: &HashMap<OutPoint, TxOut>, breakpoints: &[Breakpoint]) -> ScriptDebugReport {
+        run_debugger(tx, 0, spent_outputs, breakpoints).unwrap()
+    }
+
+    fn assert_pass(report: &ScriptDebugReport) {
+        assert_eq!(report.result, DebugResult::Pass, "{:?}", report.error);
+    }
+
+    fn assert_fail(report: &ScriptDebugReport, error: &str) {
+        assert_eq!(report.result, DebugResult::Fail);
+        assert!(report.error.as_deref().is_some_and(|e| e.starts_with(error)), "{:?}", report.error);
+    }
+
+    /// A p2pkh spend, signed unless `sign` is false.
+    fn p2pkh(secp: &Secp256k1<All>, sign: bool) -> (Transaction, HashMap<OutPoint, TxOut>) {
+        let pubkey = public_key(secp, 1);
+        let script_pubkey = ScriptBuf::new_p2pkh(&pubkey.pubkey_hash());
+        let (mut tx, spent_outputs) = spending(script_pubkey.clone());
+        let signature = match sign {
+            true => legacy_sign(secp, &tx, &script_pubkey, 1),
+            false => legacy_sign(secp, &tx, &script_pubkey, 2),
+        };
+        tx.input[0].script_sig = pushes(&[&signature, &pubkey.to_bytes()]);
+        (tx, spent_outputs)
+    }
+
+    #[test]
+    fn p2pkh_signature_checked() {
+        let secp = Secp256k1::new();
+        let (tx, spent_outputs) = p2pkh(&secp, true);
+        let report = run(&tx, &spent_outputs, &[]);
+        assert_pass(&report);
+        assert_eq!(report.agrees, Some(true));
+        assert_eq!(report.steps.last().unwrap().opcode, "OP_CHECKSIG");
+
+        let (tx, spent_outputs) = p2pkh(&secp, false);
+        let report = run(&tx, &spent_outputs, &[]);
+        assert_fail(&report, "EVAL_FALSE");
+        assert_eq!(report.agrees, Some(true));
+    }
+
+    #[test]
+    fn failing_step_is_recorded() {
+        let secp = Secp256k1::new();
+        let (mut tx, spent_outputs) = p2pkh(&secp, true);
+        let signature = tx.input[0].script_sig.instructions().next().unwrap().unwrap().push_bytes().unwrap().as_bytes().to_vec();
+        tx.input[0].script_sig = pushes(&[&signature, &public_key(&secp, 2).to_bytes()]);
+
+        let report = run(&tx, &spent_outputs, &[]);
+        assert_fail(&report, "EQUALVERIFY");
+        assert_eq!(report.steps.last().unwrap().opcode, "OP_EQUALVERIFY");
+        assert_eq!(report.agrees, Some(true));
+    }
+
+    #[test]
+    fn p2sh_multisig_checked_in_key_order() {
+        let secp = Secp256k1::new();
+        let redeem_script = Builder::new().push_int(2)
+            .push_key(&public_key(&secp, 1)).push_key(&public_key(&secp, 2)).push_key(&public_key(&secp, 3))
+            .push_int(3).push_opcode(OP_CHECKMULTISIG).into_script();
+        let (mut tx, spent_outputs) = spending(ScriptBuf::new_p2sh(&redeem_script.script_hash()));
+        let (first, third) = (legacy_sign(&secp, &tx, &redeem_script, 1), legacy_sign(&secp, &tx, &redeem_script, 3));
+
+        tx.input[0].script_sig = pushes(&[&[], &first, &third, redeem_script.as_bytes()]);
+        let report = run(&tx, &spent_outputs, &[]);
+        assert_pass(&report);
+        assert_eq!(report.agrees, Some(true));
+
+        tx.input[0].script_sig = pushes(&[&[], &third, &first, redeem_script.as_bytes()]);
+        let report = run(&tx, &spent_outputs, &[]);
+        assert_fail(&report, "EVAL_FALSE");
+        assert_eq!(report.agrees, Some(true));
+    }
+
+    #[test]
+    fn multisig_removes_every_signature_from_the_script_code() {
+        let secp = Secp256k1::new();
+        let keys = Builder::new().push_opcode(OP_DROP).push_opcode(OP_DROP).push_int(2)
+            .push_key(&public_key(&secp, 1)).push_key(&public_key(&secp, 2))
+            .push_int(2).push_opcode(OP_CHECKMULTISIG).into_script();
+        let (mut tx, mut spent_outputs) = spending(keys.clone());
+        // both signatures commit to the script with both of their pushes removed
+        let (first, second) = (legacy_sign(&secp, &tx, &keys, 1), legacy_sign(&secp, &tx, &keys, 2));
+        let mut script_pubkey = pushes(&[&first, &second]).into_bytes();
+        script_pubkey.extend(keys.as_bytes());
+        spent_outputs.values_mut().for_each(|spent| spent.script_pubkey = ScriptBuf::from(script_pubkey.clone()));
+
+        tx.input[0].script_sig = pushes(&[&[], &first, &second]);
+        let report = run(&tx, &spent_outputs, &[]);
+        assert_pass(&report);
+        assert_eq!(report.agrees, Some(true));
+    }
+
+    /// A p2wpkh spend with the signature's sighash type byte set to `sighash_type`.
+    fn p2wpkh(secp: &Secp256k1<All>, sighash_type: u8) -> (Transaction, HashMap<OutPoint, TxOut>) {
+        let pubkey = public_key(secp, 1);
+        let script_pubkey = ScriptBuf::new_p2wpkh(&pubkey.wpubkey_hash().unwrap());
+        let (mut tx, spent_outputs) = spending(script_pubkey);
+        let spent = &spent_outputs[&tx.input[0].previous_output];
+        let script_code = ScriptBuf::new_p2pkh(&pubkey.pubkey_hash());
+        let digest = Interpreter::new(&tx, 0, spent, &spent_outputs, &[]).segwit_v0_sighash(&script_code, u32::from(sighash_type));
+        tx.input[0].witness = Witness::from_slice(&[ecdsa_sign(secp, 1, digest, sighash_type), pubkey.to_bytes()]);
+        (tx, spent_outputs)
+    }
+
+    #[test]
+    fn p2wpkh_signature_checked() {
+        let secp = Secp256k1::new();
+        let (tx, spent_outputs) = p2wpkh(&secp, 1);
+        let report = run(&tx, &spent_outputs, &[]);
+        assert_pass(&report);
+        assert_eq!(report.agrees, Some(true));
+        assert_eq!(report.steps.last().unwrap().phase, "p2wpkh");
+
+        let (mut tx, spent_outputs) = p2wpkh(&secp, 1);
+        tx.output[0].value = Amount::from_sat(80_000);
+        let report = run(&tx, &spent_outputs, &[]);
+        assert_fail(&report, "EVAL_FALSE");
+        assert_eq!(report.agrees, Some(true));
+    }
+
+    #[test]
+    fn bip143_sighash_matches_rust_bitcoin_for_standard_types() {
+        let secp = Secp256k1::new();
+        let (tx, spent_outputs) = p2wpkh(&secp, 1);
+        let spent = &spent_outputs[&tx.input[0].previous_output];
+        let script_code = ScriptBuf::new_p2pkh(&public_key(&secp, 1).pubkey_hash());
+        let interpreter = Interpreter::new(&tx, 0, spent, &spent_outputs, &[]);
+        for sighash_type in [0x01, 0x02, 0x03, 0x81, 0x82, 0x83] {
+            let expected = SighashCache::new(&tx)
+                .p2wsh_signature_hash(0, &script_code, VALUE, EcdsaSighashType::from_consensus(sighash_type))
+                .unwrap();
+            assert_eq!(interpreter.segwit_v0_sighash(&script_code, sighash_type), expected.to_byte_array());
+        }
+    }
+
+    #[test]
+    fn non_standard_sighash_type_committed_as_is() {
+        let secp = Secp256k1::new();
+        let (tx, spent_outputs) = p2wpkh(&secp, 0x04);
+        let report = run(&tx, &spent_outputs, &[]);
+        assert_pass(&report);
+        assert_eq!(report.consensus, Some(true));
+    }
+
+    fn p2wsh(witness_script: &ScriptBuf) -> (Transaction, HashMap<OutPoint, TxOut>) {
+        spending(ScriptBuf::new_p2wsh(&witness_script.wscript_hash()))
+    }
+
+    #[test]
+    fn p2wsh_script_checked() {
+        let secp = Secp256k1::new();
+        let witness_script = Builder::new().push_key(&public_key(&secp, 1)).push_opcode(OP_CHECKSIG).into_script();
+        let (mut tx, spent_outputs) = p2wsh(&witness_script);
+        let signature = segwit_sign(&secp, &tx, &witness_script, 1);
+
+        tx.input[0].witness = Witness::from_slice(&[signature, witness_script.to_bytes()]);
+        let report = run(&tx, &spent_outputs, &[]);
+        assert_pass(&report);
+        assert_eq!(report.agrees, Some(true));
+
+        tx.input[0].witness = Witness::from_slice(&[segwit_sign(&secp, &tx, &witness_script, 2), witness_script.to_bytes()]);
+        let report = run(&tx, &spent_outputs, &[]);
+        assert_fail(&report, "EVAL_FALSE");
+        assert_eq!(report.agrees, Some(true));
+    }
+
+    #[test]
+    fn checksigadd_is_a_bad_opcode_outside_tapscript() {
+        let witness_script = Builder::new().push_int(0).push_int(0).push_int(1).push_opcode(OP_CHECKSIGADD).into_script();
+        let (mut tx, spent_outputs) = p2wsh(&witness_script);
+        tx.input[0].witness = Witness::from_slice(&[witness_script.to_bytes()]);
+        let report = run(&tx, &spent_outputs, &[]);
+        assert_fail(&report, "BAD_OPCODE");
+        assert_eq!(report.agrees, Some(true));
+    }
+
+    #[test]
+    fn checklocktimeverify_against_the_locktime() {
+        let witness_script = Builder::new().push_int(100).push_opcode(OP_CLTV).push_opcode(OP_DROP).push_int(1).into_script();
+        let (mut tx, spent_outputs) = p2wsh(&witness_script);
+        tx.input[0].witness = Witness::from_slice(&[witness_script.to_bytes()]);
+
+        tx.lock_time = LockTime::from_height(100).unwrap();
+        let report = run(&tx, &spent_outputs, &[]);
+        assert_pass(&report);
+        assert_eq!(report.agrees, Some(true));
+
+        tx.lock_time = LockTime::from_height(99).unwrap();
+        let report = run(&tx, &spent_outputs, &[]);
+        assert_fail(&report, "UNSATISFIED_LOCKTIME");
+        assert_eq!(report.agrees, Some(true));
+
+        tx.lock_time = LockTime::from_height(100).unwrap();
+        tx.input[0].sequence = Sequence::MAX;
+        let report = run(&tx, &spent_outputs, &[]);
+        assert_fail(&report, "UNSATISFIED_LOCKTIME");
+        assert_eq!(report.agrees, Some(true));
+    }
+
+    #[test]
+    fn checksequenceverify_against_the_sequence() {
+        let witness_script = Builder::new().push_int(10).push_opcode(OP_CSV).push_opcode(OP_DROP).push_int(1).into_script();
+        let (mut tx, spent_outputs) = p2wsh(&witness_script);
+        tx.input[0].witness = Witness::from_slice(&[witness_script.to_bytes()]);
+
+        tx.input[0].sequence = Sequence::from_height(10);
+        let report = run(&tx, &spent_outputs, &[]);
+        assert_pass(&report);
+        assert_eq!(report.agrees, Some(true));
+
+        tx.input[0].sequence = Sequence::from_height(9);
+        let report = run(&tx, &spent_outputs, &[]);
+        assert_fail(&report, "UNSATISFIED_LOCKTIME");
+        assert_eq!(report.agrees, Some(true));
+
+        tx.input[0].sequence = Sequence::from_height(10);
+        tx.version = Version::ONE;
+        let report = run(&tx, &spent_outputs, &[]);
+        assert_fail(&report, "UNSATISFIED_LOCKTIME");
+        assert_eq!(report.agrees, Some(true));
+    }
+
+    #[test]
+    fn taproot_key_path_checked() {
+        let secp = Secp256k1::new();
+        let keypair = Keypair::from_secret_key(&secp, &secret_key(1));
+        let (internal_key, _) = keypair.x_only_public_key();
+        let (mut tx, spent_outputs) = spending(ScriptBuf::new_p2tr(&secp, internal_key, None));
+        let prevouts: Vec<TxOut> = spent_outputs.values().cloned().collect();
+        let sighash = SighashCache::new(&tx)
+            .taproot_key_spend_signature_hash(0, &Prevouts::All(&prevouts), TapSighashType::Default)
+            .unwrap();
+        let signature = secp.sign_schnorr_no_aux_rand(&Message::from_digest(sighash.to_byte_array()), &keypair.tap_tweak(&secp, None).to_inner());
+
+        tx.input[0].witness = Witness::from_slice(&[signature.as_ref()]);
+        let report = run(&tx, &spent_outputs, &[]);
+        assert_pass(&report);
+        assert_eq!((report.consensus, report.agrees), (None, None));
+        assert_eq!(report.steps.last().unwrap().phase, "keypath");
+
+        let mut tampered = signature.as_ref().to_vec();
+        tampered[10] ^= 1;
+        tx.input[0].witness = Witness::from_slice(&[tampered]);
+        assert_fail(&run(&tx, &spent_outputs, &[]), "SCHNORR_SIG");
+    }
+
+    /// A script path spend of the single leaf `leaf`, with `witness` before the leaf and control block.
+    fn tapscript(secp: &Secp256k1<All>, leaf: &ScriptBuf) -> (Transaction, HashMap<OutPoint, TxOut>, TapLeafHash, ControlBlock) {
+        let (internal_key, _) = Keypair::from_secret_key(secp, &secret_key(9)).x_only_public_key();
+        let spend_info = TaprootBuilder::new().add_leaf(0, leaf.clone()).unwrap().finalize(secp, internal_key).unwrap();
+        let control_block = spend_info.control_block(&(leaf.clone(), LeafVersion::TapScript)).unwrap();
+        let (tx, spent_outputs) = spending(ScriptBuf::new_p2tr(secp, internal_key, spend_info.merkle_root()));
+        (tx, spent_outputs, TapLeafHash::from_script(leaf, LeafVersion::TapScript), control_block)
+    }
+
+    #[test]
+    fn tapscript_signature_checked() {
+        let secp = Secp256k1::new();
+        let keypair = Keypair::from_secret_key(&secp, &secret_key(1));
+        let leaf = Builder::new().push_x_only_key(&keypair.x_only_public_key().0).push_opcode(OP_CHECKSIG).into_script();
+        let (mut tx, spent_outputs, leaf_hash, control_block) = tapscript(&secp, &leaf);
+        let prevouts: Vec<TxOut> = spent_outputs.values().cloned().collect();
+        let sighash = SighashCache::new(&tx)
+            .taproot_script_spend_signature_hash(0, &Prevouts::All(&prevouts), leaf_hash, TapSighashType::Default)
+            .unwrap();
+        let signature = secp.sign_schnorr_no_aux_rand(&Message::from_digest(sighash.to_byte_array()), &keypair);
+
+        tx.input[0].witness = Witness::from_slice(&[signature.as_ref().to_vec(), leaf.to_bytes(), control_block.serialize()]);
+        let report = run(&tx, &spent_outputs, &[]);
+        assert_pass(&report);
+        assert_eq!(report.steps.last().unwrap().phase, "tapscript");
+
+        let mut tampered = signature.as_ref().to_vec();
+        tampered[10] ^= 1;
+        tx.input[0].witness = Witness::from_slice(&[tampered, leaf.to_bytes(), control_block.serialize()]);
+        assert_fail(&run(&tx, &spent_outputs, &[]), "SCHNORR_SIG");
+    }
+
+    #[test]
+    fn checkmultisig_is_disabled_in_tapscript() {
+        let secp = Secp256k1::new();
+        let leaf = Builder::new().push_int(0).push_int(0).push_int(0).push_opcode(OP_CHECKMULTISIG).into_script();
+        let (mut tx, spent_outputs, _, control_block) = tapscript(&secp, &leaf);
+        tx.input[0].witness = Witness::from_slice(&[leaf.to_bytes(), control_block.serialize()]);
+        let report = run(&tx, &spent_outputs, &[]);
+        assert_fail(&report, "TAPSCRIPT_CHECKMULTISIG");
+        assert_eq!(report.steps.last().unwrap().opcode, "OP_CHECKMULTISIG");
+    }
+
+    #[test]
+    fn breakpoints_halt_execution() {
+        let secp = Secp256k1::new();
+        let (tx, spent_outputs) = p2pkh(&secp, true);
+
+        let report = run(&tx, &spent_outputs, &[Breakpoint::Opcode("OP_HASH160".to_string())]);
+        assert_eq!((report.result, report.halted_at, report.agrees), (DebugResult::Halted, Some(4), None));
+        assert_eq!(report.steps.len(), 4);
+        assert_eq!(report.steps[3].opcode, "OP_HASH160");
+
+        let report = run(&tx, &spent_outputs, &[Breakpoint::Step(2)]);
+        assert_eq!((report.result, report.halted_at), (DebugResult::Halted, Some(2)));
+        assert_eq!(report.steps.last().unwrap().phase, "scriptSig");
+    }
+
+    #[test]
+    fn script_numbers() {
+        assert_eq!(decode_num(&[], 4).ok(), Some(0));
+        assert_eq!(decode_num(&[0x81], 4).ok(), Some(-1));
+        assert_eq!(decode_num(&[0x80], 4).ok(), Some(0));
+        assert_eq!(decode_num(&[0xff, 0x00], 4).ok(), Some(255));
+        assert_eq!(decode_num(&[0xff, 0x80], 4).ok(), Some(-255));
+        assert_eq!(decode_num(&[0xff, 0xff, 0xff, 0x7f], 4).ok(), Some(i64::from(i32::MAX)));
+        assert!(decode_num(&[0; 5], 4).is_err());
+        assert_eq!(decode_num(&[0; 5], 5).ok(), Some(0));
+
+        assert_eq!(encode_num(0), Vec::<u8>::new());
+        assert_eq!(encode_num(-1), vec![0x81]);
+        assert_eq!(encode_num(127), vec![0x7f]);
+        assert_eq!(encode_num(128), vec![0x80, 0x00]);
+        assert_eq!(encode_num(-128), vec![0x80, 0x80]);
+        assert_eq!(encode_num(-255), vec![0xff, 0x80]);
+        for n in [-70_000, -256, -1, 0, 1, 255, 256, 70_000, i64::from(i32::MAX)] {
+            assert_eq!(decode_num(&encode_num(n), 5).ok(), Some(n));
+        }
+    }
+
+    #[test]
+    fn stack_elements_as_booleans() {
+        assert!(!cast_to_bool(&[]));
+        assert!(!cast_to_bool(&[0x00]));
+        assert!(!cast_to_bool(&[0x00, 0x00]));
+        assert!(!cast_to_bool(&[0x80]));
+        assert!(!cast_to_bool(&[0x00, 0x80]));
+        assert!(cast_to_bool(&[0x01]));
+        assert!(cast_to_bool(&[0x80, 0x00]));
+        assert!(cast_to_bool(&[0x00, 0x81]));
+    }
+}
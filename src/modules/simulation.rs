@@ -0,0 +1,387 @@
+use std::fs;
+use std::path::Path;
+
+use bitcoin::hashes::Hash;
+use bitcoin::opcodes::all::OP_PUSHNUM_1;
+use bitcoin::script::Builder;
+use bitcoin::{Amount, FeeRate, PubkeyHash, ScriptBuf, ScriptHash, SignedAmount, Txid, WPubkeyHash, WScriptHash};
+use bitcoincore_rpc::json::ListUnspentResultEntry;
+use log::info;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::Serialize;
+
+use crate::utils::fees::{fee_for_weight, input_weight};
+use crate::utils::utils::{strat_handler, SelectionParams, UTXOStrategy};
+
+use super::errors::SimulationError;
+
+/// Shape of the amounts drawn for synthetic UTXOs and payment targets.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AmountDistribution {
+    /// Every amount between the bounds equally likely
+    Uniform,
+    /// Mostly small amounts, with a tail towards the upper bound
+    Exponential,
+    /// Four in five amounts near the lower bound, the rest near the upper bound
+    Bimodal,
+}
+
+impl AmountDistribution {
+    /// Draw an amount between `min` and `max`, both included.
+    pub fn sample<R: Rng>(&self, min: Amount, max: Amount, rng: &mut R) -> Amount {
+        let (min, max) = (min.to_sat(), max.to_sat());
+        let span = max - min;
+        let sats = match self {
+            AmountDistribution::Uniform => rng.gen_range(min..=max),
+            AmountDistribution::Exponential => {
+                // mean at a quarter of the range, the tail clamped to the upper bound
+                let mean = span as f64 / 4.0;
+                let draw = -mean * (1.0 - rng.gen::<f64>()).ln();
+                min + (draw as u64).min(span)
+            }
+            AmountDistribution::Bimodal => {
+                let tenth = span / 10;
+                if rng.gen_bool(0.8) {
+                    rng.gen_range(min..=min + tenth)
+                } else {
+                    rng.gen_range(max - tenth..=max)
+                }
+            }
+        };
+        Amount::from_sat(sats)
+    }
+}
+
+/// Amounts between `min` and `max` following `distribution`.
+#[derive(Clone, Copy, Debug)]
+pub struct AmountRange {
+    pub distribution: AmountDistribution,
+    pub min: Amount,
+    pub max: Amount,
+}
+
+impl AmountRange {
+    fn validate(&self) -> Result<(), SimulationError> {
+        if self.min > self.max || self.min == Amount::ZERO {
+            return Err(SimulationError::InvalidAmountRange(self.min, self.max));
+        }
+        Ok(())
+    }
+}
+
+/// Payments every strategy is asked to fund, one after the other, from its own copy of the pool.
+#[derive(Clone, Debug)]
+pub struct SimulationConfig {
+    pub strategies: Vec<UTXOStrategy>,
+    pub payments: usize,
+    pub payment_amounts: AmountRange,
+    pub fee_rate: FeeRate,
+    /// Seeds the synthetic pool, payment targets, change txids and the randomized strategies, so
+    /// the same seed reproduces the whole run. Each step's seed depends only on this and the step,
+    /// so every strategy gets the same one for the same payment.
+    pub seed: u64,
+}
+
+/// One payment funded (or not) by one strategy, and the pool it left behind.
+#[derive(Clone, Debug, Serialize)]
+pub struct SimulationStep {
+    pub strategy: &'static str,
+    pub step: usize,
+    #[serde(with = "bitcoin::amount::serde::as_btc")]
+    pub target: Amount,
+    pub funded: bool,
+    pub inputs: usize,
+    #[serde(with = "bitcoin::amount::serde::as_btc")]
+    pub fee: Amount,
+    #[serde(with = "bitcoin::amount::serde::as_btc::opt")]
+    pub change: Option<Amount>,
+    #[serde(with = "bitcoin::amount::serde::as_btc")]
+    pub waste: SignedAmount,
+    pub pool_size: usize,
+    /// UTXOs worth less than the fee to spend them at the simulated fee rate
+    pub uneconomical_utxos: usize,
+    #[serde(with = "bitcoin::amount::serde::as_btc")]
+    pub pool_value: Amount,
+}
+
+/// Totals of a strategy over the whole run.
+#[derive(Clone, Debug, Serialize)]
+pub struct StrategySummary {
+    pub strategy: &'static str,
+    pub funded: usize,
+    pub failed: usize,
+    pub avg_inputs: f64,
+    pub change_outputs: usize,
+    #[serde(with = "bitcoin::amount::serde::as_btc")]
+    pub total_fees: Amount,
+    #[serde(with = "bitcoin::amount::serde::as_btc")]
+    pub total_waste: SignedAmount,
+    pub final_pool_size: usize,
+    pub final_uneconomical_utxos: usize,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct SimulationReport {
+    pub summary: Vec<StrategySummary>,
+    pub steps: Vec<SimulationStep>,
+}
+
+/// A pool of `size` P2WPKH UTXOs with amounts drawn from `amounts`. UTXOs share a quarter as
+/// many addresses, so address grouping has something to group.
+pub fn synthetic_pool(size: usize, amounts: &AmountRange, seed: u64) -> Result<Vec<ListUnspentResultEntry>, SimulationError> {
+    amounts.validate()?;
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    let scripts: Vec<ScriptBuf> = (0..(size / 4).max(1))
+        .map(|_| ScriptBuf::new_p2wpkh(&WPubkeyHash::from_byte_array(rng.gen())))
+        .collect();
+
+    let pool = (0..size)
+        .map(|_| {
+            let script = scripts[rng.gen_range(0..scripts.len())].clone();
+            let amount = amounts.distribution.sample(amounts.min, amounts.max, &mut rng);
+            let confirmations = rng.gen_range(1..=1000);
+            simulated_utxo(Txid::from_byte_array(rng.gen()), 0, script, amount, confirmations, None)
+        })
+        .collect();
+    Ok(pool)
+}
+
+/// Read a pool from the JSON written by `list-unspent --output json`.
+pub fn load_pool(path: &Path) -> Result<Vec<ListUnspentResultEntry>, SimulationError> {
+    let pool: Vec<ListUnspentResultEntry> = serde_json::from_str(&fs::read_to_string(path)?)?;
+    info!("Loaded {} UTXOs from {}", pool.len(), path.display());
+    Ok(pool)
+}
+
+/// Fund the same series of payments with every configured strategy, each starting from `pool`.
+///
+/// Spent UTXOs leave the pool and change joins it unconfirmed, at a new address of the same type
+/// as the pool's first UTXO. Every payment ages the pool by one confirmation.
+pub fn simulate_coin_selection(pool: &[ListUnspentResultEntry], config: &SimulationConfig) -> Result<SimulationReport, SimulationError> {
+    if pool.is_empty() {
+        return Err(SimulationError::EmptyPool);
+    }
+    config.payment_amounts.validate()?;
+
+    let mut rng = StdRng::seed_from_u64(config.seed.wrapping_add(1));
+    let targets: Vec<Amount> = (0..config.payments)
+        .map(|_| {
+            let AmountRange { distribution, min, max } = config.payment_amounts;
+            distribution.sample(min, max, &mut rng)
+        })
+        .collect();
+    let payment_script = ScriptBuf::new_p2wpkh(&WPubkeyHash::all_zeros());
+    let change_template = pool[0].clone();
+    let mut params = SelectionParams::new(
+        config.fee_rate,
+        &[payment_script],
+        &change_template.script_pub_key,
+        input_weight(&change_template),
+    );
+
+    let mut summary = Vec::with_capacity(config.strategies.len());
+    let mut steps = Vec::with_capacity(config.strategies.len() * targets.len());
+    for strategy in &config.strategies {
+        let mut pool = pool.to_vec();
+        let first_step = steps.len();
+
+        for (step, target) in targets.iter().enumerate() {
+            pool.iter_mut().for_each(|utxo| utxo.confirmations += 1);
+
+            let mut step_rng = step_rng(config.seed, step);
+            params.seed = Some(step_rng.gen());
+            let selection = strat_handler(&pool, *target, &params, *strategy).ok();
+
+            if let Some(selection) = &selection {
+                pool.retain(|utxo| !selection.utxos.contains(utxo));
+                if let Some(change) = selection.change {
+                    pool.push(simulated_utxo(
+                        Txid::from_byte_array(step_rng.gen()),
+                        1,
+                        fresh_script(&change_template.script_pub_key, &mut step_rng),
+                        change,
+                        0,
+                        change_template.descriptor.clone(),
+                    ));
+                }
+            }
+
+            steps.push(SimulationStep {
+                strategy: strategy.name(),
+                step: step + 1,
+                target: *target,
+                funded: selection.is_some(),
+                inputs: selection.as_ref().map_or(0, |selection| selection.utxos.len()),
+                fee: selection.as_ref().map_or(Amount::ZERO, |selection| selection.fee),
+                change: selection.as_ref().and_then(|selection| selection.change),
+                waste: selection.as_ref().map_or(SignedAmount::ZERO, |selection| selection.waste),
+                pool_size: pool.len(),
+                uneconomical_utxos: uneconomical_utxos(&pool, config.fee_rate),
+                pool_value: pool.iter().map(|utxo| utxo.amount).sum(),
+            });
+        }
+
+        let runs = &steps[first_step..];
+        let funded: Vec<&SimulationStep> = runs.iter().filter(|step| step.funded).collect();
+        let avg_inputs = match funded.len() {
+            0 => 0.0,
+            n => funded.iter().map(|step| step.inputs).sum::<usize>() as f64 / n as f64,
+        };
+        summary.push(StrategySummary {
+            strategy: strategy.name(),
+            funded: funded.len(),
+            failed: runs.len() - funded.len(),
+            avg_inputs: (avg_inputs * 100.0).round() / 100.0,
+            change_outputs: funded.iter().filter(|step| step.change.is_some()).count(),
+            total_fees: funded.iter().map(|step| step.fee).sum(),
+            total_waste: funded.iter().map(|step| step.waste).sum(),
+            final_pool_size: pool.len(),
+            final_uneconomical_utxos: uneconomical_utxos(&pool, config.fee_rate),
+        });
+        info!("Simulated {} payments with {}", targets.len(), strategy.name());
+    }
+
+    Ok(SimulationReport { summary, steps })
+}
+
+/// Randomness for one payment, the same for every strategy.
+fn step_rng(seed: u64, step: usize) -> StdRng {
+    // spread consecutive steps over the seed space before mixing in the run's seed
+    StdRng::seed_from_u64(seed ^ (step as u64 + 1).wrapping_mul(0x9e37_79b9_7f4a_7c15))
+}
+
+fn uneconomical_utxos(pool: &[ListUnspentResultEntry], fee_rate: FeeRate) -> usize {
+    pool.iter()
+        .filter(|utxo| utxo.amount <= fee_for_weight(fee_rate, input_weight(utxo)))
+        .count()
+}
+
+/// A random script of the same type as `template`, standing in for a new change address.
+fn fresh_script<R: Rng>(template: &ScriptBuf, rng: &mut R) -> ScriptBuf {
+    if template.is_p2wpkh() {
+        ScriptBuf::new_p2wpkh(&WPubkeyHash::from_byte_array(rng.gen()))
+    } else if template.is_p2sh() {
+        ScriptBuf::new_p2sh(&ScriptHash::from_byte_array(rng.gen()))
+    } else if template.is_p2wsh() {
+        ScriptBuf::new_p2wsh(&WScriptHash::from_byte_array(rng.gen()))
+    } else if template.is_p2tr() {
+        let output_key: [u8; 32] = rng.gen();
+        Builder::new().push_opcode(OP_PUSHNUM_1).push_slice(output_key).into_script()
+    } else {
+        ScriptBuf::new_p2pkh(&PubkeyHash::from_byte_array(rng.gen()))
+    }
+}
+
+fn simulated_utxo(
+    txid: Txid,
+    vout: u32,
+    script_pub_key: ScriptBuf,
+    amount: Amount,
+    confirmations: u32,
+    descriptor: Option<String>,
+) -> ListUnspentResultEntry {
+    ListUnspentResultEntry {
+        txid,
+        vout,
+        address: None,
+        label: None,
+        redeem_script: None,
+        witness_script: None,
+        script_pub_key,
+        amount,
+        confirmations,
+        spendable: true,
+        solvable: true,
+        descriptor,
+        safe: true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHAPES: [AmountDistribution; 3] = [AmountDistribution::Uniform, AmountDistribution::Exponential, AmountDistribution::Bimodal];
+
+    fn range(min: u64, max: u64) -> AmountRange {
+        AmountRange { distribution: AmountDistribution::Uniform, min: Amount::from_sat(min), max: Amount::from_sat(max) }
+    }
+
+    fn config(strategies: Vec<UTXOStrategy>, seed: u64) -> SimulationConfig {
+        SimulationConfig {
+            strategies,
+            payments: 30,
+            payment_amounts: range(50_000, 2_000_000),
+            fee_rate: FeeRate::from_sat_per_vb_unchecked(5),
+            seed,
+        }
+    }
+
+    fn run(strategies: Vec<UTXOStrategy>, seed: u64) -> SimulationReport {
+        let pool = synthetic_pool(100, &range(10_000, 5_000_000), seed).unwrap();
+        simulate_coin_selection(&pool, &config(strategies, seed)).unwrap()
+    }
+
+    fn json(report: &SimulationReport) -> String {
+        serde_json::to_string(report).unwrap()
+    }
+
+    #[test]
+    fn same_seed_same_report() {
+        let strategies = vec![UTXOStrategy::Knapsack, UTXOStrategy::SingleRandomDraw, UTXOStrategy::BranchAndBound, UTXOStrategy::LargestFirst];
+        assert_eq!(json(&run(strategies.clone(), 3)), json(&run(strategies.clone(), 3)));
+        assert_ne!(json(&run(strategies.clone(), 3)), json(&run(strategies, 4)));
+    }
+
+    #[test]
+    fn strategies_get_the_same_seed_for_each_payment() {
+        for strategy in [UTXOStrategy::Knapsack, UTXOStrategy::SingleRandomDraw] {
+            let report = run(vec![strategy, strategy], 11);
+            let (first, second) = report.steps.split_at(report.steps.len() / 2);
+            assert_eq!(serde_json::to_string(first).unwrap(), serde_json::to_string(second).unwrap());
+        }
+    }
+
+    #[test]
+    fn samples_stay_within_bounds() {
+        let mut rng = StdRng::seed_from_u64(1);
+        for shape in SHAPES {
+            for (min, max) in [(1, 1), (1, 9), (546, 100_000), (10_000, 5_000_000)] {
+                for _ in 0..2_000 {
+                    let amount = shape.sample(Amount::from_sat(min), Amount::from_sat(max), &mut rng).to_sat();
+                    assert!((min..=max).contains(&amount), "{:?} drew {} outside {}..={}", shape, amount, min, max);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn spent_utxos_leave_the_pool_and_change_joins_it() {
+        let pool = synthetic_pool(100, &range(10_000, 5_000_000), 5).unwrap();
+        let report = simulate_coin_selection(&pool, &config(vec![UTXOStrategy::LargestFirst], 5)).unwrap();
+
+        let mut size = pool.len();
+        let mut value: Amount = pool.iter().map(|utxo| utxo.amount).sum();
+        for step in report.steps.iter().filter(|step| step.funded) {
+            size = size - step.inputs + usize::from(step.change.is_some());
+            value = value - step.target - step.fee;
+            assert_eq!((step.pool_size, step.pool_value), (size, value), "step {}", step.step);
+        }
+        assert!(report.steps.iter().any(|step| step.change.is_some()));
+        assert_eq!(report.summary[0].final_pool_size, size);
+    }
+
+    #[test]
+    fn rejects_empty_pool_and_inverted_range() {
+        assert!(matches!(simulate_coin_selection(&[], &config(vec![UTXOStrategy::Knapsack], 1)), Err(SimulationError::EmptyPool)));
+
+        let inverted = range(2_000, 1_000);
+        assert!(matches!(synthetic_pool(10, &inverted, 1), Err(SimulationError::InvalidAmountRange(_, _))));
+        let pool = synthetic_pool(10, &range(10_000, 20_000), 1).unwrap();
+        let config = SimulationConfig { payment_amounts: inverted, ..config(vec![UTXOStrategy::Knapsack], 1) };
+        assert!(matches!(simulate_coin_selection(&pool, &config), Err(SimulationError::InvalidAmountRange(_, _))));
+    }
+}
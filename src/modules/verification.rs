@@ -1,18 +1,112 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+
 use log::info;
 
-use bitcoin::{consensus::deserialize, OutPoint, Transaction, TxOut};
+use serde::{Deserialize, Serialize};
+
+use bitcoin::bitcoinconsensus::{
+    self, VERIFY_CHECKLOCKTIMEVERIFY, VERIFY_CHECKSEQUENCEVERIFY, VERIFY_DERSIG, VERIFY_NONE, VERIFY_NULLDUMMY, VERIFY_P2SH, VERIFY_WITNESS,
+};
+use bitcoin::psbt::Psbt;
+use bitcoin::{consensus::{deserialize, serialize}, Amount, OutPoint, Script, ScriptBuf, Transaction, TxOut, Txid};
 
 use crate::{
     modules::{
         client::get_tx,
-        errors::VerificationError
+        errors::{BitcoindError, VerificationError}
     },
     settings::Settings
 };
 
 use super::client::get_tx_out;
+use super::script_debug::verify_natively;
+
+#[derive(Debug, Serialize)]
+pub struct VerifiedTx {
+    pub txid: Txid,
+    pub inputs: usize,
+    pub valid: bool,
+}
+
+/// Script verification flags in the order the soft forks activated. Each set adds one flag to the
+/// previous, since libbitcoinconsensus does not accept WITNESS without P2SH.
+const FLAG_SETS: [(&str, u32); 7] = [
+    ("none", VERIFY_NONE),
+    ("p2sh", VERIFY_P2SH),
+    ("dersig", VERIFY_P2SH | VERIFY_DERSIG),
+    ("nulldummy", VERIFY_P2SH | VERIFY_DERSIG | VERIFY_NULLDUMMY),
+    ("cltv", VERIFY_P2SH | VERIFY_DERSIG | VERIFY_NULLDUMMY | VERIFY_CHECKLOCKTIMEVERIFY),
+    ("csv", VERIFY_P2SH | VERIFY_DERSIG | VERIFY_NULLDUMMY | VERIFY_CHECKLOCKTIMEVERIFY | VERIFY_CHECKSEQUENCEVERIFY),
+    ("witness", VERIFY_P2SH | VERIFY_DERSIG | VERIFY_NULLDUMMY | VERIFY_CHECKLOCKTIMEVERIFY | VERIFY_CHECKSEQUENCEVERIFY | VERIFY_WITNESS),
+];
+
+/// State of the output an input spends, as far as the node knows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum UtxoStatus {
+    /// Unspent and in a block
+    Confirmed,
+    /// Unspent, created by a mempool transaction
+    Mempool,
+    /// Spent, or never existed
+    Spent,
+    /// Not checked, the report was made offline
+    Unknown,
+}
+
+/// Verification of one input under every flag set. Each flag column is `ok`, the
+/// libbitcoinconsensus error code, or empty when the spent output is unknown.
+#[derive(Debug, Serialize)]
+pub struct InputReport {
+    pub index: usize,
+    pub outpoint: OutPoint,
+    #[serde(with = "bitcoin::amount::serde::as_btc::opt")]
+    pub value: Option<Amount>,
+    pub script_type: &'static str,
+    pub status: UtxoStatus,
+    pub confirmations: Option<u32>,
+    pub none: Option<String>,
+    pub p2sh: Option<String>,
+    pub dersig: Option<String>,
+    pub nulldummy: Option<String>,
+    pub cltv: Option<String>,
+    pub csv: Option<String>,
+    pub witness: Option<String>,
+    /// Key and script path rules, checked by the native interpreter since libbitcoinconsensus
+    /// predates taproot; empty for other inputs
+    pub taproot: Option<String>,
+    /// Passes with every flag libbitcoinconsensus supports, and the taproot rules for P2TR inputs
+    pub valid: bool,
+    pub error: Option<String>,
+}
+
+/// Where [`verify_signed_tx_offline`] finds the outputs a transaction spends.
+#[derive(Clone, Debug)]
+pub enum PrevoutSource {
+    /// Base64 PSBT of the transaction, with `witness_utxo` or `non_witness_utxo` set on every input
+    Psbt(String),
+    /// JSON array of `{"txid", "vout", "scriptPubKey", "amount"}` objects, as `signrawtransactionwithkey` takes them
+    File(PathBuf),
+    /// Raw hex of the transactions being spent from
+    ParentTxs(Vec<String>),
+}
+
+/// A previous output, in the shape Bitcoin Core's `prevtxs` arguments use.
+#[derive(Deserialize)]
+struct Prevout {
+    txid: Txid,
+    vout: u32,
+    #[serde(rename = "scriptPubKey")]
+    script_pub_key: ScriptBuf,
+    #[serde(with = "bitcoin::amount::serde::as_btc")]
+    amount: Amount,
+}
 
-pub fn verify_signed_tx(tx_hex: &str, settings: &Settings) -> Result<(), VerificationError> {
+/// Check every input of a signed transaction is unspent and passes consensus script verification.
+pub fn verify_signed_tx(tx_hex: &str, settings: &Settings) -> Result<VerifiedTx, VerificationError> {
     let tx: Transaction = deserialize(&hex::decode(tx_hex)?)?;
 
     info!("Verifying transaction: {}", tx.txid());
@@ -28,25 +122,32 @@ pub fn verify_signed_tx(tx_hex: &str, settings: &Settings) -> Result<(), Verific
         }
     }
 
-    // Closure to fetch previous transaction output (TxOut) for each input
-    let mut spent = |outpoint: &OutPoint| -> Option<TxOut> {
-        match get_tx(&outpoint.txid.to_string(), settings) {
-            Ok(prev_tx) => prev_tx.vout.get(outpoint.vout as usize).map(|output| {
-                TxOut {
-                    value: output.value,
-                    script_pubkey: bitcoin::ScriptBuf::from(output.script_pub_key.hex.clone()),
-                }
-            }),
-            Err(_) => None
+    // Fetch the previous transaction output (TxOut) of each input
+    let mut spent_outputs: HashMap<OutPoint, TxOut> = HashMap::new();
+    for input in &tx.input {
+        let outpoint = input.previous_output;
+        if let Some(output) = get_tx(&outpoint.txid.to_string(), settings).ok()
+            .and_then(|prev_tx| prev_tx.vout.into_iter().nth(outpoint.vout as usize))
+        {
+            spent_outputs.insert(outpoint, TxOut {
+                value: output.value,
+                script_pubkey: ScriptBuf::from(output.script_pub_key.hex),
+            });
         }
-    };
+    }
 
     // Verify the transaction. For each input, check if unlocking script is valid based on the corresponding TxOut.
-    tx.verify(&mut spent).map_err(|e| VerificationError::TransactionVerificationFailed(e.to_string()))?;
+    tx.verify(|outpoint: &OutPoint| spent_outputs.get(outpoint).cloned())
+        .map_err(|e| VerificationError::TransactionVerificationFailed(e.to_string()))?;
+    verify_taproot_inputs(&tx, &spent_outputs)?;
 
     info!("Transaction verified successfully");
 
-    Ok(())
+    Ok(VerifiedTx {
+        txid: tx.txid(),
+        inputs: tx.input.len(),
+        valid: true,
+    })
 }
 
 fn is_utxo_unspent(outpoint: &OutPoint, settings: &Settings) -> Result<bool, VerificationError> {
@@ -62,4 +163,380 @@ fn is_utxo_unspent(outpoint: &OutPoint, settings: &Settings) -> Result<bool, Ver
             }
         }
     }
-}
\ No newline at end of file
+}
+
+/// Check every input of a signed transaction passes consensus script verification, taking the
+/// spent outputs from `prevouts` instead of a node. Whether they are still unspent is not checked.
+pub fn verify_signed_tx_offline(tx_hex: &str, prevouts: &PrevoutSource) -> Result<VerifiedTx, VerificationError> {
+    let tx: Transaction = deserialize(&hex::decode(tx_hex)?)?;
+
+    info!("Verifying transaction offline: {}", tx.txid());
+    info!("Number of inputs: {}", tx.input.len());
+
+    let spent_outputs = load_prevouts(prevouts)?;
+    for (index, input) in tx.input.iter().enumerate() {
+        if !spent_outputs.contains_key(&input.previous_output) {
+            return Err(VerificationError::MissingPrevout(index, input.previous_output));
+        }
+    }
+
+    tx.verify(|outpoint: &OutPoint| spent_outputs.get(outpoint).cloned())
+        .map_err(|e| VerificationError::TransactionVerificationFailed(e.to_string()))?;
+    verify_taproot_inputs(&tx, &spent_outputs)?;
+
+    info!("Transaction verified successfully");
+
+    Ok(VerifiedTx {
+        txid: tx.txid(),
+        inputs: tx.input.len(),
+        valid: true,
+    })
+}
+
+/// libbitcoinconsensus predates taproot and accepts any witness v1 spend, so run the P2TR inputs
+/// through the native interpreter.
+fn verify_taproot_inputs(tx: &Transaction, spent_outputs: &HashMap<OutPoint, TxOut>) -> Result<(), VerificationError> {
+    for (index, input) in tx.input.iter().enumerate() {
+        let Some(spent) = spent_outputs.get(&input.previous_output).filter(|spent| spent.script_pubkey.is_p2tr()) else {
+            continue;
+        };
+        verify_natively(tx, index, spent, spent_outputs)
+            .map_err(|reason| VerificationError::TransactionVerificationFailed(format!("input {}: {}", index, reason)))?;
+    }
+    Ok(())
+}
+
+pub(crate) fn load_prevouts(prevouts: &PrevoutSource) -> Result<HashMap<OutPoint, TxOut>, VerificationError> {
+    let mut spent_outputs: HashMap<OutPoint, TxOut> = HashMap::new();
+    match prevouts {
+        PrevoutSource::Psbt(psbt) => {
+            let psbt = Psbt::from_str(psbt).map_err(|e| VerificationError::InvalidPsbt(e.to_string()))?;
+            for (tx_input, psbt_input) in psbt.unsigned_tx.input.iter().zip(&psbt.inputs) {
+                let outpoint = tx_input.previous_output;
+                let spent = match (&psbt_input.witness_utxo, &psbt_input.non_witness_utxo) {
+                    (Some(witness_utxo), _) => Some(witness_utxo.clone()),
+                    (None, Some(parent)) => {
+                        if parent.txid() != outpoint.txid {
+                            return Err(VerificationError::InvalidPsbt(format!("non_witness_utxo of {} is transaction {}", outpoint, parent.txid())));
+                        }
+                        parent.output.get(outpoint.vout as usize).cloned()
+                    }
+                    (None, None) => None,
+                };
+                if let Some(spent) = spent {
+                    spent_outputs.insert(outpoint, spent);
+                }
+            }
+        }
+        PrevoutSource::File(path) => {
+            let listed: Vec<Prevout> = serde_json::from_str(&fs::read_to_string(path)?)?;
+            for prevout in listed {
+                spent_outputs.insert(
+                    OutPoint::new(prevout.txid, prevout.vout),
+                    TxOut { value: prevout.amount, script_pubkey: prevout.script_pub_key },
+                );
+            }
+        }
+        PrevoutSource::ParentTxs(parent_hexes) => {
+            for parent_hex in parent_hexes {
+                let parent: Transaction = deserialize(&hex::decode(parent_hex)?)?;
+                let txid = parent.txid();
+                for (vout, output) in parent.output.into_iter().enumerate() {
+                    spent_outputs.insert(OutPoint::new(txid, vout as u32), output);
+                }
+            }
+        }
+    }
+    info!("Loaded {} previous outputs", spent_outputs.len());
+    Ok(spent_outputs)
+}
+
+/// Verify every input of a signed transaction under each consensus flag set, without stopping at
+/// the first failure. Spent outputs come from `gettxout`, or `getrawtransaction` (which needs
+/// `-txindex`) once they are spent.
+pub fn input_report(tx_hex: &str, settings: &Settings) -> Result<Vec<InputReport>, VerificationError> {
+    let tx: Transaction = deserialize(&hex::decode(tx_hex)?)?;
+    info!("Reporting on {} inputs of {}", tx.input.len(), tx.txid());
+
+    let (spent_outputs, statuses) = fetch_prevouts(&tx, settings)?;
+    Ok(report_inputs(&tx, &spent_outputs, &statuses))
+}
+
+/// [`input_report`] with the spent outputs taken from `prevouts`. Spent or unspent is not known offline.
+pub fn input_report_offline(tx_hex: &str, prevouts: &PrevoutSource) -> Result<Vec<InputReport>, VerificationError> {
+    let tx: Transaction = deserialize(&hex::decode(tx_hex)?)?;
+    info!("Reporting offline on {} inputs of {}", tx.input.len(), tx.txid());
+
+    let spent_outputs = load_prevouts(prevouts)?;
+    let statuses = vec![(UtxoStatus::Unknown, None); tx.input.len()];
+    Ok(report_inputs(&tx, &spent_outputs, &statuses))
+}
+
+/// Status and confirmations of a spent output.
+type InputStatus = (UtxoStatus, Option<u32>);
+
+/// Outputs spent by `tx`, from `gettxout` or `getrawtransaction` once they are spent, with the
+/// status of each.
+pub(crate) fn fetch_prevouts(tx: &Transaction, settings: &Settings) -> Result<(HashMap<OutPoint, TxOut>, Vec<InputStatus>), VerificationError> {
+    let mut spent_outputs: HashMap<OutPoint, TxOut> = HashMap::new();
+    let mut statuses: Vec<InputStatus> = Vec::with_capacity(tx.input.len());
+    for input in &tx.input {
+        let outpoint = input.previous_output;
+        match get_tx_out(&outpoint.txid.to_string(), outpoint.vout, None, settings) {
+            Ok(tx_out) => {
+                let status = match tx_out.confirmations {
+                    0 => UtxoStatus::Mempool,
+                    _ => UtxoStatus::Confirmed,
+                };
+                statuses.push((status, Some(tx_out.confirmations)));
+                spent_outputs.insert(outpoint, TxOut {
+                    value: tx_out.value,
+                    script_pubkey: ScriptBuf::from(tx_out.script_pub_key.hex),
+                });
+            }
+            Err(BitcoindError::TxOutNotFound) => {
+                statuses.push((UtxoStatus::Spent, None));
+                if let Some(output) = get_tx(&outpoint.txid.to_string(), settings).ok()
+                    .and_then(|prev_tx| prev_tx.vout.into_iter().nth(outpoint.vout as usize))
+                {
+                    spent_outputs.insert(outpoint, TxOut {
+                        value: output.value,
+                        script_pubkey: ScriptBuf::from(output.script_pub_key.hex),
+                    });
+                }
+            }
+            Err(e) => return Err(VerificationError::UTXOCheckError(statuses.len(), e.to_string())),
+        }
+    }
+
+    Ok((spent_outputs, statuses))
+}
+
+fn report_inputs(tx: &Transaction, spent_outputs: &HashMap<OutPoint, TxOut>, statuses: &[InputStatus]) -> Vec<InputReport> {
+    let tx_bytes = serialize(tx);
+    let reports: Vec<InputReport> = tx.input.iter().zip(statuses).enumerate()
+        .map(|(index, (input, (status, confirmations)))| {
+            let spent = spent_outputs.get(&input.previous_output);
+            let results: Vec<Option<Result<(), bitcoinconsensus::Error>>> = FLAG_SETS.iter()
+                .map(|(_, flags)| {
+                    spent.map(|spent| {
+                        bitcoinconsensus::verify_with_flags(spent.script_pubkey.as_bytes(), spent.value.to_sat(), &tx_bytes, index, *flags)
+                    })
+                })
+                .collect();
+            let column = |i: usize| results[i].as_ref().map(|result| match result {
+                Ok(()) => "ok".to_string(),
+                Err(e) => format!("{:?}", e),
+            });
+
+            let strictest = results.last().cloned().flatten();
+            let taproot = spent.filter(|spent| spent.script_pubkey.is_p2tr())
+                .map(|spent| verify_natively(tx, index, spent, spent_outputs));
+            let error = match (spent, &strictest, &taproot) {
+                (None, _, _) => Some("Spent output not found".to_string()),
+                (Some(_), Some(Err(e)), _) => Some(consensus_error(e)),
+                (Some(_), _, Some(Err(reason))) => Some(reason.clone()),
+                _ => None,
+            };
+
+            InputReport {
+                index,
+                outpoint: input.previous_output,
+                value: spent.map(|spent| spent.value),
+                script_type: spent.map_or("unknown", |spent| script_type(&spent.script_pubkey)),
+                status: *status,
+                confirmations: *confirmations,
+                none: column(0),
+                p2sh: column(1),
+                dersig: column(2),
+                nulldummy: column(3),
+                cltv: column(4),
+                csv: column(5),
+                witness: column(6),
+                valid: matches!(strictest, Some(Ok(()))) && !matches!(taproot, Some(Err(_))),
+                taproot: taproot.map(|result| match result {
+                    Ok(()) => "ok".to_string(),
+                    Err(reason) => reason.split(':').next().unwrap_or_default().to_string(),
+                }),
+                error,
+            }
+        })
+        .collect();
+
+    let failed = reports.iter().filter(|report| !report.valid).count();
+    info!("{} of {} inputs verified", reports.len() - failed, reports.len());
+    reports
+}
+
+/// The libbitcoinconsensus error code with its meaning. The library reports a script that
+/// evaluates to false as `ERR_SCRIPT`, its unset error value.
+fn consensus_error(err: &bitcoinconsensus::Error) -> String {
+    match err {
+        bitcoinconsensus::Error::ERR_SCRIPT => "ERR_SCRIPT: script evaluation failed".to_string(),
+        other => format!("{:?}: {}", other, other),
+    }
+}
+
+pub(crate) fn script_type(script: &Script) -> &'static str {
+    if script.is_p2pkh() {
+        "p2pkh"
+    } else if script.is_p2sh() {
+        "p2sh"
+    } else if script.is_p2wpkh() {
+        "p2wpkh"
+    } else if script.is_p2wsh() {
+        "p2wsh"
+    } else if script.is_p2tr() {
+        "p2tr"
+    } else if script.is_p2pk() {
+        "p2pk"
+    } else if script.is_multisig() {
+        "multisig"
+    } else if script.is_op_return() {
+        "op_return"
+    } else {
+        "nonstandard"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::absolute::LockTime;
+    use bitcoin::hashes::Hash;
+    use bitcoin::key::{Keypair, TapTweak};
+    use bitcoin::secp256k1::{Message, Secp256k1, SecretKey};
+    use bitcoin::sighash::{Prevouts, SighashCache, TapSighashType};
+    use bitcoin::transaction::Version;
+    use bitcoin::{Sequence, TxIn, Witness};
+
+    use super::*;
+
+    /// A transaction paying 100000 sat to `script_pubkey`.
+    fn funding(script_pubkey: ScriptBuf) -> Transaction {
+        Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn { previous_output: OutPoint::new(Txid::all_zeros(), 0), script_sig: ScriptBuf::new(), sequence: Sequence::MAX, witness: Witness::new() }],
+            output: vec![TxOut { value: Amount::from_sat(100_000), script_pubkey }],
+        }
+    }
+
+    /// A taproot key path spend and the transaction it spends from.
+    fn key_path_spend() -> (Transaction, Transaction) {
+        let secp = Secp256k1::new();
+        let keypair = Keypair::from_secret_key(&secp, &SecretKey::from_slice(&[1; 32]).unwrap());
+        let parent = funding(ScriptBuf::new_p2tr(&secp, keypair.x_only_public_key().0, None));
+        let mut tx = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn { previous_output: OutPoint::new(parent.txid(), 0), script_sig: ScriptBuf::new(), sequence: Sequence::MAX, witness: Witness::new() }],
+            output: vec![TxOut { value: Amount::from_sat(90_000), script_pubkey: ScriptBuf::new_op_return([0; 4]) }],
+        };
+        let sighash = SighashCache::new(&tx)
+            .taproot_key_spend_signature_hash(0, &Prevouts::All(&[&parent.output[0]]), TapSighashType::Default)
+            .unwrap();
+        let signature = secp.sign_schnorr_no_aux_rand(&Message::from_digest(sighash.to_byte_array()), &keypair.tap_tweak(&secp, None).to_inner());
+        tx.input[0].witness = Witness::from_slice(&[signature.as_ref()]);
+        (parent, tx)
+    }
+
+    fn raw(tx: &Transaction) -> String {
+        hex::encode(serialize(tx))
+    }
+
+    #[test]
+    fn prevouts_from_parent_txs() {
+        let (parent, tx) = key_path_spend();
+        let spent_outputs = load_prevouts(&PrevoutSource::ParentTxs(vec![raw(&parent)])).unwrap();
+        assert_eq!(spent_outputs, HashMap::from([(tx.input[0].previous_output, parent.output[0].clone())]));
+
+        assert!(matches!(load_prevouts(&PrevoutSource::ParentTxs(vec!["zz".to_string()])), Err(VerificationError::HexDecodeError(_))));
+    }
+
+    #[test]
+    fn prevouts_from_psbt() {
+        let (parent, mut tx) = key_path_spend();
+        tx.input[0].witness = Witness::new();
+        let outpoint = tx.input[0].previous_output;
+        let mut psbt = Psbt::from_unsigned_tx(tx).unwrap();
+
+        // an input without either UTXO field is left out
+        assert!(load_prevouts(&PrevoutSource::Psbt(psbt.to_string())).unwrap().is_empty());
+
+        psbt.inputs[0].non_witness_utxo = Some(parent.clone());
+        let spent_outputs = load_prevouts(&PrevoutSource::Psbt(psbt.to_string())).unwrap();
+        assert_eq!(spent_outputs[&outpoint], parent.output[0]);
+
+        let witness_utxo = TxOut { value: Amount::from_sat(5_000), script_pubkey: parent.output[0].script_pubkey.clone() };
+        psbt.inputs[0].witness_utxo = Some(witness_utxo.clone());
+        let spent_outputs = load_prevouts(&PrevoutSource::Psbt(psbt.to_string())).unwrap();
+        assert_eq!(spent_outputs[&outpoint], witness_utxo);
+
+        psbt.inputs[0].witness_utxo = None;
+        psbt.inputs[0].non_witness_utxo = Some(funding(ScriptBuf::new()));
+        assert!(matches!(load_prevouts(&PrevoutSource::Psbt(psbt.to_string())), Err(VerificationError::InvalidPsbt(_))));
+        assert!(matches!(load_prevouts(&PrevoutSource::Psbt("not a psbt".to_string())), Err(VerificationError::InvalidPsbt(_))));
+    }
+
+    #[test]
+    fn prevouts_from_file() {
+        let (parent, tx) = key_path_spend();
+        let outpoint = tx.input[0].previous_output;
+        let path = std::env::temp_dir().join(format!("prevouts-{}.json", std::process::id()));
+        fs::write(&path, format!(
+            r#"[{{"txid": "{}", "vout": 0, "scriptPubKey": "{}", "amount": 0.001}}]"#,
+            outpoint.txid, parent.output[0].script_pubkey.to_hex_string(),
+        )).unwrap();
+        let spent_outputs = load_prevouts(&PrevoutSource::File(path.clone()));
+
+        fs::write(&path, r#"[{"txid": "00", "vout": 0}]"#).unwrap();
+        let invalid = load_prevouts(&PrevoutSource::File(path.clone()));
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(spent_outputs.unwrap(), HashMap::from([(outpoint, parent.output[0].clone())]));
+        assert!(matches!(invalid, Err(VerificationError::Json(_))));
+        assert!(matches!(load_prevouts(&PrevoutSource::File(path)), Err(VerificationError::Io(_))));
+    }
+
+    #[test]
+    fn offline_verification_checks_taproot_signatures() {
+        let (parent, mut tx) = key_path_spend();
+        let prevouts = PrevoutSource::ParentTxs(vec![raw(&parent)]);
+
+        let verified = verify_signed_tx_offline(&raw(&tx), &prevouts).unwrap();
+        assert_eq!((verified.txid, verified.inputs, verified.valid), (tx.txid(), 1, true));
+
+        // flip a bit of the signature: libbitcoinconsensus alone would still accept the spend
+        let mut signature = tx.input[0].witness.to_vec().remove(0);
+        signature[10] ^= 1;
+        tx.input[0].witness = Witness::from_slice(&[signature]);
+        assert!(tx.verify(|outpoint: &OutPoint| (*outpoint == tx.input[0].previous_output).then(|| parent.output[0].clone())).is_ok());
+        assert!(matches!(
+            verify_signed_tx_offline(&raw(&tx), &prevouts),
+            Err(VerificationError::TransactionVerificationFailed(reason)) if reason.starts_with("input 0: SCHNORR_SIG")
+        ));
+
+        assert!(matches!(
+            verify_signed_tx_offline(&raw(&tx), &PrevoutSource::ParentTxs(Vec::new())),
+            Err(VerificationError::MissingPrevout(0, _))
+        ));
+    }
+
+    #[test]
+    fn taproot_inputs_checked_natively() {
+        let (parent, mut tx) = key_path_spend();
+        let spent_outputs = load_prevouts(&PrevoutSource::ParentTxs(vec![raw(&parent)])).unwrap();
+        let statuses = [(UtxoStatus::Unknown, None)];
+
+        let report = &report_inputs(&tx, &spent_outputs, &statuses)[0];
+        assert_eq!(report.taproot.as_deref(), Some("ok"));
+        assert!(report.valid);
+
+        tx.output[0].value = Amount::from_sat(80_000);
+        let report = &report_inputs(&tx, &spent_outputs, &statuses)[0];
+        assert_eq!(report.witness.as_deref(), Some("ok"));
+        assert_eq!(report.taproot.as_deref(), Some("SCHNORR_SIG"));
+        assert!(!report.valid);
+        assert!(report.error.is_some());
+    }
+}
@@ -1,13 +1,17 @@
+use std::str::FromStr;
+
 use log::info;
 
 use serde::Deserialize;
 
-use bitcoin::{Address, Amount, Network, OutPoint, Transaction, Txid};
+use bitcoin::consensus::deserialize;
+use bitcoin::psbt::Psbt;
+use bitcoin::{Address, Amount, FeeRate, Network, OutPoint, Transaction, Txid};
 use bitcoincore_rpc::json::{
-    AddressType, GetAddressInfoResult, GetBalancesResult, GetWalletInfoResult, ListUnspentQueryOptions, ListUnspentResultEntry, WalletProcessPsbtResult
+    AddressType, EstimateMode, GetAddressInfoResult, GetBalancesResult, GetWalletInfoResult, ListUnspentQueryOptions, ListUnspentResultEntry, WalletProcessPsbtResult
 };
 use bitcoincore_rpc::jsonrpc::serde_json::{json, Value};
-use bitcoincore_rpc::{Client, RpcApi};
+use bitcoincore_rpc::{Client, JsonOutPoint, RpcApi};
 
 use crate::modules::errors::WalletError;
 use crate::settings::Settings;
@@ -15,16 +19,66 @@ use crate::modules::bitcoind::create_rpc_client;
 
 #[derive(Deserialize)]
 struct SendResult {
-    txid: Txid,
+    complete: bool,
+    txid: Option<Txid>,
+    psbt: Option<String>,
+    hex: Option<String>,
+}
+
+/// Funding and signing options of [`Wallet::send`]. Fields left unset use the wallet's defaults.
+#[derive(Clone, Debug)]
+pub struct SendOptions {
+    /// Explicit fee rate, instead of estimating one
+    pub fee_rate: Option<FeeRate>,
+    /// Confirmation target in blocks for the fee estimate
+    pub conf_target: Option<u16>,
+    pub estimate_mode: EstimateMode,
+    /// Take the fee out of the payment instead of adding it on top
+    pub subtract_fee: bool,
+    /// Signal BIP125 replaceability, by default as `-walletrbf` says
+    pub replaceable: Option<bool>,
+    pub change_address: Option<Address>,
+    pub change_type: Option<AddressType>,
+    pub locktime: Option<u32>,
+    /// Add the transaction to the wallet and broadcast it, or only return it signed
+    pub add_to_wallet: bool,
+}
+
+impl Default for SendOptions {
+    fn default() -> Self {
+        SendOptions {
+            fee_rate: None,
+            conf_target: None,
+            estimate_mode: EstimateMode::Unset,
+            subtract_fee: false,
+            replaceable: None,
+            change_address: None,
+            change_type: None,
+            locktime: None,
+            add_to_wallet: true,
+        }
+    }
+}
+
+/// A payment made by [`Wallet::send`]: the signed transaction, the output paying the recipient
+/// and the fee.
+#[derive(Clone, Debug)]
+pub struct SentPayment {
+    pub tx: Transaction,
+    pub vout: u32,
+    pub fee: Amount,
+    pub broadcast: bool,
 }
 
-pub(crate) struct Wallet {
+/// A Bitcoin Core wallet loaded on the node, with an RPC client scoped to `/wallet/<name>`.
+pub struct Wallet {
     client: Client,
     network: Network,
 }
 
 impl Wallet {
-    pub(crate) fn new(name: &str, settings: &Settings) -> Result<Self, WalletError> {
+    /// Load the named wallet, creating it first if it does not exist and `create_wallets` is enabled.
+    pub fn new(name: &str, settings: &Settings) -> Result<Self, WalletError> {
         let name = name.to_string();
         let client = create_rpc_client(settings, None)?;
 
@@ -53,43 +107,118 @@ impl Wallet {
         })
     }
 
-    pub(crate) fn new_address(&self, address_type: &AddressType) -> Result<Address, WalletError> {
+    /// RPC client bound to this wallet, for calls the wrapper does not cover.
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// Network the wallet's addresses are checked against.
+    pub fn network(&self) -> Network {
+        self.network
+    }
+
+    /// Generate a new receive address of the given type.
+    pub fn new_address(&self, address_type: &AddressType) -> Result<Address, WalletError> {
         let address = self.client
             .get_new_address(None, Some(*address_type))?;
         address.require_network(self.network)
             .map_err(|_| WalletError::AddressNetworkMismatch)
     }
 
-    pub(crate) fn get_balances(&self) -> Result<GetBalancesResult, WalletError> {
+    /// Generate a new change address of the given type, from the wallet's internal keychain.
+    pub fn new_change_address(&self, address_type: &AddressType) -> Result<Address, WalletError> {
+        let address = self.client
+            .get_raw_change_address(Some(*address_type))?;
+        address.require_network(self.network)
+            .map_err(|_| WalletError::AddressNetworkMismatch)
+    }
+
+    /// Output descriptor of a wallet address with its key origins, as `getaddressinfo` reports it.
+    pub fn address_descriptor(&self, address: &Address) -> Result<Option<String>, WalletError> {
+        let info: Value = self.client.call("getaddressinfo", &[address.to_string().into()])?;
+        Ok(info["desc"].as_str().map(str::to_string))
+    }
+
+    /// Trusted, pending and immature balances of the wallet.
+    pub fn get_balances(&self) -> Result<GetBalancesResult, WalletError> {
         self.client.get_balances().map_err(WalletError::from)
     }
 
-    pub(crate) fn send(&self, address: &Address, amount: Amount) -> Result<OutPoint, WalletError> {
+    /// Create and sign a payment, and unless `options` say otherwise add it to the wallet and
+    /// broadcast it.
+    pub fn send(&self, address: &Address, amount: Amount, options: &SendOptions) -> Result<SentPayment, WalletError> {
         let output = json!([{
             address.to_string(): amount.to_btc()
         }]);
-        let send_result: SendResult = self
-            .client
-            .call("send", &[output, Value::Null, "unset".into(), 1.into()])?;
-        let txid = send_result.txid;
-    
-        let transaction_info = self.client
-            .get_transaction(&txid, None)?;
-        let target_vout = transaction_info.details.iter()
-            .find_map(|details| {
-                details.address.as_ref()
-                    .filter(|&tx_address| tx_address == address)
-                    .map(|_| details.vout)
-            })
+
+        let mut send_options = json!({ "add_to_wallet": options.add_to_wallet });
+        if options.subtract_fee {
+            send_options["subtract_fee_from_outputs"] = json!([0]);
+        }
+        if let Some(replaceable) = options.replaceable {
+            send_options["replaceable"] = replaceable.into();
+        }
+        if let Some(change_address) = &options.change_address {
+            send_options["change_address"] = change_address.to_string().into();
+        }
+        if let Some(change_type) = options.change_type {
+            send_options["change_type"] = json!(change_type);
+        }
+        if let Some(locktime) = options.locktime {
+            send_options["locktime"] = locktime.into();
+        }
+
+        // sat/vB, the unit `send` takes its fee_rate argument in
+        let fee_rate = options.fee_rate.map(|fee_rate| fee_rate.to_sat_per_kwu() as f64 / 250.0);
+        let send_result: SendResult = self.client.call("send", &[
+            output,
+            json!(options.conf_target),
+            json!(options.estimate_mode),
+            json!(fee_rate),
+            send_options,
+        ])?;
+        if !send_result.complete {
+            return Err(WalletError::SigningFailed("the wallet could not sign every input".to_string()));
+        }
+
+        let (tx, fee) = match (options.add_to_wallet, send_result.txid) {
+            (true, Some(txid)) => {
+                let transaction_info = self.client.get_transaction(&txid, None)?;
+                let fee = transaction_info.fee.map_or(Amount::ZERO, |fee| Amount::from_sat(fee.to_sat().unsigned_abs()));
+                let tx = transaction_info.transaction()
+                    .map_err(|e| WalletError::InvalidPsbt(e.to_string()))?;
+                (tx, fee)
+            }
+            (true, None) => return Err(WalletError::InvalidPsbt("send returned no txid".to_string())),
+            // the wallet never saw the transaction, so it comes from the reply: the signed hex,
+            // and the fee from the PSBT, which still carries the spent outputs
+            (false, _) => {
+                let psbt = Psbt::from_str(&send_result.psbt.unwrap_or_default())
+                    .map_err(|e| WalletError::InvalidPsbt(e.to_string()))?;
+                let fee = psbt.fee().map_err(|e| WalletError::InvalidPsbt(e.to_string()))?;
+                let tx = match send_result.hex {
+                    Some(hex) => deserialize::<Transaction>(&hex::decode(hex).map_err(|e| WalletError::InvalidPsbt(e.to_string()))?)
+                        .map_err(|e| WalletError::InvalidPsbt(e.to_string()))?,
+                    None => psbt.extract_tx().map_err(|e| WalletError::InvalidPsbt(e.to_string()))?,
+                };
+                (tx, fee)
+            }
+        };
+
+        let vout = tx.output.iter()
+            .position(|output| output.script_pubkey == address.script_pubkey())
             .ok_or(WalletError::AddressNotFound)?;
-    
-        Ok(OutPoint {
-            txid,
-            vout: target_vout,
+
+        Ok(SentPayment {
+            tx,
+            vout: vout as u32,
+            fee,
+            broadcast: options.add_to_wallet,
         })
     }
 
-    pub(crate) fn sign_tx(&self, tx: &Transaction) -> Result<Transaction, WalletError> {
+    /// Sign every input of `tx` the wallet has keys for.
+    pub fn sign_tx(&self, tx: &Transaction) -> Result<Transaction, WalletError> {
         let signed = self.client
             .sign_raw_transaction_with_wallet(tx, None, None)?;
         signed
@@ -97,23 +226,65 @@ impl Wallet {
             .map_err(|e| WalletError::SigningFailed(e.to_string()))
     }
 
-    pub(crate) fn get_wallet_info(&self) -> Result<GetWalletInfoResult, WalletError> {
+    /// Wallet metadata as reported by `getwalletinfo`.
+    pub fn get_wallet_info(&self) -> Result<GetWalletInfoResult, WalletError> {
         self.client.get_wallet_info().map_err(WalletError::from)
     }
 
-    pub(crate) fn get_address_info(&self, address: &Address) -> Result<GetAddressInfoResult, WalletError> {
+    /// Ownership, script and derivation details of an address.
+    pub fn get_address_info(&self, address: &Address) -> Result<GetAddressInfoResult, WalletError> {
         self.client.get_address_info(address).map_err(WalletError::from)
     }
+
+    /// Record `label` for `address` in the wallet's address book, for receive and send addresses alike.
+    pub fn set_label(&self, address: &Address, label: &str) -> Result<(), WalletError> {
+        let _: Value = self.client.call("setlabel", &[address.to_string().into(), label.into()])?;
+        Ok(())
+    }
     
-    pub(crate) fn list_all_unspent(&self, query_options: Option<ListUnspentQueryOptions>) -> Result<Vec<ListUnspentResultEntry>, WalletError> {
+    /// All UTXOs with at least one confirmation. Locked UTXOs are left out.
+    pub fn list_all_unspent(&self, query_options: Option<ListUnspentQueryOptions>) -> Result<Vec<ListUnspentResultEntry>, WalletError> {
         self.client
             .list_unspent(Some(1), Some(9999999), None, None, query_options)
             .map_err(WalletError::from)
     }
 
-    pub(crate) fn process_psbt(&self, psbt: &str) -> Result<WalletProcessPsbtResult, WalletError> {
+    /// Lock `outpoints` so coin selection, here and in the node, leaves them alone. Locks last
+    /// until released or the node restarts, or are also kept in the wallet database when
+    /// `persistent`. Fails if any outpoint is already locked.
+    pub fn lock_unspent(&self, outpoints: &[OutPoint], persistent: bool) -> Result<(), WalletError> {
+        let outpoints: Vec<JsonOutPoint> = outpoints.iter().copied().map(JsonOutPoint::from).collect();
+        let locked: bool = self.client
+            .call("lockunspent", &[false.into(), json!(outpoints), persistent.into()])?;
+        locked.then_some(()).ok_or(WalletError::LockFailed)
+    }
+
+    /// Release the locks on `outpoints`, or every lock the wallet holds if empty.
+    pub fn unlock_unspent(&self, outpoints: &[OutPoint]) -> Result<(), WalletError> {
+        let unlocked = match outpoints.is_empty() {
+            true => self.client.unlock_unspent_all()?,
+            false => self.client.unlock_unspent(outpoints)?,
+        };
+        unlocked.then_some(()).ok_or(WalletError::LockFailed)
+    }
+
+    /// Outpoints currently locked, persistent or not.
+    pub fn list_locked_unspent(&self) -> Result<Vec<OutPoint>, WalletError> {
+        let locked: Vec<JsonOutPoint> = self.client.call("listlockunspent", &[])?;
+        Ok(locked.into_iter().map(|outpoint| OutPoint::new(outpoint.txid, outpoint.vout)).collect())
+    }
+
+    /// Update and sign a base64 PSBT with the wallet's keys.
+    pub fn process_psbt(&self, psbt: &str) -> Result<WalletProcessPsbtResult, WalletError> {
         self.client
             .wallet_process_psbt(psbt, None, None, None)
             .map_err(WalletError::from)
     }
+
+    /// Add UTXO, script and BIP32 derivation data to a base64 PSBT without signing it.
+    pub fn update_psbt(&self, psbt: &str) -> Result<WalletProcessPsbtResult, WalletError> {
+        self.client
+            .wallet_process_psbt(psbt, Some(false), None, Some(true))
+            .map_err(WalletError::from)
+    }
 }
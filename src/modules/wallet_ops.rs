@@ -1,63 +1,183 @@
-use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
-use log::info;
+use log::{info, warn};
 
+use serde::Serialize;
 use serde_json::{json, Value};
 
-use bitcoin::{Address, Amount, Transaction, consensus::serialize};
-use bitcoincore_rpc::json::{AddressType, CreateRawTransactionInput, GetAddressInfoResult, GetDescriptorInfoResult, GetWalletInfoResult, ListUnspentResultEntry, WalletCreateFundedPsbtResult};
+use bitcoin::address::NetworkUnchecked;
+use bitcoin::blockdata::constants::COINBASE_MATURITY;
+use bitcoin::psbt::Psbt;
+use bitcoin::absolute::LockTime;
+use bitcoin::transaction::Version;
+use bitcoin::{Address, Amount, FeeRate, OutPoint, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Txid, Witness, consensus::serialize};
+use bitcoincore_rpc::json::{AddressType, GetAddressInfoResult, GetBalancesResultEntry, GetDescriptorInfoResult, GetWalletInfoResult, ListUnspentResultEntry, WalletCreateFundedPsbtResult, WalletProcessPsbtResult};
 use bitcoincore_rpc::{Client, RawTx, RpcApi};
 
 use miniscript::bitcoin::secp256k1::Secp256k1;
 use miniscript::{Descriptor, DescriptorPublicKey};
 
 use crate::settings::Settings;
-use crate::modules::wallet::Wallet;
+use crate::modules::wallet::{SendOptions, Wallet};
 use crate::modules::bitcoind::create_rpc_client;
-use crate::modules::client::mine_blocks;
-use crate::utils::utils::{extract_int_ext_xpubs, strat_handler, UTXOStrategy};
+use crate::modules::client::{combine_psbts, finalize_psbt_and_broadcast, get_tx_out, mine_blocks, FinalizedTx, MinedBlocks};
+use crate::modules::payouts::Payout;
+use crate::modules::psbt_analysis::{analyze_psbt, PsbtRole};
+use crate::modules::multisig::{parse_wallet_file, CosignerKey, MultisigConfig, WalletFileFormat};
+use crate::utils::fees::spend_weight;
+use crate::utils::utils::{extract_int_ext_xpubs, select_utxos_manual, strat_handler, wallet_address_type, CoinControl, CoinSelection, MultisigScriptType, SelectionParams, TxLayout, TxOrdering};
+
+use super::errors::{BitcoindError, MultisigError, UtilsError, WalletOpsError};
+
+#[derive(Debug, Serialize)]
+pub struct LoadedWallet {
+    pub wallet_name: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct NewAddress {
+    pub address: Address,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DerivedAddress {
+    pub index: u32,
+    pub address: Address<NetworkUnchecked>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MultisigWallet {
+    pub wallet_name: String,
+    pub script_type: MultisigScriptType,
+    pub external_descriptor: String,
+    pub internal_descriptor: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExportedMultisig {
+    pub wallet_name: String,
+    pub format: WalletFileFormat,
+    pub path: PathBuf,
+    pub first_address: Address<NetworkUnchecked>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BalanceDetails {
+    #[serde(with = "bitcoin::amount::serde::as_btc")]
+    pub trusted: Amount,
+    #[serde(with = "bitcoin::amount::serde::as_btc")]
+    pub untrusted_pending: Amount,
+    #[serde(with = "bitcoin::amount::serde::as_btc")]
+    pub immature: Amount,
+}
+
+impl From<GetBalancesResultEntry> for BalanceDetails {
+    fn from(entry: GetBalancesResultEntry) -> Self {
+        BalanceDetails {
+            trusted: entry.trusted,
+            untrusted_pending: entry.untrusted_pending,
+            immature: entry.immature,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct WalletBalances {
+    pub mine: BalanceDetails,
+    pub watchonly: Option<BalanceDetails>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UtxoLocks {
+    pub wallet_name: String,
+    pub utxos: Vec<OutPoint>,
+}
 
-use super::errors::WalletOpsError;
+#[derive(Debug, Serialize)]
+pub struct SignedTx {
+    pub txid: Txid,
+    pub hex: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SigningStep {
+    pub cosigner: String,
+    pub signed_inputs: Vec<usize>,
+    /// Whether the PSBT combined so far carries every required signature
+    pub complete: bool,
+    /// Role the combined PSBT is waiting for
+    pub next_role: PsbtRole,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MultisigSpend {
+    pub psbt: String,
+    #[serde(with = "bitcoin::amount::serde::as_btc")]
+    pub fee: Amount,
+    pub steps: Vec<SigningStep>,
+    pub txid: String,
+    pub hex: String,
+}
 
-pub fn new_wallet(wallet_name: &str, settings: &Settings) -> Result<(), WalletOpsError> {
+#[derive(Debug, Serialize)]
+pub struct SentTx {
+    pub txid: Txid,
+    pub vout: u32,
+    /// Paid to the recipient, less the fee when it was subtracted
+    #[serde(with = "bitcoin::amount::serde::as_btc")]
+    pub amount: Amount,
+    #[serde(with = "bitcoin::amount::serde::as_btc")]
+    pub fee: Amount,
+    pub broadcast: bool,
+    /// Signed transaction, when it was not broadcast
+    pub hex: Option<String>,
+}
+
+/// Load or create a wallet.
+pub fn new_wallet(wallet_name: &str, settings: &Settings) -> Result<LoadedWallet, WalletOpsError> {
     Wallet::new(wallet_name, settings)?;
-    Ok(())
+    Ok(LoadedWallet { wallet_name: wallet_name.to_string() })
 }
 
-pub fn get_wallet_info(wallet_name: &str, settings: &Settings) -> Result<(), WalletOpsError> {
+/// Wallet metadata for the named wallet.
+pub fn get_wallet_info(wallet_name: &str, settings: &Settings) -> Result<GetWalletInfoResult, WalletOpsError> {
     let wallet: Wallet = Wallet::new(wallet_name, settings)?;
     let wallet_info: GetWalletInfoResult = wallet.get_wallet_info()?;
-    info!("{:#?}", wallet_info);
-    Ok(())
+    Ok(wallet_info)
 }
 
+/// Raw `listdescriptors` output for the named wallet.
 pub fn list_descriptors(wallet_name: &str, settings: &Settings) -> Result<serde_json::Value, WalletOpsError> {
     let client = create_rpc_client(settings, Some(wallet_name))?;
     let descriptors: serde_json::Value = client.call("listdescriptors", &[])?;
     Ok(descriptors)
 }
 
-pub fn list_descriptors_wrapper(wallet_name: &str, settings: &Settings) -> Result<(), WalletOpsError> {
+/// [`list_descriptors`] for the `list-descriptors` command.
+pub fn list_descriptors_wrapper(wallet_name: &str, settings: &Settings) -> Result<Value, WalletOpsError> {
     let descriptors: Value = list_descriptors(wallet_name, settings)?;
-    info!("{:#?}", descriptors);
-    Ok(())
+    Ok(descriptors)
 }
 
-pub fn get_new_address(wallet_name: &str, address_type: &AddressType, settings: &Settings) -> Result<(), WalletOpsError> {
+/// Generate a new receive address of the given type.
+pub fn get_new_address(wallet_name: &str, address_type: &AddressType, settings: &Settings) -> Result<NewAddress, WalletOpsError> {
     let wallet: Wallet = Wallet::new(wallet_name, settings)?;
     let address: Address = wallet.new_address(address_type)?;
-    info!("{}", format!("{:?}", address));
-    Ok(())
+    info!("New address: {}", address);
+    Ok(NewAddress { address })
 }
 
-pub fn get_address_info(wallet_name: &str, address: &Address, settings: &Settings) -> Result<(), WalletOpsError> {
+/// Ownership, script and derivation details of an address.
+pub fn get_address_info(wallet_name: &str, address: &Address, settings: &Settings) -> Result<GetAddressInfoResult, WalletOpsError> {
     let wallet: Wallet = Wallet::new(wallet_name, settings)?;
     let address_info: GetAddressInfoResult = wallet.get_address_info(address)?;
-    info!("{:#?}", address_info);
-    Ok(())
+    Ok(address_info)
 }
 
-pub fn derive_addresses(descriptor: &str, start: &u32, end: &u32, settings: &Settings) -> Result<(), WalletOpsError> {
+/// Derive the addresses at indices `start..=end` of a ranged descriptor.
+pub fn derive_addresses(descriptor: &str, start: &u32, end: &u32, settings: &Settings) -> Result<Vec<DerivedAddress>, WalletOpsError> {
     let client = create_rpc_client(settings, None)?;
     let range: [u32; 2] = [*start, *end];
 
@@ -70,58 +190,139 @@ pub fn derive_addresses(descriptor: &str, start: &u32, end: &u32, settings: &Set
     };
 
     let addresses = client.derive_addresses(&desc, Some(range))?;
-    info!("Derived addresses:");
-    for (i, address) in addresses.iter().enumerate() {
-        info!("  {}: {:#?}", i + *start as usize, address);
-    }
-    Ok(())
+    info!("Derived {} addresses", addresses.len());
+    let derived = addresses.into_iter()
+        .zip(*start..)
+        .map(|(address, index)| DerivedAddress { index, address })
+        .collect();
+    Ok(derived)
 }
 
-pub fn new_multisig_wallet(nrequired: u32, wallet_names: &Vec<String>, multisig_name: &str, settings: &Settings) -> Result<(), WalletOpsError> {
-    if wallet_names.len() < nrequired as usize {
-        return Err(WalletOpsError::Other("More required signers than wallets".into()));
-    }
-
-    let mut xpubs: HashMap<String, String> = HashMap::new();
+/// Create a watch-only `nrequired`-of-N multisig wallet from the xpubs of `wallet_names`.
+pub fn new_multisig_wallet(nrequired: u32, wallet_names: &[String], multisig_name: &str, script_type: MultisigScriptType, settings: &Settings) -> Result<MultisigWallet, WalletOpsError> {
+    validate_multisig(nrequired, wallet_names.len(), script_type)?;
+    validate_unique_cosigners(wallet_names)?;
 
     for wallet_name in wallet_names {
         new_wallet(wallet_name, settings)?;
     }
 
-    for (i, wallet_name) in wallet_names.iter().enumerate() {
+    let mut external_xpubs: Vec<String> = Vec::new();
+    let mut internal_xpubs: Vec<String> = Vec::new();
+    for wallet_name in wallet_names {
         let descriptors: serde_json::Value = list_descriptors(wallet_name, settings)?;
         let descriptors_array: &Vec<serde_json::Value> = descriptors["descriptors"].as_array()
             .ok_or_else(|| WalletOpsError::Other("Invalid descriptor format".into()))?;
-        xpubs = extract_int_ext_xpubs(xpubs, descriptors_array.clone(), i)?;
+        let (external_xpub, internal_xpub) = extract_int_ext_xpubs(descriptors_array, script_type)?;
+        external_xpubs.push(external_xpub);
+        internal_xpubs.push(internal_xpub);
     }
 
-    let num_signers = nrequired.to_string();
-    let external_desc = format!(
-        "wsh(sortedmulti({}, {}, {}, {}))",
-        num_signers, xpubs["external_xpub_1"], xpubs["external_xpub_2"], xpubs["external_xpub_3"]
-    );
-    let internal_desc = format!(
-        "wsh(sortedmulti({}, {}, {}, {}))",
-        num_signers, xpubs["internal_xpub_1"], xpubs["internal_xpub_2"], xpubs["internal_xpub_3"]
-    );
+    let external_desc = script_type.descriptor(nrequired, &external_xpubs);
+    let internal_desc = script_type.descriptor(nrequired, &internal_xpubs);
+
+    create_watch_only_multisig(multisig_name, script_type, &external_desc, &internal_desc, None, settings)
+}
+
+/// Create a watch-only `nrequired`-of-N multisig wallet from externally supplied
+/// `[fingerprint/path]xpub` keys, for cosigners whose keys live outside Bitcoin Core.
+pub fn new_multisig_from_xpubs(nrequired: u32, xpubs: &[String], multisig_name: &str, script_type: MultisigScriptType, settings: &Settings) -> Result<MultisigWallet, WalletOpsError> {
+    let keys = xpubs.iter()
+        .map(|xpub| CosignerKey::from_str(xpub))
+        .collect::<Result<Vec<CosignerKey>, MultisigError>>()?;
+    let config = MultisigConfig { nrequired, script_type, keys };
+    validate_multisig_config(&config, settings)?;
+
+    create_watch_only_multisig(multisig_name, script_type, &config.descriptor(false), &config.descriptor(true), None, settings)
+}
+
+/// Write the named multisig wallet to `path` as a BSMS record or a Coldcard/Sparrow setup file.
+pub fn export_multisig_wallet(wallet_name: &str, format: WalletFileFormat, path: &Path, settings: &Settings) -> Result<ExportedMultisig, WalletOpsError> {
+    let descriptors: serde_json::Value = list_descriptors(wallet_name, settings)?;
+    let external_descriptor = descriptors["descriptors"].as_array()
+        .and_then(|descriptors| descriptors.iter().find(|desc| {
+            desc["active"].as_bool() == Some(true) && desc["internal"].as_bool() == Some(false)
+        }))
+        .and_then(|desc| desc["desc"].as_str())
+        .ok_or(UtilsError::ExternalXpubNotFound)?;
+    let config = MultisigConfig::from_descriptor(external_descriptor)?;
+
+    let client: Client = create_rpc_client(settings, None)?;
+    let first_address = client.derive_addresses(external_descriptor, Some([0, 0]))?
+        .into_iter()
+        .next()
+        .ok_or_else(|| WalletOpsError::Other("No address derived from the receive descriptor".into()))?;
+
+    let contents = match format {
+        WalletFileFormat::Bsms => config.to_bsms(&first_address.clone().assume_checked().to_string()),
+        WalletFileFormat::Coldcard => config.to_coldcard(wallet_name)?,
+    };
+    fs::write(path, contents).map_err(MultisigError::from)?;
+    info!("Exported multisig wallet {} to {}", wallet_name, path.display());
+
+    Ok(ExportedMultisig {
+        wallet_name: wallet_name.to_string(),
+        format,
+        path: path.to_path_buf(),
+        first_address,
+    })
+}
+
+/// Create a watch-only multisig wallet from a BSMS record or a Coldcard/Sparrow setup file.
+/// `multisig_name` overrides the name in the file and is required for BSMS records, which carry none.
+pub fn import_multisig_wallet(path: &Path, multisig_name: Option<&str>, settings: &Settings) -> Result<MultisigWallet, WalletOpsError> {
+    let contents = fs::read_to_string(path).map_err(MultisigError::from)?;
+    let wallet_file = parse_wallet_file(&contents)?;
+    let multisig_name = multisig_name
+        .map(str::to_string)
+        .or(wallet_file.name)
+        .ok_or(MultisigError::MissingWalletName)?;
+
+    let config = wallet_file.config;
+    validate_multisig_config(&config, settings)?;
+    info!("Importing {:?} wallet file {} as {}", wallet_file.format, path.display(), multisig_name);
+
+    create_watch_only_multisig(
+        &multisig_name,
+        config.script_type,
+        &config.descriptor(false),
+        &config.descriptor(true),
+        wallet_file.first_address.as_deref(),
+        settings,
+    )
+}
 
+/// Checksum both descriptors, check the first receive address against `first_address` if given,
+/// then create a blank watch-only wallet and import the descriptors into it.
+fn create_watch_only_multisig(multisig_name: &str, script_type: MultisigScriptType, external_desc: &str, internal_desc: &str, first_address: Option<&str>, settings: &Settings) -> Result<MultisigWallet, WalletOpsError> {
     let client: Client = create_rpc_client(settings, None)?;
 
-    let external_desc_info: GetDescriptorInfoResult = client.get_descriptor_info(&external_desc)?;
-    let internal_desc_info: GetDescriptorInfoResult = client.get_descriptor_info(&internal_desc)?;
+    let external_desc_info: GetDescriptorInfoResult = client.get_descriptor_info(external_desc)?;
+    let internal_desc_info: GetDescriptorInfoResult = client.get_descriptor_info(internal_desc)?;
 
     let external_descriptor: String = external_desc_info.descriptor;
     let internal_descriptor: String = internal_desc_info.descriptor;
 
+    if let Some(expected) = first_address {
+        let derived = client.derive_addresses(&external_descriptor, Some([0, 0]))?
+            .into_iter()
+            .next()
+            .map(|address| address.assume_checked().to_string())
+            .unwrap_or_default();
+        if derived != expected {
+            return Err(MultisigError::FirstAddressMismatch(expected.to_string(), derived).into());
+        }
+    }
+
     let multisig_ext_desc = json!({
-        "desc": external_descriptor,
+        "desc": &external_descriptor,
         "active": true,
         "internal": false,
         "timestamp": json!("now")
     });
 
     let multisig_int_desc = json!({
-        "desc": internal_descriptor,
+        "desc": &internal_descriptor,
         "active": true,
         "internal": true,
         "timestamp": json!("now")
@@ -135,90 +336,203 @@ pub fn new_multisig_wallet(nrequired: u32, wallet_names: &Vec<String>, multisig_
     let client2 = create_rpc_client(settings, Some(multisig_name))?;
     client2.call::<serde_json::Value>("importdescriptors", &[json!(multisig_desc_vec)])?;
 
-    get_wallet_info(multisig_name, settings)?;
+    info!("Created multisig wallet {}", multisig_name);
+
+    Ok(MultisigWallet {
+        wallet_name: multisig_name.to_string(),
+        script_type,
+        external_descriptor,
+        internal_descriptor,
+    })
+}
 
+/// Check the threshold against the number of cosigners and the script type's key limit
+/// before any wallet is created.
+fn validate_multisig(nrequired: u32, total: usize, script_type: MultisigScriptType) -> Result<(), WalletOpsError> {
+    if total == 0 {
+        return Err(WalletOpsError::NoCosigners);
+    }
+    if total > script_type.max_keys() {
+        return Err(WalletOpsError::TooManyCosigners(total, script_type.max_keys()));
+    }
+    if nrequired == 0 || nrequired as usize > total {
+        return Err(WalletOpsError::InvalidThreshold(nrequired, total));
+    }
     Ok(())
 }
 
-pub fn get_balances(wallet_name: &str, settings: &Settings) -> Result<(), WalletOpsError> {
+/// [`validate_multisig`] plus network and duplicate checks on externally supplied keys.
+fn validate_multisig_config(config: &MultisigConfig, settings: &Settings) -> Result<(), WalletOpsError> {
+    validate_multisig(config.nrequired, config.keys.len(), config.script_type)?;
+    config.require_network(settings.network)?;
+    let xpubs: Vec<String> = config.keys.iter().map(|key| key.xpub.to_string()).collect();
+    validate_unique_cosigners(&xpubs)
+}
+
+fn validate_unique_cosigners(wallet_names: &[String]) -> Result<(), WalletOpsError> {
+    let mut seen: Vec<&String> = Vec::with_capacity(wallet_names.len());
+    for wallet_name in wallet_names {
+        if seen.contains(&wallet_name) {
+            return Err(WalletOpsError::DuplicateCosigner(wallet_name.clone()));
+        }
+        seen.push(wallet_name);
+    }
+    Ok(())
+}
+
+/// Trusted, pending and immature balances of the named wallet.
+pub fn get_balances(wallet_name: &str, settings: &Settings) -> Result<WalletBalances, WalletOpsError> {
     let wallet: Wallet = Wallet::new(wallet_name, settings)?;
     let balances = wallet.get_balances()?;
-    info!("{:#?}", balances);
-    Ok(())
+    Ok(WalletBalances {
+        mine: balances.mine.into(),
+        watchonly: balances.watchonly.map(BalanceDetails::from),
+    })
 }
 
-pub fn list_unspent(wallet_name: &str, settings: &Settings) -> Result<(), WalletOpsError> {
+/// All confirmed UTXOs of the named wallet.
+pub fn list_unspent(wallet_name: &str, settings: &Settings) -> Result<Vec<ListUnspentResultEntry>, WalletOpsError> {
     let wallet: Wallet = Wallet::new(wallet_name, settings)?;
     let unspent_txs: Vec<ListUnspentResultEntry> = wallet.list_all_unspent(None)?;
-    info!("{:#?}", unspent_txs);
-    Ok(())
+    info!("Found {} unspent outputs", unspent_txs.len());
+    Ok(unspent_txs)
 }
 
-pub fn mine_blocks_wrapper(wallet_name: &str, blocks: u64, settings: &Settings) -> Result<(), WalletOpsError> {
-    let miner_wallet = Wallet::new(wallet_name, settings)?;
-    let address = miner_wallet.new_address(&AddressType::Bech32)?;
-    mine_blocks(Some(blocks), &address, settings)?;
-    Ok(())
+/// Lock `outpoints` of the named wallet against coin selection, across node restarts when `persistent`.
+pub fn lock_utxos(wallet_name: &str, outpoints: &[OutPoint], persistent: bool, settings: &Settings) -> Result<UtxoLocks, WalletOpsError> {
+    let wallet: Wallet = Wallet::new(wallet_name, settings)?;
+    wallet.lock_unspent(outpoints, persistent)?;
+    info!("Locked {} UTXOs in wallet {}", outpoints.len(), wallet_name);
+    Ok(UtxoLocks {
+        wallet_name: wallet_name.to_string(),
+        utxos: outpoints.to_vec(),
+    })
 }
 
-pub fn sign_tx(wallet_name: &str, recipient: &Address, amount: Amount, fee_amount: Amount, utxo_strat: UTXOStrategy, settings: &Settings) -> Result<Vec<u8>, WalletOpsError> {
+/// Release the named wallet's locks on `outpoints`, or all of them if empty.
+pub fn unlock_utxos(wallet_name: &str, outpoints: &[OutPoint], settings: &Settings) -> Result<UtxoLocks, WalletOpsError> {
     let wallet: Wallet = Wallet::new(wallet_name, settings)?;
-    let balances = wallet.get_balances()?;
+    let released = match outpoints.is_empty() {
+        true => wallet.list_locked_unspent()?,
+        false => outpoints.to_vec(),
+    };
+    wallet.unlock_unspent(outpoints)?;
+    info!("Unlocked {} UTXOs in wallet {}", released.len(), wallet_name);
+    Ok(UtxoLocks {
+        wallet_name: wallet_name.to_string(),
+        utxos: released,
+    })
+}
 
-    if balances.mine.trusted.to_sat() < amount.to_sat() {
-        return Err(WalletOpsError::InsufficientBalance);
-    }
+/// Release the named wallet's locks on the inputs of `tx_hex`, once it is broadcast, rejected or
+/// given up on. Inputs the wallet has no lock on are left alone.
+pub fn release_tx_inputs(wallet_name: &str, tx_hex: &str, settings: &Settings) -> Result<UtxoLocks, WalletOpsError> {
+    let wallet: Wallet = Wallet::new(wallet_name, settings)?;
+    let tx: Transaction = bitcoin::consensus::deserialize(&hex::decode(tx_hex).map_err(|e| WalletOpsError::Other(e.to_string()))?)
+        .map_err(|e| WalletOpsError::Other(e.to_string()))?;
+    let inputs: Vec<OutPoint> = tx.input.iter().map(|input| input.previous_output).collect();
+    let released = release_coins(&wallet, &inputs)?;
+    Ok(UtxoLocks {
+        wallet_name: wallet_name.to_string(),
+        utxos: released,
+    })
+}
 
-    let unspent_txs: Vec<ListUnspentResultEntry> = wallet.list_all_unspent(None)?;
-    if unspent_txs.is_empty() {
-        return Err(WalletOpsError::NoUnspentTransactions);
-    }
+/// Outpoints the named wallet currently has locked.
+pub fn list_locked_utxos(wallet_name: &str, settings: &Settings) -> Result<UtxoLocks, WalletOpsError> {
+    let wallet: Wallet = Wallet::new(wallet_name, settings)?;
+    let locked = wallet.list_locked_unspent()?;
+    info!("Wallet {} has {} locked UTXOs", wallet_name, locked.len());
+    Ok(UtxoLocks {
+        wallet_name: wallet_name.to_string(),
+        utxos: locked,
+    })
+}
 
-    let selected_utxos = strat_handler(&unspent_txs, amount, fee_amount, utxo_strat)
-        .map_err(|e| WalletOpsError::Other(e.to_string()))?;
+/// Mine `blocks` blocks paying the coinbase to a new address of the named wallet.
+pub fn mine_blocks_wrapper(wallet_name: &str, blocks: u64, settings: &Settings) -> Result<MinedBlocks, WalletOpsError> {
+    let miner_wallet = Wallet::new(wallet_name, settings)?;
+    let address = miner_wallet.new_address(&AddressType::Bech32)?;
+    let mined = mine_blocks(Some(blocks), &address, settings)?;
+    Ok(mined)
+}
 
-    let mut utxo_inputs: Vec<CreateRawTransactionInput> = Vec::new();
-    let mut total_amount = Amount::from_sat(0);
-    for utxo in &selected_utxos {
-        utxo_inputs.push(CreateRawTransactionInput {
-            txid: utxo.txid,
-            vout: utxo.vout,
-            sequence: Some(0),
-        });
-        total_amount += utxo.amount;
-    }
+/// Build and sign a transaction paying `amount` to `recipient` at `fee_rate`, returning the serialized transaction.
+/// The inputs are locked so no other selection picks them before the transaction is broadcast.
+pub fn sign_tx(wallet_name: &str, recipient: &Address, amount: Amount, fee_rate: FeeRate, coin_control: &CoinControl, layout: &TxLayout, settings: &Settings) -> Result<Vec<u8>, WalletOpsError> {
+    sign_batch_tx(wallet_name, &[Payout::new(recipient.clone(), amount)], fee_rate, coin_control, layout, settings)
+}
 
-    let mut outputs: HashMap<String, Amount> = HashMap::new();
-    outputs.insert(recipient.to_string(), amount);
+/// Build and sign one transaction paying every payout at `fee_rate`, with a single change output.
+/// Labelled payout addresses are added to the wallet's address book.
+pub fn sign_batch_tx(wallet_name: &str, payouts: &[Payout], fee_rate: FeeRate, coin_control: &CoinControl, layout: &TxLayout, settings: &Settings) -> Result<Vec<u8>, WalletOpsError> {
+    let wallet: Wallet = Wallet::new(wallet_name, settings)?;
+    let change_address = change_address(&wallet, payouts, layout)?;
+    let selection = select_coins(&wallet, payouts, &change_address, fee_rate, coin_control, settings)?;
 
-    let change_amount = total_amount - amount - fee_amount;
-    if change_amount.to_sat() > 0 {
-        let change_address: Address = wallet.new_address(&AddressType::Bech32)?;
-        outputs.insert(change_address.to_string(), change_amount);
-    }
+    with_reserved_coins(&wallet, &selection, || {
+        let tx: Transaction = unsigned_tx(&selection, payouts, &change_address, Sequence::ZERO, layout);
 
-    let client: Client = create_rpc_client(settings, Some(wallet_name))?;
-    let tx: Transaction = client.create_raw_transaction(&utxo_inputs[..], &outputs, None, None)?;
+        let signed_tx: Transaction = wallet.sign_tx(&tx)?;
+        let raw_tx: String = serialize(&signed_tx).raw_hex();
+        info!("Signed raw transaction: {}", raw_tx);
+        label_payouts(&wallet, payouts)?;
 
-    let signed_tx: Transaction = wallet.sign_tx(&tx)?;
-    let raw_tx: String = serialize(&signed_tx).raw_hex();
-    info!("Signed raw transaction: {}", raw_tx);
+        Ok(serialize(&signed_tx))
+    })
+}
 
-    Ok(serialize(&signed_tx))
+/// [`sign_tx`] returning the txid and hex.
+pub fn sign_tx_wrapper(wallet_name: &str, recipient: &Address, amount: Amount, fee_rate: FeeRate, coin_control: &CoinControl, layout: &TxLayout, settings: &Settings) -> Result<SignedTx, WalletOpsError> {
+    let raw_tx: Vec<u8> = sign_tx(wallet_name, recipient, amount, fee_rate, coin_control, layout, settings)?;
+    let signed_tx: Transaction = bitcoin::consensus::deserialize(&raw_tx)
+        .map_err(|e| WalletOpsError::Other(e.to_string()))?;
+    Ok(SignedTx {
+        txid: signed_tx.txid(),
+        hex: hex::encode(&raw_tx),
+    })
 }
 
-pub fn sign_tx_wrapper(wallet_name: &str, recipient: &Address, amount: Amount, fee_amount: Amount, utxo_strat: UTXOStrategy, settings: &Settings) -> Result<(), WalletOpsError> {
-    sign_tx(wallet_name, recipient, amount, fee_amount, utxo_strat, settings)?;
-    Ok(())
+/// [`sign_batch_tx`] returning the txid and hex.
+pub fn sign_batch_tx_wrapper(wallet_name: &str, payouts: &[Payout], fee_rate: FeeRate, coin_control: &CoinControl, layout: &TxLayout, settings: &Settings) -> Result<SignedTx, WalletOpsError> {
+    let raw_tx: Vec<u8> = sign_batch_tx(wallet_name, payouts, fee_rate, coin_control, layout, settings)?;
+    let signed_tx: Transaction = bitcoin::consensus::deserialize(&raw_tx)
+        .map_err(|e| WalletOpsError::Other(e.to_string()))?;
+    Ok(SignedTx {
+        txid: signed_tx.txid(),
+        hex: hex::encode(&raw_tx),
+    })
 }
 
-pub fn send_btc(wallet_name: &str, recipient: &Address, amount: Amount, settings: &Settings) -> Result<(), WalletOpsError> {
+/// Let the wallet fund and sign a payment, and broadcast it unless `options` turn `add_to_wallet` off.
+pub fn send_btc(wallet_name: &str, recipient: &Address, amount: Amount, options: &SendOptions, settings: &Settings) -> Result<SentTx, WalletOpsError> {
     let wallet: Wallet = Wallet::new(wallet_name, settings)?;
-    wallet.send(recipient, amount)?;
-    Ok(())
+    let sent = wallet.send(recipient, amount, options)?;
+    let txid = sent.tx.txid();
+    let paid = sent.tx.output[sent.vout as usize].value;
+    match sent.broadcast {
+        true => info!("Sent {} BTC to {} in {}, fee {} BTC", paid.to_btc(), recipient, txid, sent.fee.to_btc()),
+        false => info!("Signed {} BTC to {} in {}, fee {} BTC, not broadcast", paid.to_btc(), recipient, txid, sent.fee.to_btc()),
+    }
+    Ok(SentTx {
+        txid,
+        vout: sent.vout,
+        amount: paid,
+        fee: sent.fee,
+        broadcast: sent.broadcast,
+        hex: (!sent.broadcast).then(|| serialize(&sent.tx).raw_hex()),
+    })
 }
 
-pub fn create_psbt(wallet_name: &str, recipient: &Address, amount: Amount, fee_amount: Amount, utxo_strat: UTXOStrategy, settings: &Settings) -> Result<(), WalletOpsError> {
+/// Create an unsigned PSBT from a watch-only (multisig) wallet paying `amount` to `recipient` at `fee_rate`.
+/// The inputs are locked so no other selection picks them before the PSBT is broadcast.
+pub fn create_psbt(wallet_name: &str, recipient: &Address, amount: Amount, fee_rate: FeeRate, coin_control: &CoinControl, layout: &TxLayout, settings: &Settings) -> Result<WalletCreateFundedPsbtResult, WalletOpsError> {
+    create_batch_psbt(wallet_name, &[Payout::new(recipient.clone(), amount)], fee_rate, coin_control, layout, settings)
+}
+
+/// Create one unsigned PSBT from a watch-only (multisig) wallet paying every payout at `fee_rate`,
+/// with a single change output. Labelled payout addresses are added to the wallet's address book.
+pub fn create_batch_psbt(wallet_name: &str, payouts: &[Payout], fee_rate: FeeRate, coin_control: &CoinControl, layout: &TxLayout, settings: &Settings) -> Result<WalletCreateFundedPsbtResult, WalletOpsError> {
     let wallet: Wallet = Wallet::new(wallet_name, settings)?;
 
     // Ensure the wallet is a multisig wallet
@@ -226,58 +540,321 @@ pub fn create_psbt(wallet_name: &str, recipient: &Address, amount: Amount, fee_a
         return Err(WalletOpsError::NotMultisigWallet);
     }
 
-    let bal = wallet.get_balances()?;
-    if bal.mine.trusted.to_sat() < amount.to_sat() {
-        return Err(WalletOpsError::InsufficientBalance);
-    }
+    let change_address = change_address(&wallet, payouts, layout)?;
+    let selection = select_coins(&wallet, payouts, &change_address, fee_rate, coin_control, settings)?;
+
+    with_reserved_coins(&wallet, &selection, || {
+        // signal BIP125 so the transaction can be fee bumped
+        let tx = unsigned_tx(&selection, payouts, &change_address, Sequence::ENABLE_RBF_NO_LOCKTIME, layout);
+        let change_position = selection.change
+            .and_then(|_| {
+                tx.output.iter()
+                    .position(|output| output.script_pubkey == change_address.script_pubkey())
+            })
+            .map_or(-1, |position| position as i32);
+
+        // The inputs and fee are already settled, so only ask the wallet to add the UTXO,
+        // script and BIP32 derivation data cosigners need to sign
+        let psbt = Psbt::from_unsigned_tx(tx).map_err(|e| WalletOpsError::InvalidPsbt(e.to_string()))?;
+        let updated = wallet.update_psbt(&psbt.to_string())?;
+
+        info!("Created PSBT with fee {} BTC", selection.fee.to_btc());
+        label_payouts(&wallet, payouts)?;
+
+        Ok(WalletCreateFundedPsbtResult {
+            psbt: updated.psbt,
+            fee: selection.fee,
+            change_position,
+        })
+    })
+}
 
-    let unspent_txs: Vec<ListUnspentResultEntry> = wallet.list_all_unspent(None)?;
-    if unspent_txs.is_empty() {
+/// Choose the inputs of a payment to every payout at `fee_rate`: run a strategy over the wallet's
+/// UTXOs, or spend exactly the outpoints named. Change is priced as an output to `change_address`,
+/// spent later as its descriptor says.
+fn select_coins(wallet: &Wallet, payouts: &[Payout], change_address: &Address, fee_rate: FeeRate, coin_control: &CoinControl, settings: &Settings) -> Result<CoinSelection, WalletOpsError> {
+    let amount: Amount = payouts.iter().map(|payout| payout.amount).sum();
+    let candidates = match coin_control {
+        CoinControl::Strategy(_) => {
+            if wallet.get_balances()?.mine.trusted < amount {
+                return Err(WalletOpsError::InsufficientBalance);
+            }
+            wallet.list_all_unspent(None)?
+        }
+        CoinControl::Manual(outpoints) => wallet_utxos(wallet, outpoints, settings)?,
+    };
+    if candidates.is_empty() {
         return Err(WalletOpsError::NoUnspentTransactions);
     }
 
-    // Based on the strategy, select UTXOs
-    let selected_utxos = strat_handler(&unspent_txs, amount, fee_amount, utxo_strat)
-        .map_err(|e| WalletOpsError::Other(e.to_string()))?;
+    let change_script = change_address.script_pubkey();
+    let change_spend_weight = spend_weight(&change_script, wallet.address_descriptor(change_address)?.as_deref());
+    let payment_scripts: Vec<ScriptBuf> = payouts.iter().map(|payout| payout.address.script_pubkey()).collect();
+    let params = SelectionParams::new(fee_rate, &payment_scripts, &change_script, change_spend_weight);
+    let selection = match coin_control {
+        CoinControl::Strategy(utxo_strat) => strat_handler(&candidates, amount, &params, *utxo_strat)?,
+        CoinControl::Manual(_) => select_utxos_manual(&candidates, amount, &params)?,
+    };
+    info!(
+        "Selected {} UTXOs, fee {} sat, change {} sat, waste {} sat",
+        selection.utxos.len(),
+        selection.fee.to_sat(),
+        selection.change.map_or(0, |change| change.to_sat()),
+        selection.waste.to_sat(),
+    );
+    Ok(selection)
+}
+
+/// A new wallet change address: of the configured type, or else of the payments' type when
+/// they all share one. Without a configured type, falls back to bech32 when the wallet cannot
+/// make the payments' type, e.g. a multisig wallet with a single descriptor.
+///
+/// The address is taken before coin selection to price the change output, so it stays unused
+/// when the selection needs no change.
+fn change_address(wallet: &Wallet, payouts: &[Payout], layout: &TxLayout) -> Result<Address, WalletOpsError> {
+    if let Some(change_type) = layout.change_type {
+        return Ok(wallet.new_change_address(&change_type)?);
+    }
 
-    let mut tx_inputs: Vec<CreateRawTransactionInput> = Vec::new();
-    let mut total_amount = Amount::from_sat(0);
-    for utxo in &selected_utxos {
-        tx_inputs.push(CreateRawTransactionInput {
-            txid: utxo.txid,
-            vout: utxo.vout,
-            sequence: None,
+    let mut payout_types = payouts.iter().map(|payout| wallet_address_type(&payout.address));
+    let matching = match payout_types.next().flatten() {
+        Some(first) if payout_types.all(|payout_type| payout_type == Some(first)) => first,
+        _ => AddressType::Bech32,
+    };
+    match wallet.new_change_address(&matching) {
+        Ok(address) => Ok(address),
+        Err(err) if matching != AddressType::Bech32 => {
+            info!("Cannot match the payments' address type ({}), using bech32 change", err);
+            Ok(wallet.new_change_address(&AddressType::Bech32)?)
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// The transaction spending `selection` to the payouts and, if there is any, the change,
+/// ordered as `layout` says.
+fn unsigned_tx(selection: &CoinSelection, payouts: &[Payout], change_address: &Address, sequence: Sequence, layout: &TxLayout) -> Transaction {
+    let input = selection.utxos.iter()
+        .map(|utxo| TxIn {
+            previous_output: OutPoint::new(utxo.txid, utxo.vout),
+            script_sig: ScriptBuf::new(),
+            sequence,
+            witness: Witness::default(),
+        })
+        .collect();
+
+    let mut output: Vec<TxOut> = payouts.iter()
+        .map(|payout| TxOut {
+            value: payout.amount,
+            script_pubkey: payout.address.script_pubkey(),
+        })
+        .collect();
+    if let Some(change_amount) = selection.change {
+        output.push(TxOut {
+            value: change_amount,
+            script_pubkey: change_address.script_pubkey(),
         });
-        total_amount += utxo.amount;
     }
 
-    let mut tx_outputs: HashMap<String, Amount> = HashMap::new();
-    tx_outputs.insert(recipient.to_string(), amount);
+    let mut tx = Transaction {
+        version: Version::TWO,
+        lock_time: LockTime::ZERO,
+        input,
+        output,
+    };
+    layout.ordering.apply(&mut tx);
+    if let TxOrdering::Shuffle(seed) = layout.ordering {
+        info!("Shuffled outputs with seed {}", seed);
+    }
+    tx
+}
 
-    // Add change output if there's any remaining amount
-    let change_amount = total_amount - amount - fee_amount;
-    if change_amount.to_sat() > 0 {
-        let change_address = wallet.new_address(&AddressType::Bech32)?;
-        tx_outputs.insert(change_address.to_string(), change_amount);
+fn label_payouts(wallet: &Wallet, payouts: &[Payout]) -> Result<(), WalletOpsError> {
+    for payout in payouts {
+        if let Some(label) = &payout.label {
+            wallet.set_label(&payout.address, label)?;
+        }
     }
+    Ok(())
+}
 
-    let locktime = None;
-    // TODO: can optionally specify the fee rate here, otherwise it will have the wallet estimate it
-    let options = None;
-    let bip32derivs = None;
-    let client = create_rpc_client(settings, Some(wallet_name))?;
-    let psbt: WalletCreateFundedPsbtResult = client
-        .wallet_create_funded_psbt(&tx_inputs[..], &tx_outputs, locktime, options, bip32derivs)?;
+/// Lock the inputs of `selection` as soon as they are chosen, then run `build`. If another run
+/// locked one of them first, the node refuses and this selection fails instead of double
+/// spending. The locks are released again if `build` fails.
+fn with_reserved_coins<T>(wallet: &Wallet, selection: &CoinSelection, build: impl FnOnce() -> Result<T, WalletOpsError>) -> Result<T, WalletOpsError> {
+    let outpoints: Vec<OutPoint> = selection.utxos.iter()
+        .map(|utxo| OutPoint::new(utxo.txid, utxo.vout))
+        .collect();
+    wallet.lock_unspent(&outpoints, false)?;
+    info!("Locked {} UTXOs until broadcast", outpoints.len());
+
+    let built = build();
+    if built.is_err() {
+        if let Err(err) = release_coins(wallet, &outpoints) {
+            warn!("Could not release the locks on the selected UTXOs: {}", err);
+        }
+    }
+    built
+}
 
-    info!("PSBT: {:#?}", psbt);
+/// Drop the locks still held on `outpoints`, e.g. once the transaction spending them is out,
+/// returning the ones released.
+fn release_coins(wallet: &Wallet, outpoints: &[OutPoint]) -> Result<Vec<OutPoint>, WalletOpsError> {
+    let locked: Vec<OutPoint> = wallet.list_locked_unspent()?.into_iter()
+        .filter(|outpoint| outpoints.contains(outpoint))
+        .collect();
+    if !locked.is_empty() {
+        wallet.unlock_unspent(&locked)?;
+        info!("Released {} UTXO locks", locked.len());
+    }
+    Ok(locked)
+}
 
-    Ok(())
+/// Look up outpoints the user chose to spend, checking each is unlocked, unspent according to
+/// `gettxout` (mempool spends included) and pays to the wallet. Coinbase outputs must have matured.
+fn wallet_utxos(wallet: &Wallet, outpoints: &[OutPoint], settings: &Settings) -> Result<Vec<ListUnspentResultEntry>, WalletOpsError> {
+    // listunspent knows the descriptors, which price multisig inputs more accurately
+    let listed: Vec<ListUnspentResultEntry> = wallet.client()
+        .list_unspent(Some(0), None, None, Some(true), None)?;
+
+    let locked: Vec<OutPoint> = wallet.list_locked_unspent()?;
+
+    let mut utxos: Vec<ListUnspentResultEntry> = Vec::with_capacity(outpoints.len());
+    for outpoint in outpoints {
+        if utxos.iter().any(|utxo| utxo.txid == outpoint.txid && utxo.vout == outpoint.vout) {
+            return Err(WalletOpsError::DuplicateUtxo(*outpoint));
+        }
+        if locked.contains(outpoint) {
+            return Err(WalletOpsError::UtxoLocked(*outpoint));
+        }
+
+        let tx_out = match get_tx_out(&outpoint.txid.to_string(), outpoint.vout, None, settings) {
+            Ok(tx_out) => tx_out,
+            Err(BitcoindError::TxOutNotFound) => return Err(WalletOpsError::UtxoUnavailable(*outpoint)),
+            Err(err) => return Err(err.into()),
+        };
+        if tx_out.coinbase && tx_out.confirmations < COINBASE_MATURITY {
+            return Err(WalletOpsError::ImmatureCoinbase(*outpoint, tx_out.confirmations));
+        }
+
+        let script_pub_key = ScriptBuf::from_bytes(tx_out.script_pub_key.hex);
+        let address = Address::from_script(&script_pub_key, settings.network)
+            .map_err(|_| WalletOpsError::NotWalletUtxo(*outpoint))?;
+        let address_info = wallet.get_address_info(&address)?;
+        if !address_info.is_mine.unwrap_or(false) && !address_info.is_watchonly.unwrap_or(false) {
+            return Err(WalletOpsError::NotWalletUtxo(*outpoint));
+        }
+
+        let descriptor = listed.iter()
+            .find(|utxo| utxo.txid == outpoint.txid && utxo.vout == outpoint.vout)
+            .and_then(|utxo| utxo.descriptor.clone());
+        utxos.push(ListUnspentResultEntry {
+            txid: outpoint.txid,
+            vout: outpoint.vout,
+            address: Some(address.as_unchecked().clone()),
+            label: None,
+            redeem_script: None,
+            witness_script: None,
+            script_pub_key,
+            amount: tx_out.value,
+            confirmations: tx_out.confirmations,
+            spendable: true,
+            solvable: true,
+            descriptor,
+            safe: true,
+        });
+    }
+
+    info!("Spending {} chosen UTXOs", utxos.len());
+    Ok(utxos)
 }
 
-pub fn process_psbt(wallet_name: &str, psbt: &str, settings: &Settings) -> Result<(), WalletOpsError> {
+/// Sign a PSBT with the named wallet's keys.
+pub fn process_psbt(wallet_name: &str, psbt: &str, settings: &Settings) -> Result<WalletProcessPsbtResult, WalletOpsError> {
     let wallet: Wallet = Wallet::new(wallet_name, settings)?;
     let signed_psbt = wallet.process_psbt(psbt)?;
-    info!("Signed PSBT: {:#?}", signed_psbt);
+    info!("Processed PSBT with wallet {}, complete: {}", wallet_name, signed_psbt.complete);
 
-    Ok(())
+    Ok(signed_psbt)
+}
+
+/// Run a whole multisig spending round: create a PSBT from `wallet_name`, have each of
+/// `cosigners` sign it in turn until it has every signature it needs, then finalize and broadcast.
+/// Cosigners after the one completing the PSBT are not asked to sign.
+#[allow(clippy::too_many_arguments)]
+pub fn multisig_spend(wallet_name: &str, cosigners: &[String], recipient: &Address, amount: Amount, fee_rate: FeeRate, coin_control: &CoinControl, layout: &TxLayout, settings: &Settings) -> Result<MultisigSpend, WalletOpsError> {
+    if cosigners.is_empty() {
+        return Err(WalletOpsError::NoCosigners);
+    }
+    validate_unique_cosigners(cosigners)?;
+
+    let funded = create_psbt(wallet_name, recipient, amount, fee_rate, coin_control, layout, settings)?;
+    let unsigned = Psbt::from_str(&funded.psbt).map_err(|e| WalletOpsError::InvalidPsbt(e.to_string()))?;
+
+    // the inputs stay locked only while the round runs, whether it broadcasts or fails
+    let round = collect_signatures_and_broadcast(&funded.psbt, &unsigned, cosigners, settings);
+    let spent: Vec<OutPoint> = unsigned.unsigned_tx.input.iter().map(|input| input.previous_output).collect();
+    release_coins(&Wallet::new(wallet_name, settings)?, &spent)?;
+    let (combined, steps, finalized) = round?;
+
+    Ok(MultisigSpend {
+        psbt: combined,
+        fee: funded.fee,
+        steps,
+        txid: finalized.txid,
+        hex: finalized.hex,
+    })
+}
+
+/// Have each cosigner sign `psbt` in turn until it is complete, then finalize and broadcast it.
+fn collect_signatures_and_broadcast(psbt: &str, unsigned: &Psbt, cosigners: &[String], settings: &Settings) -> Result<(String, Vec<SigningStep>, FinalizedTx), WalletOpsError> {
+    let mut signed_psbts: Vec<String> = Vec::new();
+    let mut steps: Vec<SigningStep> = Vec::new();
+    let mut combined = psbt.to_string();
+    let mut complete = false;
+
+    for cosigner in cosigners {
+        let processed = process_psbt(cosigner, psbt, settings)?;
+        let signed = Psbt::from_str(&processed.psbt).map_err(|e| WalletOpsError::InvalidPsbt(e.to_string()))?;
+        signed_psbts.push(processed.psbt);
+
+        combined = combine_psbts(&signed_psbts, settings)?.psbt;
+        let next_role = analyze_psbt(&combined, settings.network).map_err(|e| WalletOpsError::InvalidPsbt(e.to_string()))?.next;
+        complete = next_role >= PsbtRole::Finalizer;
+
+        let signed_inputs = newly_signed_inputs(unsigned, &signed);
+        info!("Cosigner {} signed inputs {:?}, next role: {}", cosigner, signed_inputs, next_role);
+        steps.push(SigningStep {
+            cosigner: cosigner.clone(),
+            signed_inputs,
+            complete,
+            next_role,
+        });
+
+        if complete {
+            break;
+        }
+    }
+
+    if !complete {
+        return Err(WalletOpsError::SigningIncomplete(cosigners.len()));
+    }
+
+    let finalized = finalize_psbt_and_broadcast(&combined, settings)?;
+    Ok((combined, steps, finalized))
+}
+
+/// Indices of the inputs that gained a signature, or were finalized, between `before` and `after`.
+fn newly_signed_inputs(before: &Psbt, after: &Psbt) -> Vec<usize> {
+    let signatures = |input: &bitcoin::psbt::Input| input.partial_sigs.len() + input.tap_script_sigs.len();
+    let is_final = |input: &bitcoin::psbt::Input| input.final_script_sig.is_some() || input.final_script_witness.is_some();
+
+    before.inputs.iter()
+        .zip(after.inputs.iter())
+        .enumerate()
+        .filter(|(_, (before, after))| {
+            signatures(after) > signatures(before) || (is_final(after) && !is_final(before))
+        })
+        .map(|(index, _)| index)
+        .collect()
 }
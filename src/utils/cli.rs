@@ -1,52 +1,183 @@
 use std::path::PathBuf;
 use std::str::FromStr;
 
-use bitcoincore_rpc::json::AddressType;
-use bitcoin::{Amount, Address};
+use bitcoincore_rpc::json::{AddressType, EstimateMode};
+use bitcoin::address::NetworkUnchecked;
+use bitcoin::psbt::Psbt;
+use bitcoin::{Address, Amount, FeeRate, Network, OutPoint, Txid};
 use bitcoin::amount::Denomination::Bitcoin;
-use clap::Parser;
+use clap::{Args, Parser, Subcommand};
 
-use super::utils::UTXOStrategy;
+use crate::modules::errors::CliError;
+use crate::modules::fee_bump::BumpMethod;
+use crate::modules::multisig::WalletFileFormat;
+use crate::modules::simulation::AmountDistribution;
+use crate::modules::script_debug::Breakpoint;
+use crate::modules::verification::PrevoutSource;
+
+use super::output::OutputFormat;
+use super::utils::{CoinControl, MultisigScriptType, TxLayout, TxOrdering, UTXOStrategy};
 
 #[derive(Parser)]
 pub struct Cli {
     #[arg(long, default_value = "settings.toml")]
     pub settings_file: PathBuf,
 
+    /// Named profile from the settings file, e.g. `alice` for `[profiles.alice]`
+    #[arg(long)]
+    pub profile: Option<String>,
+
+    /// Output format for command results: json, yaml, text, table or csv
+    #[arg(long, value_parser = parse_output_format, default_value = "text")]
+    pub output: OutputFormat,
+
+    #[command(subcommand)]
+    pub action: Action,
+}
+
+#[derive(Subcommand)]
+pub enum Action {
+    GetBlockHeight,
+    NewWallet(WalletArgs),
+    GetWalletInfo(WalletArgs),
+    ListDescriptors(WalletArgs),
+    NewMultisig(NewMultisigArgs),
+    NewMultisigFromXpubs(NewMultisigFromXpubsArgs),
+    ExportMultisig(ExportMultisigArgs),
+    ImportMultisig(ImportMultisigArgs),
+    GetNewAddress(GetNewAddressArgs),
+    GetAddressInfo(GetAddressInfoArgs),
+    DeriveAddresses(DeriveAddressesArgs),
+    RescanBlockchain,
+    GetBalance(WalletArgs),
+    GetSpendableBalance(AddressArgs),
+    MineBlocks(MineBlocksArgs),
+    ListUnspent(WalletArgs),
+    LockUtxos(LockUtxosArgs),
+    UnlockUtxos(UnlockUtxosArgs),
+    ListLockedUtxos(WalletArgs),
+    GetTx(TxidArgs),
+    GetTxOut(GetTxOutArgs),
+    SignTx(SpendArgs),
+    SignBatchTx(BatchSpendArgs),
+    DecodeRawTx(TxHexArgs),
+    BroadcastTx(BroadcastTxArgs),
+    CheckPolicy(CheckPolicyArgs),
+    SendBtc(SendBtcArgs),
+    CreatePsbt(SpendArgs),
+    CreateBatchPsbt(BatchSpendArgs),
+    MultisigSpend(MultisigSpendArgs),
+    BumpFee(BumpFeeArgs),
+    Cpfp(CpfpArgs),
+    DecodePsbt(PsbtArgs),
+    AnalyzePsbt(PsbtArgs),
+    WalletProcessPsbt(ProcessPsbtArgs),
+    CombinePsbts(CombinePsbtsArgs),
+    FinalizePsbt(PsbtArgs),
+    FinalizePsbtAndBroadcast(PsbtArgs),
+    VerifySignedTx(VerifySignedTxArgs),
+    DebugScript(DebugScriptArgs),
+    SimulateCoinSelection(SimulateCoinSelectionArgs),
+    GenerateRpcAuth(GenerateRpcAuthArgs),
+}
+
+#[derive(Args)]
+pub struct WalletArgs {
     /// Name of the wallet
-    #[arg(short='w', long, default_value = "default_wallet")]
+    #[arg(short='w', long)]
     pub wallet_name: String,
+}
+
+#[derive(Args)]
+pub struct NewMultisigArgs {
+    /// Required number of signatures for multisig
+    #[arg(short='n', long)]
+    pub nrequired: u32,
+
+    /// List of cosigner wallet names
+    #[arg(short='v', long, value_delimiter = ',', required = true)]
+    pub wallet_names: Vec<String>,
 
     /// Name of the multisig wallet
-    #[arg(short='m', long, default_value = "multisig_wallet")]
+    #[arg(short='m', long)]
     pub multisig_name: String,
 
-    /// list of wallet names
-    #[arg(short='v', long, value_delimiter = ',', default_value = "default_wallet1,default_wallet2,default_wallet3")]
-    pub wallet_names: Vec<String>,
+    /// Multisig script type: wsh, sh-wsh, sh or tr
+    #[arg(short='k', long, value_parser = parse_multisig_type, default_value = "wsh")]
+    pub script_type: MultisigScriptType,
+}
 
-    /// required number of signatures for multisig
-    #[arg(short='n', long, default_value = "2")]
+#[derive(Args)]
+pub struct NewMultisigFromXpubsArgs {
+    /// Required number of signatures for multisig
+    #[arg(short='n', long)]
     pub nrequired: u32,
 
+    /// Comma-separated cosigner keys, each `[fingerprint/path]xpub`
+    #[arg(long, value_delimiter = ',', required = true)]
+    pub xpubs: Vec<String>,
+
+    /// Name of the multisig wallet
+    #[arg(short='m', long)]
+    pub multisig_name: String,
+
+    /// Multisig script type: wsh, sh-wsh, sh or tr
+    #[arg(short='k', long, value_parser = parse_multisig_type, default_value = "wsh")]
+    pub script_type: MultisigScriptType,
+}
+
+#[derive(Args)]
+pub struct ExportMultisigArgs {
+    /// Name of the multisig wallet
+    #[arg(short='w', long)]
+    pub wallet_name: String,
+
+    /// Wallet file format: bsms or coldcard
+    #[arg(short='F', long, value_parser = parse_wallet_file_format, default_value = "bsms")]
+    pub format: WalletFileFormat,
+
+    /// File to write the wallet to
+    #[arg(short='f', long)]
+    pub file: PathBuf,
+}
+
+#[derive(Args)]
+pub struct ImportMultisigArgs {
+    /// BSMS record or Coldcard/Sparrow setup file
+    #[arg(short='f', long)]
+    pub file: PathBuf,
+
+    /// Name of the multisig wallet, defaults to the name in the file
+    #[arg(short='m', long)]
+    pub multisig_name: Option<String>,
+}
+
+#[derive(Args)]
+pub struct GetNewAddressArgs {
+    /// Name of the wallet
+    #[arg(short='w', long)]
+    pub wallet_name: String,
+
     /// Address type
     #[arg(short='z', long, value_parser = parse_address_type, default_value = "bech32")]
     pub address_type: AddressType,
+}
 
-    /// Number of blocks to mine
-    #[arg(short='b', long, default_value = "1")]
-    pub blocks: u64,
-
-    /// Transaction recipient address
-    #[arg(short='r', long, value_parser = string_to_address, default_value = "1F1tAaz5x1HUXrCNLbtMDqcw6o5GNn4xqX")] // dummy address, do not use
-    pub recipient: Address,
+#[derive(Args)]
+pub struct GetAddressInfoArgs {
+    /// Name of the wallet
+    #[arg(short='w', long)]
+    pub wallet_name: String,
 
     /// Wallet address
-    #[arg(short='a', long, value_parser = string_to_address, default_value = "1F1tAaz5x1HUXrCNLbtMDqcw6o5GNn4xqX")] // dummy address, do not use
-    pub address: Address,
+    #[arg(short='a', long, value_parser = string_to_address)]
+    pub address: Address<NetworkUnchecked>,
+}
 
+#[derive(Args)]
+pub struct DeriveAddressesArgs {
     /// Wallet descriptor
-    #[arg(short='d', long, default_value = "descriptor-here")]
+    #[arg(short='d', long)]
     pub descriptor: String,
 
     /// Start index to derive
@@ -56,91 +187,517 @@ pub struct Cli {
     /// End index to derive
     #[arg(short='e', long, default_value = "2")]
     pub end: u32,
+}
+
+#[derive(Args)]
+pub struct AddressArgs {
+    /// Bitcoin address
+    #[arg(short='a', long, value_parser = string_to_address)]
+    pub address: Address<NetworkUnchecked>,
+}
+
+#[derive(Args)]
+pub struct MineBlocksArgs {
+    /// Name of the wallet receiving the block rewards
+    #[arg(short='w', long)]
+    pub wallet_name: String,
+
+    /// Number of blocks to mine
+    #[arg(short='b', long, default_value = "1")]
+    pub blocks: u64,
+}
+
+#[derive(Args)]
+pub struct LockUtxosArgs {
+    /// Name of the wallet
+    #[arg(short='w', long)]
+    pub wallet_name: String,
+
+    /// Comma-separated `txid:vout` outpoints to lock
+    #[arg(long, value_delimiter = ',', value_parser = parse_outpoint, required = true)]
+    pub utxos: Vec<OutPoint>,
 
-    /// Transaction amount
-    #[arg(short='x', long, value_parser = parse_amount, default_value = "49.9")]
+    /// Keep the locks in the wallet database so they survive a node restart
+    #[arg(long)]
+    pub persistent: bool,
+}
+
+#[derive(Args)]
+pub struct UnlockUtxosArgs {
+    /// Name of the wallet
+    #[arg(short='w', long)]
+    pub wallet_name: String,
+
+    /// Comma-separated `txid:vout` outpoints to unlock, every locked UTXO if omitted
+    #[arg(long, value_delimiter = ',', value_parser = parse_outpoint)]
+    pub utxos: Vec<OutPoint>,
+
+    /// Unlock the inputs of this raw transaction instead, e.g. one that will not be broadcast
+    #[arg(short='t', long, value_parser = parse_hex, conflicts_with = "utxos")]
+    pub tx_hex: Option<String>,
+}
+
+#[derive(Args)]
+pub struct TxidArgs {
+    /// Transaction ID
+    #[arg(short='i', long, value_parser = parse_txid)]
+    pub txid: Txid,
+}
+
+#[derive(Args)]
+pub struct GetTxOutArgs {
+    /// Transaction ID
+    #[arg(short='i', long, value_parser = parse_txid)]
+    pub txid: Txid,
+
+    /// Output index
+    #[arg(short='o', long)]
+    pub vout: u32,
+
+    /// Minimum number of confirmations
+    #[arg(short='c', long, default_value = "0")]
+    pub confirmations: u32,
+}
+
+#[derive(Args)]
+pub struct SpendArgs {
+    /// Name of the wallet funding the transaction
+    #[arg(short='w', long)]
+    pub wallet_name: String,
+
+    /// Transaction recipient address
+    #[arg(short='r', long, value_parser = string_to_address)]
+    pub recipient: Address<NetworkUnchecked>,
+
+    /// Transaction amount in BTC
+    #[arg(short='x', long, value_parser = parse_amount)]
     pub amount: Amount,
 
-    /// Transaction fee
-    #[arg(short='f', long, value_parser = parse_amount, default_value = "0.1")]
-    pub fee_amount: Amount,
+    /// Target fee rate in sat/vB
+    #[arg(short='f', long, value_parser = parse_fee_rate, default_value = "1")]
+    pub fee_rate: FeeRate,
 
-    /// Max transaction fee rate in sat/vB
-    #[arg(short='u', long, default_value = "0.1")]
-    pub max_fee_rate: f64,
+    #[command(flatten)]
+    pub coins: CoinControlArgs,
+
+    #[command(flatten)]
+    pub layout: LayoutArgs,
+}
+
+#[derive(Args)]
+pub struct BatchSpendArgs {
+    /// Name of the wallet funding the transaction
+    #[arg(short='w', long)]
+    pub wallet_name: String,
+
+    /// Payout file: CSV lines of `address,amount[,label]`, or a JSON array when it ends in .json
+    #[arg(short='p', long)]
+    pub payout_file: PathBuf,
 
+    /// Target fee rate in sat/vB
+    #[arg(short='f', long, value_parser = parse_fee_rate, default_value = "1")]
+    pub fee_rate: FeeRate,
+
+    #[command(flatten)]
+    pub coins: CoinControlArgs,
+
+    #[command(flatten)]
+    pub layout: LayoutArgs,
+}
+
+#[derive(Args)]
+pub struct CoinControlArgs {
     /// UTXO selection strategy
     #[arg(short='y', long, value_parser = parse_utxo_strategy, default_value = "fifo")]
     pub utxo_strat: UTXOStrategy,
 
-    /// Transaction ID
-    #[arg(short='i', long, default_value = "c36d0c020577c2703dc0e202d8f1ac2626d29d81c449f81079b60c6b07263166")] // dummy tx, do not use
-    pub txid: String,
+    /// Comma-separated `txid:vout` outpoints to spend, all of them, instead of running a strategy
+    #[arg(long, value_delimiter = ',', value_parser = parse_outpoint, conflicts_with = "utxo_strat")]
+    pub utxos: Vec<OutPoint>,
+}
+
+#[derive(Args)]
+pub struct LayoutArgs {
+    /// Shuffle the outputs with this seed instead of ordering inputs and outputs by BIP69
+    #[arg(long)]
+    pub shuffle_seed: Option<u64>,
+
+    /// Change address type: legacy, p2sh-segwit, bech32 or bech32m. Defaults to the recipients' type
+    #[arg(long, value_parser = parse_address_type)]
+    pub change_type: Option<AddressType>,
+}
+
+impl LayoutArgs {
+    pub fn layout(&self) -> TxLayout {
+        TxLayout {
+            ordering: self.shuffle_seed.map_or(TxOrdering::Bip69, TxOrdering::Shuffle),
+            change_type: self.change_type,
+        }
+    }
+}
+
+impl CoinControlArgs {
+    /// The outpoints given with `--utxos`, or the selection strategy when there are none.
+    pub fn coin_control(&self) -> CoinControl {
+        match self.utxos.is_empty() {
+            true => CoinControl::Strategy(self.utxo_strat),
+            false => CoinControl::Manual(self.utxos.clone()),
+        }
+    }
+}
+
+#[derive(Args)]
+pub struct MultisigSpendArgs {
+    #[command(flatten)]
+    pub spend: SpendArgs,
+
+    /// Comma-separated cosigner wallets, asked to sign in order until the PSBT is complete
+    #[arg(short='v', long, value_delimiter = ',', required = true)]
+    pub cosigners: Vec<String>,
+}
+
+#[derive(Args)]
+pub struct BumpFeeArgs {
+    /// Name of the wallet that sent the transaction
+    #[arg(short='w', long)]
+    pub wallet_name: String,
+
+    /// Transaction ID of the unconfirmed transaction to replace
+    #[arg(short='i', long, value_parser = parse_txid)]
+    pub txid: Txid,
+
+    /// New fee rate in sat/vB
+    #[arg(short='f', long, value_parser = parse_fee_rate)]
+    pub fee_rate: FeeRate,
+
+    /// How to build the replacement: node (bumpfee/psbtbumpfee) or change (lower the change output)
+    #[arg(long, value_parser = parse_bump_method, default_value = "node")]
+    pub method: BumpMethod,
+}
+
+#[derive(Args)]
+pub struct CpfpArgs {
+    /// Name of the wallet receiving an output of the parent, which signs the child
+    #[arg(short='w', long)]
+    pub wallet_name: String,
+
+    /// Transaction ID of the parent
+    #[arg(short='i', long, value_parser = parse_txid)]
+    pub txid: Txid,
+
+    /// Parent output to spend, the first one paying the wallet if omitted
+    #[arg(short='o', long)]
+    pub vout: Option<u32>,
+
+    /// Target fee rate of the whole package in sat/vB
+    #[arg(short='f', long, value_parser = parse_fee_rate)]
+    pub fee_rate: FeeRate,
 
-    /// Transaction hash
-    #[arg(short='t', long, default_value = "dcaf015d7d6fdfc8a7f38f1a17991aa9975bd93109db2d3756e1533b519d4fae")] // dummy tx, do not use
+    /// Send parent and child together with `submitpackage`
+    #[arg(long)]
+    pub submit_package: bool,
+}
+
+#[derive(Args)]
+pub struct SendBtcArgs {
+    /// Name of the wallet funding the transaction
+    #[arg(short='w', long)]
+    pub wallet_name: String,
+
+    /// Transaction recipient address
+    #[arg(short='r', long, value_parser = string_to_address)]
+    pub recipient: Address<NetworkUnchecked>,
+
+    /// Transaction amount in BTC
+    #[arg(short='x', long, value_parser = parse_amount)]
+    pub amount: Amount,
+
+    /// Fee rate in sat/vB, instead of estimating one
+    #[arg(short='f', long, value_parser = parse_fee_rate, conflicts_with_all = ["conf_target", "estimate_mode"])]
+    pub fee_rate: Option<FeeRate>,
+
+    /// Confirmation target in blocks for the fee estimate
+    #[arg(short='c', long)]
+    pub conf_target: Option<u16>,
+
+    /// Fee estimate mode: unset, economical or conservative
+    #[arg(short='e', long, value_parser = parse_estimate_mode, default_value = "unset")]
+    pub estimate_mode: EstimateMode,
+
+    /// Take the fee out of the amount instead of adding it on top
+    #[arg(long)]
+    pub subtract_fee: bool,
+
+    /// Signal BIP125 replaceability: true or false, by default as the node's -walletrbf says
+    #[arg(long)]
+    pub replaceable: Option<bool>,
+
+    /// Address receiving the change
+    #[arg(long, value_parser = string_to_address, conflicts_with = "change_type")]
+    pub change_address: Option<Address<NetworkUnchecked>>,
+
+    /// Change address type: legacy, p2sh-segwit, bech32 or bech32m
+    #[arg(long, value_parser = parse_address_type)]
+    pub change_type: Option<AddressType>,
+
+    /// Transaction locktime, a block height or unix time
+    #[arg(long)]
+    pub locktime: Option<u32>,
+
+    /// Return the signed transaction instead of adding it to the wallet and broadcasting it
+    #[arg(long)]
+    pub no_broadcast: bool,
+}
+
+#[derive(Args)]
+pub struct TxHexArgs {
+    /// Raw transaction hex
+    #[arg(short='t', long, value_parser = parse_hex)]
+    pub tx_hex: String,
+}
+
+#[derive(Args)]
+pub struct BroadcastTxArgs {
+    /// Raw transaction hex
+    #[arg(short='t', long, value_parser = parse_hex)]
+    pub tx_hex: String,
+
+    /// Max transaction fee rate in sat/vB, 0 disables the check
+    #[arg(short='u', long, default_value = "0.1")]
+    pub max_fee_rate: f64,
+
+    /// Check mempool policy first and only broadcast if the node would accept the transaction
+    #[arg(long)]
+    pub preflight: bool,
+
+    /// Wallet that signed the transaction, whose locks on its inputs are released whether the
+    /// node accepts it or not
+    #[arg(short='w', long)]
+    pub wallet_name: Option<String>,
+}
+
+#[derive(Args)]
+pub struct CheckPolicyArgs {
+    /// Raw transaction hex
+    #[arg(short='t', long, value_parser = parse_hex)]
+    pub tx_hex: String,
+
+    /// Max transaction fee rate in sat/vB, 0 disables the check
+    #[arg(short='u', long, default_value = "0")]
+    pub max_fee_rate: f64,
+
+    /// Print only the issues found, one row per issue
+    #[arg(long)]
+    pub issues: bool,
+}
+
+#[derive(Args)]
+pub struct PrevoutArgs {
+    /// Work offline, taking the spent outputs from this base64 PSBT of the transaction
+    #[arg(short='p', long, value_parser = parse_psbt, conflicts_with_all = ["prevouts_file", "parent_txs"])]
+    pub psbt: Option<String>,
+
+    /// Work offline, taking the spent outputs from a JSON array of {txid, vout, scriptPubKey, amount}
+    #[arg(long, conflicts_with = "parent_txs")]
+    pub prevouts_file: Option<PathBuf>,
+
+    /// Work offline, taking the spent outputs from these comma-separated raw parent transactions
+    #[arg(long, value_delimiter = ',', value_parser = parse_hex)]
+    pub parent_txs: Vec<String>,
+}
+
+impl PrevoutArgs {
+    /// Where to find the spent outputs offline, or `None` to ask the node.
+    pub fn prevout_source(&self) -> Option<PrevoutSource> {
+        if let Some(psbt) = &self.psbt {
+            return Some(PrevoutSource::Psbt(psbt.clone()));
+        }
+        if let Some(path) = &self.prevouts_file {
+            return Some(PrevoutSource::File(path.clone()));
+        }
+        (!self.parent_txs.is_empty()).then(|| PrevoutSource::ParentTxs(self.parent_txs.clone()))
+    }
+}
+
+#[derive(Args)]
+pub struct VerifySignedTxArgs {
+    /// Raw transaction hex
+    #[arg(short='t', long, value_parser = parse_hex)]
+    pub tx_hex: String,
+
+    #[command(flatten)]
+    pub prevouts: PrevoutArgs,
+
+    /// Report every input under each consensus flag set instead of stopping at the first failure
+    #[arg(long)]
+    pub report: bool,
+}
+
+#[derive(Args)]
+pub struct DebugScriptArgs {
+    /// Raw signed transaction hex
+    #[arg(short='t', long, value_parser = parse_hex)]
     pub tx_hex: String,
 
-    /// PSBT hash
-    #[arg(short='p', long, default_value = "cHNidP8BAH0CAAAAAbleQkslv9ReG8S64ny+JbejMMyMKKNF2SOBOiqVAAAAD9///")] // dummy tx, do not use
+    /// Index of the input to run
+    #[arg(short='n', long, default_value = "0")]
+    pub input: usize,
+
+    #[command(flatten)]
+    pub prevouts: PrevoutArgs,
+
+    /// Comma-separated step numbers or opcode names (e.g. 4,OP_CHECKSIG) to halt after
+    #[arg(long = "break", value_delimiter = ',', value_parser = parse_breakpoint)]
+    pub breakpoints: Vec<Breakpoint>,
+
+    /// Print only the executed steps, one row per opcode
+    #[arg(long)]
+    pub trace: bool,
+}
+
+#[derive(Args)]
+pub struct PsbtArgs {
+    /// Base64 encoded PSBT
+    #[arg(short='p', long, value_parser = parse_psbt)]
     pub psbt_hex: String,
+}
 
-    /// Multiple PSBTs
-    #[arg(short='l', long, value_delimiter = ',', default_value = "cHNidP8BAH0CAAAAAbAip9TqQ,cHNidP8BAH0CAAAAAbAip9TqQ")]
+#[derive(Args)]
+pub struct ProcessPsbtArgs {
+    /// Name of the signing wallet
+    #[arg(short='w', long)]
+    pub wallet_name: String,
+
+    /// Base64 encoded PSBT
+    #[arg(short='p', long, value_parser = parse_psbt)]
+    pub psbt_hex: String,
+}
+
+#[derive(Args)]
+pub struct CombinePsbtsArgs {
+    /// Base64 encoded PSBTs to combine
+    #[arg(short='l', long, value_delimiter = ',', value_parser = parse_psbt, required = true)]
     pub psbts: Vec<String>,
+}
 
-    /// Vout
-    #[arg(short='o', long, default_value = "0")]
-    pub vout: u32,
+#[derive(Args)]
+pub struct SimulateCoinSelectionArgs {
+    /// JSON written by `list-unspent --output json`, used instead of a synthetic pool
+    #[arg(long)]
+    pub pool_file: Option<PathBuf>,
 
-    /// Transaction confirmations
-    #[arg(short='c', long, default_value = "0")]
-    pub confirmations: u32,
+    /// Number of UTXOs in the synthetic pool
+    #[arg(short='n', long, default_value = "100")]
+    pub pool_size: usize,
 
-    #[command(subcommand)]
-    pub action: Action,
+    /// Distribution of the synthetic UTXO amounts: uniform, exponential or bimodal
+    #[arg(long, value_parser = parse_amount_distribution, default_value = "exponential")]
+    pub pool_distribution: AmountDistribution,
+
+    /// Smallest synthetic UTXO in BTC
+    #[arg(long, value_parser = parse_amount, default_value = "0.0001")]
+    pub pool_min: Amount,
+
+    /// Largest synthetic UTXO in BTC
+    #[arg(long, value_parser = parse_amount, default_value = "1")]
+    pub pool_max: Amount,
+
+    /// Number of payments to fund, one after the other
+    #[arg(short='p', long, default_value = "50")]
+    pub payments: usize,
+
+    /// Distribution of the payment targets: uniform, exponential or bimodal
+    #[arg(long, value_parser = parse_amount_distribution, default_value = "uniform")]
+    pub payment_distribution: AmountDistribution,
+
+    /// Smallest payment in BTC
+    #[arg(long, value_parser = parse_amount, default_value = "0.001")]
+    pub payment_min: Amount,
+
+    /// Largest payment in BTC
+    #[arg(long, value_parser = parse_amount, default_value = "0.1")]
+    pub payment_max: Amount,
+
+    /// Target fee rate in sat/vB
+    #[arg(short='f', long, value_parser = parse_fee_rate, default_value = "5")]
+    pub fee_rate: FeeRate,
+
+    /// Comma-separated UTXO selection strategies to compare, all of them if omitted
+    #[arg(short='y', long, value_delimiter = ',', value_parser = parse_utxo_strategy)]
+    pub strategies: Vec<UTXOStrategy>,
+
+    /// Seed for the synthetic pool, payment targets and randomized strategies, random if omitted
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// Report every payment instead of a summary per strategy
+    #[arg(long)]
+    pub steps: bool,
 }
 
-#[derive(Parser)]
-pub enum Action {
-    GetBlockHeight,
-    NewWallet,
-    GetWalletInfo,
-    ListDescriptors,
-    NewMultisig,
-    GetNewAddress,
-    GetAddressInfo,
-    DeriveAddresses,
-    RescanBlockchain,
-    GetBalance,
-    GetSpendableBalance,
-    MineBlocks,
-    ListUnspent,
-    GetTx,
-    GetTxOut,
-    SignTx,
-    DecodeRawTx,
-    BroadcastTx,
-    SendBtc,
-    CreatePsbt,
-    DecodePsbt,
-    AnalyzePsbt,
-    WalletProcessPsbt,
-    CombinePsbts,
-    FinalizePsbt,
-    FinalizePsbtAndBroadcast,
-    VerifySignedTx,
+#[derive(Args)]
+pub struct GenerateRpcAuthArgs {
+    /// Username for the generated rpcauth line
+    #[arg(long)]
+    pub rpc_username: String,
+
+    /// Password for the generated rpcauth line, a random one is generated if omitted
+    #[arg(long)]
+    pub rpc_password: Option<String>,
+}
+
+/// Check an address parsed from the command line belongs to the configured network.
+pub fn require_network(address: &Address<NetworkUnchecked>, network: Network) -> Result<Address, CliError> {
+    address.clone()
+        .require_network(network)
+        .map_err(|_| CliError::AddressNetworkMismatch(address.clone().assume_checked().to_string(), network))
 }
 
 fn parse_amount(s: &str) -> Result<Amount, &'static str> {
     Amount::from_str_in(s, Bitcoin).map_err(|_| "invalid amount")
 }
 
-fn string_to_address(addr_str: &str) -> Result<Address, &'static str> {
-    match Address::from_str(addr_str) {
-        Ok(address) => Ok(address.assume_checked()),
-        Err(_) => Err("Invalid address string"),
+fn parse_fee_rate(s: &str) -> Result<FeeRate, &'static str> {
+    let sat_per_vb: f64 = s.parse().map_err(|_| "Invalid fee rate")?;
+    if !sat_per_vb.is_finite() || sat_per_vb < 0.0 {
+        return Err("Fee rate must be a non-negative number of sat/vB");
     }
+    // 1 sat/vB is 250 sat per 1000 weight units
+    Ok(FeeRate::from_sat_per_kwu((sat_per_vb * 250.0).round() as u64))
+}
+
+fn string_to_address(addr_str: &str) -> Result<Address<NetworkUnchecked>, &'static str> {
+    Address::from_str(addr_str).map_err(|_| "Invalid address string")
+}
+
+fn parse_txid(s: &str) -> Result<Txid, &'static str> {
+    Txid::from_str(s).map_err(|_| "Invalid transaction ID")
+}
+
+fn parse_outpoint(s: &str) -> Result<OutPoint, &'static str> {
+    OutPoint::from_str(s).map_err(|_| "Invalid outpoint, expected txid:vout")
+}
+
+fn parse_hex(s: &str) -> Result<String, &'static str> {
+    hex::decode(s).map_err(|_| "Invalid hex string")?;
+    Ok(s.to_string())
+}
+
+fn parse_breakpoint(s: &str) -> Result<Breakpoint, &'static str> {
+    if let Ok(step) = s.parse::<usize>() {
+        return Ok(Breakpoint::Step(step));
+    }
+    let name = s.trim().to_uppercase();
+    match name.is_empty() {
+        true => Err("Breakpoint must be a step number or an opcode name"),
+        false if name.starts_with("OP_") => Ok(Breakpoint::Opcode(name)),
+        false => Ok(Breakpoint::Opcode(format!("OP_{}", name))),
+    }
+}
+
+fn parse_psbt(s: &str) -> Result<String, &'static str> {
+    Psbt::from_str(s).map_err(|_| "Invalid base64 PSBT")?;
+    Ok(s.to_string())
 }
 
 fn parse_address_type(s: &str) -> Result<AddressType, &'static str> {
@@ -153,12 +710,72 @@ fn parse_address_type(s: &str) -> Result<AddressType, &'static str> {
     }
 }
 
+fn parse_estimate_mode(s: &str) -> Result<EstimateMode, &'static str> {
+    match s {
+        "unset" => Ok(EstimateMode::Unset),
+        "economical" => Ok(EstimateMode::Economical),
+        "conservative" => Ok(EstimateMode::Conservative),
+        _ => Err("Unknown estimate mode"),
+    }
+}
+
+fn parse_multisig_type(s: &str) -> Result<MultisigScriptType, &'static str> {
+    match s {
+        "wsh" => Ok(MultisigScriptType::Wsh),
+        "sh-wsh" => Ok(MultisigScriptType::ShWsh),
+        "sh" => Ok(MultisigScriptType::Sh),
+        "tr" => Ok(MultisigScriptType::Tr),
+        _ => Err("Unknown multisig script type"),
+    }
+}
+
+fn parse_wallet_file_format(s: &str) -> Result<WalletFileFormat, &'static str> {
+    match s {
+        "bsms" => Ok(WalletFileFormat::Bsms),
+        "coldcard" => Ok(WalletFileFormat::Coldcard),
+        _ => Err("Unknown wallet file format"),
+    }
+}
+
+fn parse_amount_distribution(s: &str) -> Result<AmountDistribution, &'static str> {
+    match s {
+        "uniform" => Ok(AmountDistribution::Uniform),
+        "exponential" => Ok(AmountDistribution::Exponential),
+        "bimodal" => Ok(AmountDistribution::Bimodal),
+        _ => Err("Unknown amount distribution"),
+    }
+}
+
+fn parse_bump_method(s: &str) -> Result<BumpMethod, &'static str> {
+    match s {
+        "node" => Ok(BumpMethod::Node),
+        "change" => Ok(BumpMethod::Change),
+        _ => Err("Unknown fee bump method"),
+    }
+}
+
+fn parse_output_format(s: &str) -> Result<OutputFormat, &'static str> {
+    match s {
+        "json" => Ok(OutputFormat::Json),
+        "yaml" => Ok(OutputFormat::Yaml),
+        "text" => Ok(OutputFormat::Text),
+        "table" => Ok(OutputFormat::Table),
+        "csv" => Ok(OutputFormat::Csv),
+        _ => Err("Unknown output format"),
+    }
+}
+
 fn parse_utxo_strategy(s: &str) -> Result<UTXOStrategy, &'static str> {
     match s {
         "branch-and-bound" => Ok(UTXOStrategy::BranchAndBound),
-        "fifo" => Ok(UTXOStrategy::Fifo),
+        "fifo" | "oldest-first" => Ok(UTXOStrategy::Fifo),
+        "newest-first" => Ok(UTXOStrategy::NewestFirst),
         "largest-first" => Ok(UTXOStrategy::LargestFirst),
         "smallest-first" => Ok(UTXOStrategy::SmallestFirst),
+        "knapsack" => Ok(UTXOStrategy::Knapsack),
+        "single-random-draw" => Ok(UTXOStrategy::SingleRandomDraw),
+        "address-grouping" => Ok(UTXOStrategy::AddressGrouping),
+        "lowest-waste" => Ok(UTXOStrategy::LowestWaste),
         _ => Err("Unknown UTXO selection strategy"),
     }
 }
@@ -0,0 +1,303 @@
+use bitcoin::blockdata::opcodes::all::{OP_CHECKSIG, OP_CHECKSIGADD, OP_NUMEQUAL};
+use bitcoin::blockdata::opcodes::{Class, ClassifyContext};
+use bitcoin::psbt::Input;
+use bitcoin::script::Instruction;
+use bitcoin::{Amount, FeeRate, Script, Weight};
+use bitcoincore_rpc::json::ListUnspentResultEntry;
+
+/// Version, locktime, input and output counts, plus the segwit marker and flag.
+pub const TX_OVERHEAD_WEIGHT: Weight = Weight::from_wu(4 * (4 + 4 + 1 + 1) + 2);
+
+/// Outpoint, sequence and a one byte script length, all non-witness data.
+const TXIN_BASE_BYTES: u64 = 32 + 4 + 4 + 1;
+
+/// A DER encoded ECDSA signature with its sighash byte, at its largest.
+const ECDSA_SIG_BYTES: u64 = 72;
+
+/// A BIP340 signature with the default sighash.
+const SCHNORR_SIG_BYTES: u64 = 64;
+
+/// Estimated weight of spending `utxo`, worst case signature sizes included.
+///
+/// Multisig inputs are recognised from the output descriptor `listunspent` reports for
+/// descriptor wallets; everything else is inferred from the scriptPubKey.
+pub fn input_weight(utxo: &ListUnspentResultEntry) -> Weight {
+    spend_weight(&utxo.script_pub_key, utxo.descriptor.as_deref())
+}
+
+/// [`input_weight`] of an output paying to `script`, described by `descriptor` when known.
+pub fn spend_weight(script: &Script, descriptor: Option<&str>) -> Weight {
+    let descriptor = descriptor.unwrap_or_default();
+
+    let wu = match multisig_counts(descriptor) {
+        Some((m, n)) if descriptor.starts_with("sh(wsh(") => {
+            // scriptSig pushes the 34 byte P2WSH program
+            (TXIN_BASE_BYTES + 35) * 4 + multisig_witness_bytes(m, n)
+        }
+        Some((m, n)) if descriptor.starts_with("wsh(") => TXIN_BASE_BYTES * 4 + multisig_witness_bytes(m, n),
+        Some((m, n)) if descriptor.starts_with("sh(") => {
+            let redeem_script = multisig_script_bytes(n);
+            let script_sig = 1 + m * (1 + ECDSA_SIG_BYTES) + push_len(redeem_script) + redeem_script;
+            (TXIN_BASE_BYTES - 1 + varint_len(script_sig) + script_sig) * 4
+        }
+        Some((m, n)) if descriptor.starts_with("tr(") => {
+            // keys are x-only: <key> OP_CHECKSIG(ADD) per key, then <m> OP_NUMEQUAL
+            let leaf_script = 34 * n + push_num_len(m) + 1;
+            let control_block = 33;
+            let witness = varint_len(n + 2)
+                + m * (1 + SCHNORR_SIG_BYTES)
+                + (n - m)
+                + varint_len(leaf_script) + leaf_script
+                + 1 + control_block;
+            TXIN_BASE_BYTES * 4 + witness
+        }
+        _ if script.is_p2wpkh() => TXIN_BASE_BYTES * 4 + p2wpkh_witness_bytes(),
+        // assume nested P2WPKH, the only P2SH script a single-key wallet produces
+        _ if script.is_p2sh() => (TXIN_BASE_BYTES + 23) * 4 + p2wpkh_witness_bytes(),
+        _ if script.is_p2tr() => TXIN_BASE_BYTES * 4 + 1 + 1 + SCHNORR_SIG_BYTES,
+        // unknown witness script, price it like a single-key P2WPKH spend
+        _ if script.is_p2wsh() => TXIN_BASE_BYTES * 4 + p2wpkh_witness_bytes(),
+        // P2PKH: <sig> <33 byte pubkey>
+        _ => (TXIN_BASE_BYTES + 1 + ECDSA_SIG_BYTES + 1 + 33) * 4,
+    };
+
+    Weight::from_wu(wu)
+}
+
+/// Estimated weight of a PSBT input once finalized, worst case signature sizes included: from its
+/// final scripts when it has them, otherwise from the scripts the PSBT carries.
+/// `None` when the spend cannot be worked out, e.g. a P2WSH input without its witness script.
+pub fn psbt_input_weight(input: &Input, script_pubkey: &Script) -> Option<Weight> {
+    if input.final_script_sig.is_some() || input.final_script_witness.is_some() {
+        let script_sig = input.final_script_sig.as_ref().map_or(0, |script| script.len() as u64);
+        let witness = input.final_script_witness.as_ref().map_or(0, |witness| witness.size() as u64);
+        return Some(Weight::from_wu((TXIN_BASE_BYTES - 1 + varint_len(script_sig) + script_sig) * 4 + witness));
+    }
+
+    let p2wsh_witness = |witness_script: &Script| {
+        let (m, _) = script_multisig_counts(witness_script)?;
+        let len = witness_script.len() as u64;
+        Some(varint_len(m + 2) + 1 + m * (1 + ECDSA_SIG_BYTES) + varint_len(len) + len)
+    };
+
+    let wu = if script_pubkey.is_p2wpkh() {
+        TXIN_BASE_BYTES * 4 + p2wpkh_witness_bytes()
+    } else if script_pubkey.is_p2pkh() {
+        (TXIN_BASE_BYTES + 1 + ECDSA_SIG_BYTES + 1 + 33) * 4
+    } else if script_pubkey.is_p2wsh() {
+        TXIN_BASE_BYTES * 4 + p2wsh_witness(input.witness_script.as_ref()?)?
+    } else if script_pubkey.is_p2sh() {
+        let redeem_script = input.redeem_script.as_ref()?;
+        let nested = (TXIN_BASE_BYTES + 1 + redeem_script.len() as u64) * 4;
+        if redeem_script.is_p2wpkh() {
+            nested + p2wpkh_witness_bytes()
+        } else if redeem_script.is_p2wsh() {
+            nested + p2wsh_witness(input.witness_script.as_ref()?)?
+        } else {
+            let (m, _) = script_multisig_counts(redeem_script)?;
+            let len = redeem_script.len() as u64;
+            let script_sig = 1 + m * (1 + ECDSA_SIG_BYTES) + push_len(len) + len;
+            (TXIN_BASE_BYTES - 1 + varint_len(script_sig) + script_sig) * 4
+        }
+    } else if script_pubkey.is_p2tr() {
+        // the first leaf with a known threshold, or the key path
+        let leaf = input.tap_scripts.iter().find_map(|(control_block, (leaf_script, _))| {
+            script_multisig_counts(leaf_script).map(|counts| (control_block, leaf_script, counts))
+        });
+        match leaf {
+            Some((control_block, leaf_script, (m, n))) if input.tap_key_sig.is_none() => {
+                let leaf_len = leaf_script.len() as u64;
+                let control_len = control_block.size() as u64;
+                let witness = varint_len(n + 2)
+                    + m * (1 + SCHNORR_SIG_BYTES)
+                    + (n - m)
+                    + varint_len(leaf_len) + leaf_len
+                    + varint_len(control_len) + control_len;
+                TXIN_BASE_BYTES * 4 + witness
+            }
+            _ => TXIN_BASE_BYTES * 4 + 1 + 1 + SCHNORR_SIG_BYTES,
+        }
+    } else {
+        return None;
+    };
+
+    Some(Weight::from_wu(wu))
+}
+
+/// Threshold and key count of a script: `OP_CHECKMULTISIG` multisig, an `OP_CHECKSIGADD`
+/// tapscript multisig, or a single `<key> OP_CHECKSIG`.
+pub fn script_multisig_counts(script: &Script) -> Option<(u64, u64)> {
+    let instructions: Vec<Instruction> = script.instructions().collect::<Result<_, _>>().ok()?;
+    let push_num = |instruction: &Instruction| match instruction {
+        Instruction::Op(op) => match op.classify(ClassifyContext::Legacy) {
+            Class::PushNum(n) if n > 0 => Some(n as u64),
+            _ => None,
+        },
+        Instruction::PushBytes(data) if data.len() == 1 => Some(u64::from(data.as_bytes()[0])),
+        _ => None,
+    };
+
+    if script.is_multisig() {
+        let m = push_num(instructions.first()?)?;
+        let n = push_num(instructions.iter().rev().nth(1)?)?;
+        return Some((m, n));
+    }
+    match instructions.as_slice() {
+        [Instruction::PushBytes(key), Instruction::Op(OP_CHECKSIG)] if matches!(key.len(), 32 | 33 | 65) => Some((1, 1)),
+        [.., threshold, Instruction::Op(OP_NUMEQUAL)] if instructions.contains(&Instruction::Op(OP_CHECKSIGADD)) => {
+            let n = instructions.iter()
+                .filter(|instruction| matches!(instruction, Instruction::PushBytes(key) if key.len() == 32))
+                .count() as u64;
+            let m = push_num(threshold)?;
+            (m <= n).then_some((m, n))
+        }
+        _ => None,
+    }
+}
+
+/// Weight of an output paying to `script_pubkey`.
+pub fn output_weight(script_pubkey: &Script) -> Weight {
+    let len = script_pubkey.len() as u64;
+    Weight::from_wu((8 + varint_len(len) + len) * 4)
+}
+
+/// Fee for `weight` at `fee_rate`, rounded up to the next satoshi.
+pub fn fee_for_weight(fee_rate: FeeRate, weight: Weight) -> Amount {
+    let sats = (fee_rate.to_sat_per_kwu() * weight.to_wu()).div_ceil(1000);
+    Amount::from_sat(sats)
+}
+
+/// Threshold and key count of a `(sorted)multi(_a)` descriptor.
+fn multisig_counts(descriptor: &str) -> Option<(u64, u64)> {
+    let start = ["sortedmulti_a(", "multi_a(", "sortedmulti(", "multi("].iter()
+        .find_map(|fragment| descriptor.find(fragment).map(|pos| pos + fragment.len()))?;
+    let inner = &descriptor[start..];
+    let inner = &inner[..inner.find(')')?];
+
+    let mut parts = inner.split(',');
+    let m: u64 = parts.next()?.parse().ok()?;
+    let n = parts.count() as u64;
+    (m <= n).then_some((m, n))
+}
+
+/// `OP_m <n keys> OP_n OP_CHECKMULTISIG`
+fn multisig_script_bytes(n: u64) -> u64 {
+    1 + 34 * n + 1 + 1
+}
+
+/// Item count, the `OP_CHECKMULTISIG` dummy, the signatures and the witness script.
+fn multisig_witness_bytes(m: u64, n: u64) -> u64 {
+    let witness_script = multisig_script_bytes(n);
+    varint_len(m + 2) + 1 + m * (1 + ECDSA_SIG_BYTES) + varint_len(witness_script) + witness_script
+}
+
+fn p2wpkh_witness_bytes() -> u64 {
+    1 + (1 + ECDSA_SIG_BYTES) + (1 + 33)
+}
+
+fn varint_len(n: u64) -> u64 {
+    match n {
+        0..=0xfc => 1,
+        0xfd..=0xffff => 3,
+        0x10000..=0xffff_ffff => 5,
+        _ => 9,
+    }
+}
+
+/// Opcode bytes needed to push a data element of `n` bytes.
+fn push_len(n: u64) -> u64 {
+    match n {
+        0..=75 => 1,
+        76..=0xff => 2,
+        _ => 3,
+    }
+}
+
+/// Bytes of a minimal push of the number `n`, a single opcode up to 16.
+fn push_num_len(n: u64) -> u64 {
+    match n {
+        0..=16 => 1,
+        17..=0x7f => 2,
+        _ => 3,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::blockdata::opcodes::all::{OP_CHECKMULTISIG, OP_CHECKSIGADD};
+    use bitcoin::key::{PublicKey, XOnlyPublicKey};
+    use bitcoin::script::Builder;
+    use bitcoin::secp256k1::{Secp256k1, SecretKey};
+    use bitcoin::{ScriptBuf, Witness};
+
+    use super::*;
+
+    fn keys(n: u8) -> Vec<PublicKey> {
+        let secp = Secp256k1::new();
+        (1..=n).map(|i| PublicKey::new(SecretKey::from_slice(&[i; 32]).unwrap().public_key(&secp))).collect()
+    }
+
+    fn multisig(m: i64, keys: &[PublicKey]) -> ScriptBuf {
+        keys.iter()
+            .fold(Builder::new().push_int(m), |builder, key| builder.push_key(key))
+            .push_int(keys.len() as i64)
+            .push_opcode(OP_CHECKMULTISIG)
+            .into_script()
+    }
+
+    fn multi_a(m: i64, keys: &[PublicKey]) -> ScriptBuf {
+        keys.iter().enumerate()
+            .fold(Builder::new(), |builder, (i, key)| {
+                let opcode = if i == 0 { OP_CHECKSIG } else { OP_CHECKSIGADD };
+                builder.push_x_only_key(&XOnlyPublicKey::from(key.inner)).push_opcode(opcode)
+            })
+            .push_int(m)
+            .push_opcode(OP_NUMEQUAL)
+            .into_script()
+    }
+
+    #[test]
+    fn multisig_counts_from_scripts() {
+        let keys = keys(3);
+        assert_eq!(script_multisig_counts(&multisig(2, &keys)), Some((2, 3)));
+        assert_eq!(script_multisig_counts(&multisig(1, &keys[..1])), Some((1, 1)));
+        assert_eq!(script_multisig_counts(&multi_a(2, &keys)), Some((2, 3)));
+        assert_eq!(script_multisig_counts(&multi_a(3, &keys)), Some((3, 3)));
+        assert_eq!(script_multisig_counts(&Builder::new().push_key(&keys[0]).push_opcode(OP_CHECKSIG).into_script()), Some((1, 1)));
+
+        // more signatures than keys, and scripts that are no multisig at all
+        assert_eq!(script_multisig_counts(&multi_a(4, &keys)), None);
+        assert_eq!(script_multisig_counts(&ScriptBuf::new_p2wpkh(&keys[0].wpubkey_hash().unwrap())), None);
+        assert_eq!(script_multisig_counts(&ScriptBuf::new()), None);
+    }
+
+    #[test]
+    fn psbt_input_weights() {
+        let keys = keys(3);
+        let p2wpkh = ScriptBuf::new_p2wpkh(&keys[0].wpubkey_hash().unwrap());
+
+        // 41 bytes of outpoint, sequence and empty scriptSig, then a 108 byte witness
+        assert_eq!(psbt_input_weight(&Input::default(), &p2wpkh), Some(Weight::from_wu(41 * 4 + 108)));
+        // the scriptSig pushes a 22 byte P2WPKH program
+        let nested = Input { redeem_script: Some(p2wpkh.clone()), ..Default::default() };
+        assert_eq!(psbt_input_weight(&nested, &ScriptBuf::new_p2sh(&p2wpkh.script_hash())), Some(Weight::from_wu(64 * 4 + 108)));
+
+        // 2-of-3: item count, dummy, two 72 byte signatures and the 105 byte witness script
+        let witness_script = multisig(2, &keys);
+        let p2wsh = ScriptBuf::new_p2wsh(&witness_script.wscript_hash());
+        assert_eq!(psbt_input_weight(&Input::default(), &p2wsh), None);
+        let input = Input { witness_script: Some(witness_script), ..Default::default() };
+        assert_eq!(psbt_input_weight(&input, &p2wsh), Some(Weight::from_wu(41 * 4 + 1 + 1 + 2 * 73 + 1 + 105)));
+
+        // taproot key path: item count and a 64 byte signature
+        let p2tr = ScriptBuf::new_p2tr(&Secp256k1::new(), XOnlyPublicKey::from(keys[0].inner), None);
+        assert_eq!(psbt_input_weight(&Input::default(), &p2tr), Some(Weight::from_wu(41 * 4 + 66)));
+
+        // a final input weighs what its final scripts do
+        let witness = Witness::from_slice(&[vec![0; 71], vec![2; 33]]);
+        let finalized = Input { final_script_witness: Some(witness), ..Default::default() };
+        assert_eq!(psbt_input_weight(&finalized, &p2wpkh), Some(Weight::from_wu(41 * 4 + 107)));
+
+        assert_eq!(psbt_input_weight(&Input::default(), &ScriptBuf::new_op_return([])), None);
+    }
+}
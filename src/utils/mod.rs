@@ -1,2 +1,5 @@
 pub mod cli;
-pub mod utils;
\ No newline at end of file
+pub mod fees;
+pub mod output;
+#[allow(clippy::module_inception)]
+pub mod utils;
@@ -0,0 +1,235 @@
+use std::io::{self, Write};
+
+use serde::Serialize;
+use serde_yaml::Value;
+
+use crate::modules::errors::OutputError;
+
+#[derive(Clone)]
+pub enum OutputFormat {
+    Json,
+    Yaml,
+    Text,
+    Table,
+    Csv,
+}
+
+/// Write a command result to stdout in the requested format. Logs go to stderr, so
+/// stdout only ever carries the serialized document.
+pub fn print_output<T: Serialize>(value: &T, format: &OutputFormat) -> Result<(), OutputError> {
+    let rendered = match format {
+        OutputFormat::Json => serde_json::to_string_pretty(value)?,
+        OutputFormat::Yaml => serde_yaml::to_string(value)?,
+        OutputFormat::Text => render_text(&serde_yaml::to_value(value)?)?,
+        OutputFormat::Table => render_table(&serde_yaml::to_value(value)?)?,
+        OutputFormat::Csv => render_csv(&serde_yaml::to_value(value)?)?,
+    };
+
+    let mut stdout = io::stdout().lock();
+    writeln!(stdout, "{}", rendered.trim_end())?;
+    Ok(())
+}
+
+/// Indented `field: value` lines. Nested structs are indented under their field and list
+/// items are numbered from 1, so each value sits on its own greppable line.
+fn render_text(value: &Value) -> Result<String, OutputError> {
+    let mut lines = Vec::new();
+    text_lines(value, 0, &mut lines)?;
+    Ok(lines.join("\n"))
+}
+
+fn text_lines(value: &Value, indent: usize, lines: &mut Vec<String>) -> Result<(), OutputError> {
+    let pad = " ".repeat(indent);
+    match value {
+        Value::Mapping(map) => {
+            for (key, value) in map {
+                match value {
+                    Value::Mapping(nested) if !nested.is_empty() => {
+                        lines.push(format!("{}{}:", pad, cell(key)?));
+                        text_lines(value, indent + 2, lines)?;
+                    }
+                    Value::Sequence(items) if !items.is_empty() => {
+                        lines.push(format!("{}{}:", pad, cell(key)?));
+                        text_lines(value, indent + 2, lines)?;
+                    }
+                    Value::Mapping(_) | Value::Sequence(_) => lines.push(format!("{}{}: none", pad, cell(key)?)),
+                    scalar => lines.push(format!("{}{}: {}", pad, cell(key)?, cell(scalar)?).trim_end().to_string()),
+                }
+            }
+        }
+        Value::Sequence(items) => {
+            for (i, item) in items.iter().enumerate() {
+                match item {
+                    Value::Mapping(_) | Value::Sequence(_) => {
+                        lines.push(format!("{}[{}]", pad, i + 1));
+                        text_lines(item, indent + 2, lines)?;
+                    }
+                    scalar => lines.push(format!("{}[{}] {}", pad, i + 1, cell(scalar)?)),
+                }
+            }
+            if items.is_empty() {
+                lines.push(format!("{}none", pad));
+            }
+        }
+        scalar => lines.push(format!("{}{}", pad, cell(scalar)?)),
+    }
+    Ok(())
+}
+
+/// Columns padded to their widest cell, two spaces apart.
+fn render_table(value: &Value) -> Result<String, OutputError> {
+    let (headers, rows) = to_rows(value)?;
+    let widths: Vec<usize> = headers.iter().enumerate()
+        .map(|(i, header)| {
+            rows.iter()
+                .map(|row| row[i].chars().count())
+                .fold(header.chars().count(), usize::max)
+        })
+        .collect();
+
+    let format_line = |cells: &[String]| {
+        let line = cells.iter().zip(&widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect::<Vec<_>>()
+            .join("  ");
+        line.trim_end().to_string()
+    };
+
+    let mut lines = vec![format_line(&headers)];
+    lines.push(widths.iter().map(|width| "-".repeat(*width)).collect::<Vec<_>>().join("  "));
+    lines.extend(rows.iter().map(|row| format_line(row)));
+    Ok(lines.join("\n"))
+}
+
+/// RFC 4180 CSV with a header line.
+fn render_csv(value: &Value) -> Result<String, OutputError> {
+    let (headers, rows) = to_rows(value)?;
+    let format_line = |cells: &[String]| {
+        cells.iter()
+            .map(|cell| {
+                if cell.contains([',', '"', '\n', '\r']) {
+                    format!("\"{}\"", cell.replace('"', "\"\""))
+                } else {
+                    cell.clone()
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(",")
+    };
+
+    let mut lines = vec![format_line(&headers)];
+    lines.extend(rows.iter().map(|row| format_line(row)));
+    Ok(lines.join("\n"))
+}
+
+/// Flatten a result into a header and rows: a list of structs gets one row per element and
+/// one column per field, a single struct one `field,value` row per field. Nested values are
+/// written as inline JSON.
+fn to_rows(value: &Value) -> Result<(Vec<String>, Vec<Vec<String>>), OutputError> {
+    match value {
+        Value::Sequence(items) if items.iter().all(Value::is_mapping) && !items.is_empty() => {
+            let mut headers: Vec<String> = Vec::new();
+            for item in items.iter().filter_map(Value::as_mapping) {
+                for key in item.keys() {
+                    let key = cell(key)?;
+                    if !headers.contains(&key) {
+                        headers.push(key);
+                    }
+                }
+            }
+
+            let rows = items.iter()
+                .map(|item| {
+                    headers.iter()
+                        .map(|header| item.get(header.as_str()).map(cell).unwrap_or(Ok(String::new())))
+                        .collect()
+                })
+                .collect::<Result<_, _>>()?;
+            Ok((headers, rows))
+        }
+        Value::Sequence(items) => {
+            let rows = items.iter().map(|item| Ok(vec![cell(item)?])).collect::<Result<_, OutputError>>()?;
+            Ok((vec!["value".to_string()], rows))
+        }
+        Value::Mapping(map) => {
+            let rows = map.iter().map(|(key, value)| Ok(vec![cell(key)?, cell(value)?])).collect::<Result<_, OutputError>>()?;
+            Ok((vec!["field".to_string(), "value".to_string()], rows))
+        }
+        other => Ok((vec!["value".to_string()], vec![vec![cell(other)?]])),
+    }
+}
+
+fn cell(value: &Value) -> Result<String, OutputError> {
+    Ok(match value {
+        Value::Null => String::new(),
+        Value::Bool(b) => b.to_string(),
+        // plain decimals for BTC amounts, rather than the exponent YAML gives small floats
+        Value::Number(n) if n.is_f64() => n.as_f64().unwrap_or_default().to_string(),
+        Value::Number(n) => n.to_string(),
+        Value::String(s) => s.clone(),
+        nested => serde_json::to_string(nested)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Serialize;
+
+    use super::*;
+
+    #[derive(Serialize)]
+    struct Balance {
+        wallet: String,
+        trusted: f64,
+        label: Option<String>,
+    }
+
+    #[derive(Serialize)]
+    struct Report {
+        txid: String,
+        fee: f64,
+        balances: Vec<Balance>,
+        warnings: Vec<String>,
+        errors: Vec<String>,
+    }
+
+    #[test]
+    fn text_is_indented_field_value_lines() {
+        let report = Report {
+            txid: "ab".repeat(32),
+            fee: 0.00000141,
+            balances: vec![
+                Balance { wallet: "alice".to_string(), trusted: 1.5, label: None },
+                Balance { wallet: "bob".to_string(), trusted: 0.25, label: Some("cold".to_string()) },
+            ],
+            warnings: vec!["low fee".to_string()],
+            errors: vec![],
+        };
+
+        let text = render_text(&serde_yaml::to_value(&report).unwrap()).unwrap();
+        let expected = [
+            format!("txid: {}", "ab".repeat(32)),
+            "fee: 0.00000141".to_string(),
+            "balances:".to_string(),
+            "  [1]".to_string(),
+            "    wallet: alice".to_string(),
+            "    trusted: 1.5".to_string(),
+            "    label:".to_string(),
+            "  [2]".to_string(),
+            "    wallet: bob".to_string(),
+            "    trusted: 0.25".to_string(),
+            "    label: cold".to_string(),
+            "warnings:".to_string(),
+            "  [1] low fee".to_string(),
+            "errors: none".to_string(),
+        ];
+        assert_eq!(text, expected.join("\n"));
+    }
+
+    #[test]
+    fn text_of_a_list_and_a_scalar() {
+        assert_eq!(render_text(&serde_yaml::to_value(["a", "b"]).unwrap()).unwrap(), "[1] a\n[2] b");
+        assert_eq!(render_text(&serde_yaml::to_value(Vec::<String>::new()).unwrap()).unwrap(), "none");
+        assert_eq!(render_text(&serde_yaml::to_value("txid").unwrap()).unwrap(), "txid");
+    }
+}
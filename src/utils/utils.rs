@@ -1,30 +1,198 @@
-use std::collections::{HashMap, VecDeque};
-
-use bitcoin::Amount;
-use bitcoincore_rpc::json::ListUnspentResultEntry;
+use bitcoin::hashes::Hash;
+use bitcoin::{Address, Amount, FeeRate, OutPoint, Script, ScriptBuf, SignedAmount, Transaction, Weight};
+use bitcoincore_rpc::json::{AddressType, ListUnspentResultEntry};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use serde::Serialize;
 
 use crate::modules::errors::UtilsError;
 
-#[derive(Clone)]
+use super::fees::{fee_for_weight, input_weight, output_weight, TX_OVERHEAD_WEIGHT};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum UTXOStrategy {
     BranchAndBound,
+    /// Oldest UTXOs first, by number of confirmations
     Fifo,
+    /// Newest UTXOs first, by number of confirmations
+    NewestFirst,
     LargestFirst,
-    SmallestFirst
+    SmallestFirst,
+    /// Bitcoin Core's stochastic subset-sum approximation
+    Knapsack,
+    /// UTXOs in random order until the target plus a minimum change is reached
+    SingleRandomDraw,
+    /// Spend whole addresses, preferring a single address over mixing several
+    AddressGrouping,
+    /// Run every other strategy and keep the selection with the lowest waste
+    LowestWaste,
+}
+
+impl UTXOStrategy {
+    /// Strategies [`UTXOStrategy::LowestWaste`] chooses between.
+    pub const CANDIDATES: [UTXOStrategy; 8] = [
+        UTXOStrategy::BranchAndBound,
+        UTXOStrategy::Fifo,
+        UTXOStrategy::NewestFirst,
+        UTXOStrategy::LargestFirst,
+        UTXOStrategy::SmallestFirst,
+        UTXOStrategy::Knapsack,
+        UTXOStrategy::SingleRandomDraw,
+        UTXOStrategy::AddressGrouping,
+    ];
+
+    /// The name the strategy is selected by on the command line.
+    pub fn name(&self) -> &'static str {
+        match self {
+            UTXOStrategy::BranchAndBound => "branch-and-bound",
+            UTXOStrategy::Fifo => "fifo",
+            UTXOStrategy::NewestFirst => "newest-first",
+            UTXOStrategy::LargestFirst => "largest-first",
+            UTXOStrategy::SmallestFirst => "smallest-first",
+            UTXOStrategy::Knapsack => "knapsack",
+            UTXOStrategy::SingleRandomDraw => "single-random-draw",
+            UTXOStrategy::AddressGrouping => "address-grouping",
+            UTXOStrategy::LowestWaste => "lowest-waste",
+        }
+    }
 }
 
-/// Extract xpubs from descriptors
+/// How the inputs of a transaction are chosen.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CoinControl {
+    /// Let a strategy pick from the wallet's UTXOs
+    Strategy(UTXOStrategy),
+    /// Spend exactly these outpoints, all of them
+    Manual(Vec<OutPoint>),
+}
+
+impl From<UTXOStrategy> for CoinControl {
+    fn from(strategy: UTXOStrategy) -> Self {
+        CoinControl::Strategy(strategy)
+    }
+}
+
+/// How the inputs and outputs of a built transaction are ordered.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TxOrdering {
+    /// BIP69: inputs by txid then vout, outputs by amount then script
+    Bip69,
+    /// Outputs shuffled by an RNG seeded with the value, inputs left in selection order
+    Shuffle(u64),
+}
+
+impl TxOrdering {
+    /// Reorder `tx` in place. Must run before signing, as it changes the txid.
+    pub fn apply(&self, tx: &mut Transaction) {
+        match self {
+            TxOrdering::Bip69 => {
+                // txids compare in the byte order they are displayed in, the reverse of the internal one
+                tx.input.sort_by_key(|input| {
+                    let mut txid = input.previous_output.txid.to_byte_array();
+                    txid.reverse();
+                    (txid, input.previous_output.vout)
+                });
+                tx.output.sort_by(|a, b| {
+                    a.value.cmp(&b.value).then_with(|| a.script_pubkey.as_bytes().cmp(b.script_pubkey.as_bytes()))
+                });
+            }
+            TxOrdering::Shuffle(seed) => tx.output.shuffle(&mut StdRng::seed_from_u64(*seed)),
+        }
+    }
+}
+
+/// Ordering and change choices for transactions built from a coin selection.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TxLayout {
+    pub ordering: TxOrdering,
+    /// Change address type, or `None` to match the payments
+    pub change_type: Option<AddressType>,
+}
+
+impl Default for TxLayout {
+    fn default() -> Self {
+        TxLayout {
+            ordering: TxOrdering::Bip69,
+            change_type: None,
+        }
+    }
+}
+
+/// The wallet address type producing scripts like `address`, if the wallet can make one.
+pub fn wallet_address_type(address: &Address) -> Option<AddressType> {
+    match address.address_type()? {
+        bitcoin::AddressType::P2pkh => Some(AddressType::Legacy),
+        bitcoin::AddressType::P2sh => Some(AddressType::P2shSegwit),
+        bitcoin::AddressType::P2wpkh | bitcoin::AddressType::P2wsh => Some(AddressType::Bech32),
+        bitcoin::AddressType::P2tr => Some(AddressType::Bech32m),
+        _ => None,
+    }
+}
+
+/// Script wrapping the cosigner keys of a multisig wallet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MultisigScriptType {
+    /// `wsh(sortedmulti(...))`, native segwit
+    Wsh,
+    /// `sh(wsh(sortedmulti(...)))`, nested segwit
+    ShWsh,
+    /// `sh(sortedmulti(...))`, legacy P2SH
+    Sh,
+    /// `tr(<NUMS>, sortedmulti_a(...))`, a taproot script path with an unspendable internal key
+    Tr,
+}
+
+/// BIP341's provably unspendable internal key H = lift_x(SHA256(G)).
+pub const NUMS_INTERNAL_KEY: &str = "50929b74c1a04954b78b4b6035e97a5e078a5a0f28ec96d547bfee9ace803ac0";
+
+impl MultisigScriptType {
+    /// Maximum number of cosigners. P2SH redeem scripts are capped at 520 bytes (15 keys),
+    /// `OP_CHECKMULTISIG` at 20 keys, and Bitcoin Core accepts up to 999 keys in `multi_a`.
+    pub fn max_keys(&self) -> usize {
+        match self {
+            MultisigScriptType::Sh => 15,
+            MultisigScriptType::Wsh | MultisigScriptType::ShWsh => 20,
+            MultisigScriptType::Tr => 999,
+        }
+    }
+
+    /// Prefix of the single-key descriptor each cosigner wallet contributes its xpubs from,
+    /// so every key comes from the BIP44/49/84/86 account matching the multisig address type.
+    pub fn cosigner_descriptor_prefix(&self) -> &'static str {
+        match self {
+            MultisigScriptType::Wsh => "wpkh(",
+            MultisigScriptType::ShWsh => "sh(wpkh(",
+            MultisigScriptType::Sh => "pkh(",
+            MultisigScriptType::Tr => "tr(",
+        }
+    }
+
+    pub fn descriptor(&self, nrequired: u32, keys: &[String]) -> String {
+        let keys = keys.join(",");
+        match self {
+            MultisigScriptType::Wsh => format!("wsh(sortedmulti({},{}))", nrequired, keys),
+            MultisigScriptType::ShWsh => format!("sh(wsh(sortedmulti({},{})))", nrequired, keys),
+            MultisigScriptType::Sh => format!("sh(sortedmulti({},{}))", nrequired, keys),
+            MultisigScriptType::Tr => format!("tr({},sortedmulti_a({},{}))", NUMS_INTERNAL_KEY, nrequired, keys),
+        }
+    }
+}
+
+// Extract xpubs from descriptors
 
 pub fn extract_int_ext_xpubs(
-    mut xpubs: HashMap<String,String>,
-    descriptors_array: Vec<serde_json::Value>,
-    i: usize
-) -> Result<HashMap<String, String>, UtilsError> {
+    descriptors_array: &[serde_json::Value],
+    script_type: MultisigScriptType,
+) -> Result<(String, String), UtilsError> {
+    let prefix = script_type.cosigner_descriptor_prefix();
+
     // Find the correct descriptors for external and internal xpubs
     let external_xpub = descriptors_array
         .iter()
         .find(|desc| {
-            desc["desc"].as_str().unwrap_or_default().starts_with("wpkh") && desc["desc"].as_str().unwrap_or_default().contains("/0/*")
+            desc["desc"].as_str().unwrap_or_default().starts_with(prefix) && desc["desc"].as_str().unwrap_or_default().contains("/0/*")
         })
         .ok_or(UtilsError::ExternalXpubNotFound)?["desc"]
         .as_str().ok_or(UtilsError::ExternalXpubNotFound)?
@@ -33,7 +201,7 @@ pub fn extract_int_ext_xpubs(
     let internal_xpub = descriptors_array
         .iter()
         .find(|desc| {
-            desc["desc"].as_str().unwrap_or_default().starts_with("wpkh") && desc["desc"].as_str().unwrap_or_default().contains("/1/*")
+            desc["desc"].as_str().unwrap_or_default().starts_with(prefix) && desc["desc"].as_str().unwrap_or_default().contains("/1/*")
         })
         .ok_or(UtilsError::InternalXpubNotFound)?["desc"]
         .as_str().ok_or(UtilsError::InternalXpubNotFound)?
@@ -41,127 +209,819 @@ pub fn extract_int_ext_xpubs(
 
     // formatting notes: https://bitcoincoredocs.com/descriptors.html
     // split at "]" and take the last part
-    let external_xpub_no_path = external_xpub.split("]").last().unwrap().to_string();
-    let internal_xpub_no_path = internal_xpub.split("]").last().unwrap().to_string();
+    let external_xpub_no_path = external_xpub.split(']').next_back().unwrap().to_string();
+    let internal_xpub_no_path = internal_xpub.split(']').next_back().unwrap().to_string();
 
     // split at ")" and take the first part
-    let external_xpub_no_path = external_xpub_no_path.split(")").next().unwrap().to_string();
-    let internal_xpub_no_path = internal_xpub_no_path.split(")").next().unwrap().to_string();
+    let external_xpub_no_path = external_xpub_no_path.split(')').next().unwrap().to_string();
+    let internal_xpub_no_path = internal_xpub_no_path.split(')').next().unwrap().to_string();
+
+    Ok((external_xpub_no_path, internal_xpub_no_path))
+}
+
+// UTXO Selection Strategies
+
+/// Fee rate we expect to pay when the selected inputs would otherwise be spent later,
+/// Bitcoin Core's default `-consolidatefeerate` of 10 sat/vB.
+pub const LONG_TERM_FEE_RATE: FeeRate = FeeRate::from_sat_per_kwu(2500);
+
+/// Bounds of the change the knapsack and single random draw strategies aim to leave, in sat.
+const CHANGE_LOWER: i64 = 50_000;
+const CHANGE_UPPER: i64 = 1_000_000;
+
+/// Upper bound on the number of branches branch-and-bound explores, as in Bitcoin Core.
+const BNB_TOTAL_TRIES: usize = 100_000;
+
+/// Everything a strategy needs to price a candidate input set at the target fee rate.
+#[derive(Clone, Debug)]
+pub struct SelectionParams {
+    pub fee_rate: FeeRate,
+    /// Fee rate inputs are assumed to cost when spent later instead, for the waste metric
+    pub long_term_fee_rate: FeeRate,
+    /// Transaction overhead plus the payment outputs
+    pub base_weight: Weight,
+    /// Weight of the change output, added only when change is created
+    pub change_weight: Weight,
+    /// Weight of the input that will later spend the change output
+    pub change_spend_weight: Weight,
+    /// Change below this value is added to the fee instead of creating an output
+    pub change_dust: Amount,
+    /// Seeds the knapsack and single random draw shuffles, which use fresh entropy when unset
+    pub seed: Option<u64>,
+}
+
+impl SelectionParams {
+    /// Parameters for a transaction paying to `payment_scripts`, with change to a `change_script`
+    /// output that costs `change_spend_weight` to spend.
+    pub fn new(fee_rate: FeeRate, payment_scripts: &[ScriptBuf], change_script: &Script, change_spend_weight: Weight) -> Self {
+        let base_weight = payment_scripts.iter()
+            .fold(TX_OVERHEAD_WEIGHT, |weight, script| weight + output_weight(script));
+        SelectionParams {
+            fee_rate,
+            long_term_fee_rate: LONG_TERM_FEE_RATE,
+            base_weight,
+            change_weight: output_weight(change_script),
+            change_spend_weight,
+            change_dust: change_script.dust_value(),
+            seed: None,
+        }
+    }
+
+    /// Fee paid for adding the change output at the current fee rate.
+    pub fn change_fee(&self) -> Amount {
+        fee_for_weight(self.fee_rate, self.change_weight)
+    }
+
+    /// Fee of a transaction spending inputs of `input_weight`, with or without a change output.
+    pub fn fee(&self, input_weight: Weight, with_change: bool) -> Amount {
+        let mut weight = self.base_weight + input_weight;
+        if with_change {
+            weight += self.change_weight;
+        }
+        fee_for_weight(self.fee_rate, weight)
+    }
+
+    /// Creating change now plus spending it later at the long-term fee rate.
+    pub fn cost_of_change(&self) -> Amount {
+        fee_for_weight(self.fee_rate, self.change_weight)
+            + fee_for_weight(self.long_term_fee_rate, self.change_spend_weight)
+    }
+
+    /// The RNG of the randomized strategies, reproducible when a seed is set.
+    fn rng(&self) -> StdRng {
+        self.seed.map_or_else(StdRng::from_entropy, StdRng::seed_from_u64)
+    }
+
+    /// Input value left once its own input fee is paid at the target fee rate.
+    fn effective_value(&self, utxo: &ListUnspentResultEntry) -> i64 {
+        utxo.amount.to_sat() as i64 - fee_for_weight(self.fee_rate, input_weight(utxo)).to_sat() as i64
+    }
 
-    xpubs.insert(format!("internal_xpub_{}", i + 1), internal_xpub_no_path);
-    xpubs.insert(format!("external_xpub_{}", i + 1), external_xpub_no_path);
+    /// What spending `utxo` now costs over spending it at the long-term fee rate.
+    fn input_waste(&self, utxo: &ListUnspentResultEntry) -> i64 {
+        let weight = input_weight(utxo);
+        fee_for_weight(self.fee_rate, weight).to_sat() as i64
+            - fee_for_weight(self.long_term_fee_rate, weight).to_sat() as i64
+    }
 
-    Ok(xpubs)
+    /// Settle the fee, change and waste of `utxos`, or `None` if they cannot pay `target_amount`
+    /// plus fee. Change that would fall below dust once its output is paid for goes to the fee,
+    /// as does all of the excess when `allow_change` is false.
+    fn finish(&self, utxos: Vec<ListUnspentResultEntry>, target_amount: Amount, allow_change: bool) -> Option<CoinSelection> {
+        let total: Amount = utxos.iter().map(|utxo| utxo.amount).sum();
+        let input_weight: Weight = utxos.iter().map(input_weight).sum();
+
+        let fee_without_change = self.fee(input_weight, false);
+        if total < target_amount + fee_without_change {
+            return None;
+        }
+
+        let fee_with_change = self.fee(input_weight, true);
+        let change = total.checked_sub(target_amount + fee_with_change)
+            .filter(|change| allow_change && *change >= self.change_dust);
+
+        // waste = input fees above the long-term rate, plus the cost of change or the excess given up
+        let inputs_waste: i64 = utxos.iter().map(|utxo| self.input_waste(utxo)).sum();
+        let (fee, change_waste) = match change {
+            Some(_) => (fee_with_change, self.cost_of_change()),
+            None => (total - target_amount, total - target_amount - fee_without_change),
+        };
+        let waste = SignedAmount::from_sat(inputs_waste + change_waste.to_sat() as i64);
+
+        Some(CoinSelection { utxos, fee, change, waste })
+    }
 }
 
-/// UTXO Selection Strategies
+/// UTXOs chosen by a strategy with the resulting fee, change and waste.
+#[derive(Clone, Debug)]
+pub struct CoinSelection {
+    pub utxos: Vec<ListUnspentResultEntry>,
+    pub fee: Amount,
+    pub change: Option<Amount>,
+    /// Bitcoin Core's waste metric: lower is better, negative when fees are below the long-term rate
+    pub waste: SignedAmount,
+}
 
 pub fn strat_handler(
     utxos: &[ListUnspentResultEntry],
     target_amount: Amount,
-    fee_amount: Amount,
+    params: &SelectionParams,
     utxo_strategy: UTXOStrategy
-) -> Result<Vec<ListUnspentResultEntry>, UtilsError> {
+) -> Result<CoinSelection, UtilsError> {
     match utxo_strategy {
-        UTXOStrategy::BranchAndBound => select_utxos_branch_and_bound(utxos, target_amount, fee_amount)
-            .ok_or(UtilsError::InsufficientUTXOs),
-        UTXOStrategy::Fifo => select_utxos_fifo(utxos, target_amount, fee_amount),
-        UTXOStrategy::LargestFirst => select_utxos_largest_first(utxos, target_amount, fee_amount),
-        UTXOStrategy::SmallestFirst => select_utxos_smallest_first(utxos, target_amount, fee_amount),
+        UTXOStrategy::BranchAndBound => select_utxos_branch_and_bound(utxos, target_amount, params),
+        UTXOStrategy::Fifo => select_utxos_fifo(utxos, target_amount, params),
+        UTXOStrategy::NewestFirst => select_utxos_newest_first(utxos, target_amount, params),
+        UTXOStrategy::LargestFirst => select_utxos_largest_first(utxos, target_amount, params),
+        UTXOStrategy::SmallestFirst => select_utxos_smallest_first(utxos, target_amount, params),
+        UTXOStrategy::Knapsack => select_utxos_knapsack(utxos, target_amount, params),
+        UTXOStrategy::SingleRandomDraw => select_utxos_single_random_draw(utxos, target_amount, params),
+        UTXOStrategy::AddressGrouping => select_utxos_address_grouping(utxos, target_amount, params),
+        UTXOStrategy::LowestWaste => select_utxos_lowest_waste(utxos, target_amount, params),
     }
 }
 
+/// Spend all of `utxos`, with change if the excess allows. Fails when they cannot cover
+/// `target_amount` plus the fee.
+pub fn select_utxos_manual(
+    utxos: &[ListUnspentResultEntry],
+    target_amount: Amount,
+    params: &SelectionParams,
+) -> Result<CoinSelection, UtilsError> {
+    params.finish(utxos.to_vec(), target_amount, true).ok_or_else(|| {
+        let total: Amount = utxos.iter().map(|utxo| utxo.amount).sum();
+        let fee = params.fee(utxos.iter().map(input_weight).sum(), false);
+        UtilsError::UncoveredTarget(total, target_amount + fee)
+    })
+}
+
+fn select_utxos_lowest_waste(
+    utxos: &[ListUnspentResultEntry],
+    target_amount: Amount,
+    params: &SelectionParams,
+) -> Result<CoinSelection, UtilsError> {
+    // ties go to the selection spending fewer inputs
+    UTXOStrategy::CANDIDATES.iter()
+        .filter_map(|strategy| strat_handler(utxos, target_amount, params, *strategy).ok())
+        .min_by_key(|selection| (selection.waste, selection.utxos.len()))
+        .ok_or(UtilsError::InsufficientUTXOs)
+}
+
+/// Depth-first branch-and-bound search for a changeless input set, following Bitcoin Core.
+///
+/// UTXOs are explored by descending effective value. A branch is cut once it overshoots the
+/// target by more than the cost of change, can no longer reach the target, or (at fee rates above
+/// the long-term rate) already wastes more than the best solution. The search stops after
+/// [`BNB_TOTAL_TRIES`] steps and falls back to largest-first, with change, if nothing was found.
 fn select_utxos_branch_and_bound(
     utxos: &[ListUnspentResultEntry],
     target_amount: Amount,
-    fee_amount: Amount,
-) -> Option<Vec<ListUnspentResultEntry>> {
-    let mut current_best_solution = None;
-    let mut current_best_change = Amount::from_sat(u64::MAX);
-
-    // the queue is a "vector double ended queue" that allows us to add and remove
-    // elements from both ends of the vector
-    let mut queue: VecDeque<(Vec<ListUnspentResultEntry>, Amount)> = VecDeque::new();
-
-    // add the first element to the queue
-    queue.push_back((Vec::new(), Amount::from_sat(0)));
-
-    // This while loop uses a breadth-first search approach to explore all possible combinations of UTXOs.
-    // It continually checks if the current combination is sufficient to cover the target amount plus fees
-    // and updates the best solution found so far. If a combination is not sufficient, it expands the search
-    // by adding more UTXOs to the combination and continues the process until all possibilities have been
-    // explored. This ensures that the algorithm finds an optimal set of UTXOs with minimal leftover change.
-    while let Some((current_selection, current_total)) = queue.pop_front() {
-        if current_total >= target_amount + fee_amount {
-            let change = current_total - target_amount - fee_amount;
-            if change < current_best_change {
-                current_best_change = change;
-                current_best_solution = Some(current_selection.clone());
+    params: &SelectionParams,
+) -> Result<CoinSelection, UtilsError> {
+    let mut pool: Vec<(&ListUnspentResultEntry, i64, i64)> = utxos.iter()
+        .map(|utxo| (utxo, params.effective_value(utxo), params.input_waste(utxo)))
+        .filter(|(_, effective_value, _)| *effective_value > 0)
+        .collect();
+    pool.sort_by_key(|(_, effective_value, _)| std::cmp::Reverse(*effective_value));
+
+    let target = (target_amount + params.fee(Weight::ZERO, false)).to_sat() as i64;
+    let cost_of_change = params.cost_of_change().to_sat() as i64;
+    let is_feerate_high = params.fee_rate > params.long_term_fee_rate;
+
+    let mut curr_value: i64 = 0;
+    let mut curr_waste: i64 = 0;
+    let mut curr_available: i64 = pool.iter().map(|(_, effective_value, _)| effective_value).sum();
+    let mut curr_selection: Vec<usize> = Vec::new();
+    let mut best_selection: Option<Vec<usize>> = None;
+    let mut best_waste = i64::MAX;
+
+    let mut index = 0;
+    for _ in 0..BNB_TOTAL_TRIES {
+        let mut backtrack = false;
+        if curr_value + curr_available < target
+            || curr_value > target + cost_of_change
+            || (is_feerate_high && curr_waste > best_waste)
+        {
+            backtrack = true;
+        } else if curr_value >= target {
+            // the excess is given up to fees, so it counts as waste
+            let waste = curr_waste + (curr_value - target);
+            if waste <= best_waste {
+                best_waste = waste;
+                best_selection = Some(curr_selection.clone());
+            }
+            backtrack = true;
+        }
+
+        if backtrack {
+            let Some(&last) = curr_selection.last() else {
+                // every branch has been explored
+                break;
+            };
+            // put back the UTXOs skipped after the last included one, then exclude that one too
+            index -= 1;
+            while index > last {
+                curr_available += pool[index].1;
+                index -= 1;
             }
+            curr_value -= pool[index].1;
+            curr_waste -= pool[index].2;
+            curr_selection.pop();
         } else {
-            for (_index, utxo) in utxos.iter().enumerate() {
-                if !current_selection.contains(utxo) {
-                    let mut new_selection = current_selection.clone();
-                    new_selection.push(utxo.clone());
-                    let new_total = current_total + utxo.amount;
-                    queue.push_back((new_selection, new_total));
-                }
+            let (_, effective_value, waste) = pool[index];
+            curr_available -= effective_value;
+            // skip a UTXO equal to one that was just excluded, it leads to the same solutions
+            let duplicate_of_excluded = index > 0
+                && !curr_selection.is_empty()
+                && curr_selection.last() != Some(&(index - 1))
+                && pool[index - 1].1 == effective_value
+                && pool[index - 1].2 == waste;
+            if !duplicate_of_excluded {
+                curr_selection.push(index);
+                curr_value += effective_value;
+                curr_waste += waste;
             }
         }
+        index += 1;
     }
 
-    current_best_solution
+    match best_selection {
+        Some(selection) => {
+            let selected = selection.into_iter().map(|i| pool[i].0.clone()).collect();
+            params.finish(selected, target_amount, false).ok_or(UtilsError::InsufficientUTXOs)
+        }
+        None => select_utxos_largest_first(utxos, target_amount, params),
+    }
 }
 
 fn select_utxos_fifo(
     utxos: &[ListUnspentResultEntry],
     target_amount: Amount,
-    fee_amount: Amount,
-) -> Result<Vec<ListUnspentResultEntry>, UtilsError> {
-    let sorted_utxos = utxos.to_vec();
-    return select_utxos(sorted_utxos, target_amount, fee_amount);
+    params: &SelectionParams,
+) -> Result<CoinSelection, UtilsError> {
+    // Most confirmations first
+    let mut sorted_utxos = utxos.to_vec();
+    sorted_utxos.sort_by_key(|utxo| std::cmp::Reverse(utxo.confirmations));
+
+    select_utxos(sorted_utxos, target_amount, params)
+}
+
+fn select_utxos_newest_first(
+    utxos: &[ListUnspentResultEntry],
+    target_amount: Amount,
+    params: &SelectionParams,
+) -> Result<CoinSelection, UtilsError> {
+    // Fewest confirmations first
+    let mut sorted_utxos = utxos.to_vec();
+    sorted_utxos.sort_by_key(|utxo| utxo.confirmations);
+
+    select_utxos(sorted_utxos, target_amount, params)
 }
 
 fn select_utxos_largest_first(
     utxos: &[ListUnspentResultEntry],
     target_amount: Amount,
-    fee_amount: Amount,
-) -> Result<Vec<ListUnspentResultEntry>, UtilsError> {
+    params: &SelectionParams,
+) -> Result<CoinSelection, UtilsError> {
     // Sort UTXOs by amount in descending order
     let mut sorted_utxos = utxos.to_vec();
-    sorted_utxos.sort_by(|a, b| b.amount.cmp(&a.amount));
+    sorted_utxos.sort_by_key(|utxo| std::cmp::Reverse(utxo.amount));
 
-    return select_utxos(sorted_utxos, target_amount, fee_amount);
+    select_utxos(sorted_utxos, target_amount, params)
 }
 
 fn select_utxos_smallest_first(
     utxos: &[ListUnspentResultEntry],
     target_amount: Amount,
-    fee_amount: Amount,
-) -> Result<Vec<ListUnspentResultEntry>, UtilsError> {
-    // Sort UTXOs by amount in descending order
+    params: &SelectionParams,
+) -> Result<CoinSelection, UtilsError> {
+    // Sort UTXOs by amount in ascending order
     let mut sorted_utxos = utxos.to_vec();
-    sorted_utxos.sort_by(|a, b| a.amount.cmp(&b.amount));
+    sorted_utxos.sort_by_key(|utxo| utxo.amount);
 
-    return select_utxos(sorted_utxos, target_amount, fee_amount);
+    select_utxos(sorted_utxos, target_amount, params)
 }
 
+/// Bitcoin Core's knapsack solver over effective values: an exact match if one UTXO or all
+/// smaller ones hit the target, otherwise the best of a randomized subset-sum approximation
+/// aiming for the target plus a minimum change and the change output's fee, and the smallest
+/// UTXO covering that on its own.
+fn select_utxos_knapsack(
+    utxos: &[ListUnspentResultEntry],
+    target_amount: Amount,
+    params: &SelectionParams,
+) -> Result<CoinSelection, UtilsError> {
+    let mut rng = params.rng();
+    let mut pool: Vec<(&ListUnspentResultEntry, i64)> = utxos.iter()
+        .map(|utxo| (utxo, params.effective_value(utxo)))
+        .filter(|(_, effective_value)| *effective_value > 0)
+        .collect();
+    pool.shuffle(&mut rng);
+
+    let target = (target_amount + params.fee(Weight::ZERO, false)).to_sat() as i64;
+    // like Core's m_min_change_target plus m_change_fee, so the change output is paid for
+    let change_target = knapsack_change_target(target_amount, &mut rng) + params.change_fee().to_sat() as i64;
+    let finish = |selected: Vec<&ListUnspentResultEntry>| {
+        let selected = selected.into_iter().cloned().collect();
+        params.finish(selected, target_amount, true).ok_or(UtilsError::InsufficientUTXOs)
+    };
+
+    let mut applicable: Vec<(&ListUnspentResultEntry, i64)> = Vec::new();
+    let mut total_lower: i64 = 0;
+    let mut lowest_larger: Option<(&ListUnspentResultEntry, i64)> = None;
+    for &(utxo, effective_value) in &pool {
+        if effective_value == target {
+            return finish(vec![utxo]);
+        } else if effective_value < target + change_target {
+            applicable.push((utxo, effective_value));
+            total_lower += effective_value;
+        } else if lowest_larger.is_none_or(|(_, lowest)| effective_value < lowest) {
+            lowest_larger = Some((utxo, effective_value));
+        }
+    }
+
+    if total_lower == target {
+        return finish(applicable.iter().map(|(utxo, _)| *utxo).collect());
+    }
+    if total_lower < target {
+        let (utxo, _) = lowest_larger.ok_or(UtilsError::InsufficientUTXOs)?;
+        return finish(vec![utxo]);
+    }
+
+    applicable.sort_by_key(|(_, effective_value)| std::cmp::Reverse(*effective_value));
+    let values: Vec<i64> = applicable.iter().map(|(_, effective_value)| *effective_value).collect();
+    let (mut best, mut best_value) = approximate_best_subset(&values, total_lower, target, &mut rng);
+    if best_value != target && total_lower >= target + change_target {
+        (best, best_value) = approximate_best_subset(&values, total_lower, target + change_target, &mut rng);
+    }
+
+    // a single larger UTXO wins if the subset misses both targets or costs more
+    if let Some((utxo, lowest_value)) = lowest_larger {
+        if (best_value != target && best_value < target + change_target) || lowest_value <= best_value {
+            return finish(vec![utxo]);
+        }
+    }
+
+    let selected = applicable.iter()
+        .zip(best)
+        .filter(|(_, included)| *included)
+        .map(|((utxo, _), _)| *utxo)
+        .collect();
+    finish(selected)
+}
+
+/// Change the knapsack solver aims to leave: random between 50,000 sat and twice the payment,
+/// capped at 0.01 BTC, as in Bitcoin Core.
+fn knapsack_change_target(target_amount: Amount, rng: &mut impl Rng) -> i64 {
+    let upper = (2 * target_amount.to_sat() as i64).clamp(CHANGE_LOWER, CHANGE_UPPER);
+    rng.gen_range(CHANGE_LOWER..=upper)
+}
+
+/// 1000 rounds of random inclusion, each followed by a pass adding the remaining values, keeping
+/// the smallest sum reaching `target`. Starts from including everything.
+fn approximate_best_subset(values: &[i64], total_lower: i64, target: i64, rng: &mut impl Rng) -> (Vec<bool>, i64) {
+    let mut best = vec![true; values.len()];
+    let mut best_value = total_lower;
+
+    for _ in 0..1000 {
+        if best_value == target {
+            break;
+        }
+        let mut included = vec![false; values.len()];
+        let mut total = 0;
+        let mut reached_target = false;
+        for pass in 0..2 {
+            if reached_target {
+                break;
+            }
+            for (i, value) in values.iter().enumerate() {
+                let include = if pass == 0 { rng.gen_bool(0.5) } else { !included[i] };
+                if !include {
+                    continue;
+                }
+                total += value;
+                included[i] = true;
+                if total >= target {
+                    reached_target = true;
+                    if total < best_value {
+                        best_value = total;
+                        best = included.clone();
+                    }
+                    total -= value;
+                    included[i] = false;
+                }
+            }
+        }
+    }
+
+    (best, best_value)
+}
+
+/// Draw UTXOs at random until their effective value covers the target, a change output and
+/// [`CHANGE_LOWER`] of change, so the result always leaves a useful change output.
+fn select_utxos_single_random_draw(
+    utxos: &[ListUnspentResultEntry],
+    target_amount: Amount,
+    params: &SelectionParams,
+) -> Result<CoinSelection, UtilsError> {
+    let mut shuffled = utxos.to_vec();
+    shuffled.shuffle(&mut params.rng());
+
+    let target = (target_amount + params.fee(Weight::ZERO, false) + params.change_fee()).to_sat() as i64 + CHANGE_LOWER;
+
+    let mut selected = Vec::new();
+    let mut total: i64 = 0;
+    for utxo in shuffled {
+        let effective_value = params.effective_value(&utxo);
+        if effective_value <= 0 {
+            continue;
+        }
+        total += effective_value;
+        selected.push(utxo);
+        if total >= target {
+            break;
+        }
+    }
+
+    params.finish(selected, target_amount, true).ok_or(UtilsError::InsufficientUTXOs)
+}
+
+/// Spend every UTXO of an address together so no address is left half spent. The single
+/// address with the lowest waste is preferred; if none covers the target alone, whole
+/// addresses are added largest first so as few as possible are linked.
+fn select_utxos_address_grouping(
+    utxos: &[ListUnspentResultEntry],
+    target_amount: Amount,
+    params: &SelectionParams,
+) -> Result<CoinSelection, UtilsError> {
+    let mut groups: Vec<Vec<ListUnspentResultEntry>> = Vec::new();
+    for utxo in utxos {
+        match groups.iter_mut().find(|group| group[0].script_pub_key == utxo.script_pub_key) {
+            Some(group) => group.push(utxo.clone()),
+            None => groups.push(vec![utxo.clone()]),
+        }
+    }
+
+    let single_address = groups.iter()
+        .filter_map(|group| params.finish(group.clone(), target_amount, true))
+        .min_by_key(|selection| (selection.waste, selection.utxos.len()));
+    if let Some(selection) = single_address {
+        return Ok(selection);
+    }
+
+    groups.sort_by_key(|group| std::cmp::Reverse(group.iter().map(|utxo| utxo.amount).sum::<Amount>()));
+    let mut selected = Vec::new();
+    for group in groups {
+        selected.extend(group);
+        if let Some(selection) = params.finish(selected.clone(), target_amount, true) {
+            return Ok(selection);
+        }
+    }
+
+    Err(UtilsError::InsufficientUTXOs)
+}
+
+/// Take UTXOs in order until they pay for the target and for their own inputs.
 fn select_utxos(
     sorted_utxos: Vec<ListUnspentResultEntry>,
     target_amount: Amount,
-    fee_amount: Amount
-) -> Result<Vec<ListUnspentResultEntry>, UtilsError> {
+    params: &SelectionParams,
+) -> Result<CoinSelection, UtilsError> {
     let mut selected_utxos = Vec::new();
     let mut total_amount = Amount::from_sat(0);
+    let mut total_weight = Weight::ZERO;
 
-    for utxo in sorted_utxos.iter() {
-        selected_utxos.push(utxo.clone());
+    for utxo in sorted_utxos.into_iter() {
         total_amount += utxo.amount;
+        total_weight += input_weight(&utxo);
+        selected_utxos.push(utxo);
 
-        if total_amount >= target_amount + fee_amount {
-            return Ok(selected_utxos);
+        if total_amount >= target_amount + params.fee(total_weight, false) {
+            return params.finish(selected_utxos, target_amount, true)
+                .ok_or(UtilsError::InsufficientUTXOs);
         }
     }
 
     Err(UtilsError::InsufficientUTXOs)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use bitcoin::absolute::LockTime;
+    use bitcoin::transaction::Version;
+    use bitcoin::{Sequence, TxIn, TxOut, Txid, WPubkeyHash, Witness};
+
+    use super::*;
+
+    /// 20 sat/vB, twice the long-term rate, so every P2WPKH input wastes 68 vB * 10 sat/vB.
+    const FEE_RATE: FeeRate = FeeRate::from_sat_per_kwu(5000);
+    const INPUT_WASTE: i64 = 680;
+    /// A 31 vB P2WPKH change output at 20 sat/vB, plus spending it later at 10 sat/vB
+    const COST_OF_CHANGE: i64 = 620 + 680;
+    /// Overhead and the payment output, 166 wu at 20 sat/vB
+    const BASE_FEE: u64 = 830;
+    /// One P2WPKH input, 272 wu at 20 sat/vB
+    const INPUT_FEE: u64 = 1360;
+
+    fn script(address: u8) -> ScriptBuf {
+        ScriptBuf::new_p2wpkh(&WPubkeyHash::from_byte_array([address; 20]))
+    }
+
+    fn utxo(vout: u32, sat: u64, address: u8) -> ListUnspentResultEntry {
+        ListUnspentResultEntry {
+            txid: Txid::all_zeros(),
+            vout,
+            address: None,
+            label: None,
+            redeem_script: None,
+            witness_script: None,
+            script_pub_key: script(address),
+            amount: Amount::from_sat(sat),
+            confirmations: 6,
+            spendable: true,
+            solvable: true,
+            descriptor: None,
+            safe: true,
+        }
+    }
+
+    /// A P2WPKH UTXO for each amount, each at its own address.
+    fn pool(amounts: &[u64]) -> Vec<ListUnspentResultEntry> {
+        amounts.iter().enumerate().map(|(i, sat)| utxo(i as u32, *sat, i as u8)).collect()
+    }
+
+    fn params() -> SelectionParams {
+        SelectionParams::new(FEE_RATE, &[script(0xff)], &script(0xfe), Weight::from_wu(272))
+    }
+
+    fn amounts(selection: &CoinSelection) -> Vec<u64> {
+        let mut amounts: Vec<u64> = selection.utxos.iter().map(|utxo| utxo.amount.to_sat()).collect();
+        amounts.sort_unstable();
+        amounts
+    }
+
+    #[test]
+    fn branch_and_bound_finds_changeless_match() {
+        let utxos = pool(&[1_100_000, 600_000, 300_000, 400_000, 250_000]);
+        // exactly the effective value of 600,000 and 400,000 once the base fee is paid
+        let target = Amount::from_sat(1_000_000 - 2 * INPUT_FEE - BASE_FEE);
+
+        let selection = strat_handler(&utxos, target, &params(), UTXOStrategy::BranchAndBound).unwrap();
+        assert_eq!(amounts(&selection), vec![400_000, 600_000]);
+        assert_eq!(selection.change, None);
+        assert_eq!(selection.fee, Amount::from_sat(BASE_FEE + 2 * INPUT_FEE));
+        assert_eq!(selection.waste, SignedAmount::from_sat(2 * INPUT_WASTE));
+    }
+
+    #[test]
+    fn branch_and_bound_without_match_falls_back_to_largest_first() {
+        let utxos = pool(&[5_000_000, 2_000_000]);
+        let target = Amount::from_sat(1_000_000);
+
+        let selection = strat_handler(&utxos, target, &params(), UTXOStrategy::BranchAndBound).unwrap();
+        assert_eq!(amounts(&selection), vec![5_000_000]);
+        assert_eq!(selection.change, Some(Amount::from_sat(5_000_000 - 1_000_000 - BASE_FEE - 620 - INPUT_FEE)));
+        assert_eq!(selection.waste, SignedAmount::from_sat(INPUT_WASTE + COST_OF_CHANGE));
+    }
+
+    #[test]
+    fn waste_counts_input_fees_and_change_or_excess() {
+        let params = params();
+        let target = Amount::from_sat(1_000_000);
+        let utxos = pool(&[700_000, 400_000]);
+
+        let with_change = params.finish(utxos.clone(), target, true).unwrap();
+        assert_eq!(with_change.waste, SignedAmount::from_sat(2 * INPUT_WASTE + COST_OF_CHANGE));
+
+        // without change the whole excess is given up to the fee
+        let without_change = params.finish(utxos, target, false).unwrap();
+        let excess = 1_100_000 - 1_000_000 - BASE_FEE - 2 * INPUT_FEE;
+        assert_eq!(without_change.fee, Amount::from_sat(100_000));
+        assert_eq!(without_change.waste, SignedAmount::from_sat(2 * INPUT_WASTE + excess as i64));
+
+        // below the long-term fee rate, spending inputs now saves fees
+        let cheap = SelectionParams::new(FeeRate::from_sat_per_kwu(250), &[script(0xff)], &script(0xfe), Weight::from_wu(272));
+        assert!(cheap.finish(pool(&[700_000, 400_000]), target, true).unwrap().waste < SignedAmount::ZERO);
+    }
+
+    #[test]
+    fn lowest_waste_prefers_the_changeless_match() {
+        let utxos = pool(&[1_100_000, 600_000, 300_000, 400_000, 250_000]);
+        let target = Amount::from_sat(1_000_000 - 2 * INPUT_FEE - BASE_FEE);
+
+        let selection = strat_handler(&utxos, target, &params(), UTXOStrategy::LowestWaste).unwrap();
+        assert_eq!(amounts(&selection), vec![400_000, 600_000]);
+        assert_eq!(selection.change, None);
+    }
+
+    /// Twenty UTXOs between 0.001 and 0.02 BTC, each at its own address.
+    fn varied_pool() -> Vec<ListUnspentResultEntry> {
+        pool(&(1..=20).map(|i| i * 100_000).collect::<Vec<u64>>())
+    }
+
+    fn seeded(seed: u64) -> SelectionParams {
+        SelectionParams { seed: Some(seed), ..params() }
+    }
+
+    #[test]
+    fn knapsack_takes_an_exact_match() {
+        let utxos = pool(&[3_000_000, 1_000_000, 500_000]);
+        let target = Amount::from_sat(1_000_000 - INPUT_FEE - BASE_FEE);
+
+        for seed in 0..10 {
+            let selection = strat_handler(&utxos, target, &seeded(seed), UTXOStrategy::Knapsack).unwrap();
+            assert_eq!(amounts(&selection), vec![1_000_000]);
+        }
+    }
+
+    #[test]
+    fn knapsack_change_target_pays_for_the_change_output() {
+        // a 20,000 sat payment pins the change target to CHANGE_LOWER; the first UTXO covers
+        // that but not the 620 sat change output on top, so the second one is picked
+        let target = 20_000;
+        let short = target + BASE_FEE + CHANGE_LOWER as u64 + 100 + INPUT_FEE;
+        let enough = target + BASE_FEE + CHANGE_LOWER as u64 + 10_000 + INPUT_FEE;
+        let utxos = vec![utxo(0, short, 1), utxo(1, enough, 2)];
+
+        for seed in 0..10 {
+            let selection = strat_handler(&utxos, Amount::from_sat(target), &seeded(seed), UTXOStrategy::Knapsack).unwrap();
+            assert_eq!(amounts(&selection), vec![enough]);
+            assert!(selection.change.is_some_and(|change| change.to_sat() as i64 >= CHANGE_LOWER));
+        }
+    }
+
+    #[test]
+    fn knapsack_is_reproducible_with_a_seed() {
+        let utxos = varied_pool();
+        let target = Amount::from_sat(2_345_678);
+
+        let selection = strat_handler(&utxos, target, &seeded(7), UTXOStrategy::Knapsack).unwrap();
+        for _ in 0..5 {
+            let again = strat_handler(&utxos, target, &seeded(7), UTXOStrategy::Knapsack).unwrap();
+            assert_eq!(amounts(&again), amounts(&selection));
+        }
+        let total: u64 = amounts(&selection).iter().sum();
+        assert!(total >= target.to_sat() + selection.fee.to_sat());
+    }
+
+    #[test]
+    fn single_random_draw_is_reproducible_and_leaves_change() {
+        let utxos = varied_pool();
+        let target = Amount::from_sat(1_500_000);
+
+        let selection = strat_handler(&utxos, target, &seeded(42), UTXOStrategy::SingleRandomDraw).unwrap();
+        let again = strat_handler(&utxos, target, &seeded(42), UTXOStrategy::SingleRandomDraw).unwrap();
+        assert_eq!(selection.utxos, again.utxos);
+        assert!(selection.change.is_some_and(|change| change.to_sat() as i64 >= CHANGE_LOWER));
+
+        // some other seed draws in another order
+        assert!((0..20).any(|seed| {
+            strat_handler(&utxos, target, &seeded(seed), UTXOStrategy::SingleRandomDraw).unwrap().utxos != selection.utxos
+        }));
+    }
+
+    #[test]
+    fn address_grouping_spends_whole_addresses() {
+        // address 1 holds 3 x 0.001 BTC, address 2 holds 2 x 0.001 BTC, address 3 holds 0.0005 BTC
+        let utxos = vec![
+            utxo(0, 100_000, 1), utxo(1, 100_000, 2), utxo(2, 100_000, 1),
+            utxo(3, 50_000, 3), utxo(4, 100_000, 1), utxo(5, 100_000, 2),
+        ];
+
+        // no address covers 0.004 BTC alone, so the two largest are spent in full
+        let selection = strat_handler(&utxos, Amount::from_sat(400_000), &params(), UTXOStrategy::AddressGrouping).unwrap();
+        let mut vouts: Vec<u32> = selection.utxos.iter().map(|utxo| utxo.vout).collect();
+        vouts.sort_unstable();
+        assert_eq!(vouts, vec![0, 1, 2, 4, 5]);
+
+        // address 1 covers 0.0025 BTC on its own, and all three of its UTXOs go together
+        let selection = strat_handler(&utxos, Amount::from_sat(250_000), &params(), UTXOStrategy::AddressGrouping).unwrap();
+        assert!(selection.utxos.iter().all(|utxo| utxo.script_pub_key == script(1)));
+        assert_eq!(selection.utxos.len(), 3);
+    }
+
+    fn unordered_tx(inputs: &[(&str, u32)], outputs: &[(u64, &str)]) -> Transaction {
+        Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: inputs.iter().map(|(txid, vout)| TxIn {
+                previous_output: OutPoint::new(Txid::from_str(txid).unwrap(), *vout),
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::MAX,
+                witness: Witness::default(),
+            }).collect(),
+            output: outputs.iter().map(|(sat, script)| TxOut {
+                value: Amount::from_sat(*sat),
+                script_pubkey: ScriptBuf::from_hex(script).unwrap(),
+            }).collect(),
+        }
+    }
+
+    fn inputs(tx: &Transaction) -> Vec<(String, u32)> {
+        tx.input.iter().map(|input| (input.previous_output.txid.to_string(), input.previous_output.vout)).collect()
+    }
+
+    fn outputs(tx: &Transaction) -> Vec<(u64, String)> {
+        tx.output.iter().map(|output| (output.value.to_sat(), output.script_pubkey.to_hex_string())).collect()
+    }
+
+    /// Inputs of BIP69 test vector 1, tx 0a6a357e2f7796444e02638749d9611c008b253fb55f5dc88b739b230ed0c4c3, in sorted order
+    const BIP69_SORTED_INPUTS: [(&str, u32); 17] = [
+        ("0e53ec5dfb2cb8a71fec32dc9a634a35b7e24799295ddd5278217822e0b31f57", 0),
+        ("26aa6e6d8b9e49bb0630aac301db6757c02e3619feb4ee0eea81eb1672947024", 1),
+        ("28e0fdd185542f2c6ea19030b0796051e7772b6026dd5ddccd7a2f93b73e6fc2", 0),
+        ("381de9b9ae1a94d9c17f6a08ef9d341a5ce29e2e60c36a52d333ff6203e58d5d", 1),
+        ("3b8b2f8efceb60ba78ca8bba206a137f14cb5ea4035e761ee204302d46b98de2", 0),
+        ("402b2c02411720bf409eff60d05adad684f135838962823f3614cc657dd7bc0a", 1),
+        ("54ffff182965ed0957dba1239c27164ace5a73c9b62a660c74b7b7f15ff61e7a", 1),
+        ("643e5f4e66373a57251fb173151e838ccd27d279aca882997e005016bb53d5aa", 0),
+        ("6c1d56f31b2de4bfc6aaea28396b333102b1f600da9c6d6149e96ca43f1102b1", 1),
+        ("7a1de137cbafb5c70405455c49c5104ca3057a1f1243e6563bb9245c9c88c191", 0),
+        ("7d037ceb2ee0dc03e82f17be7935d238b35d1deabf953a892a4507bfbeeb3ba4", 1),
+        ("a5e899dddb28776ea9ddac0a502316d53a4a3fca607c72f66c470e0412e34086", 0),
+        ("b4112b8f900a7ca0c8b0e7c4dfad35c6be5f6be46b3458974988e1cdb2fa61b8", 0),
+        ("bafd65e3c7f3f9fdfdc1ddb026131b278c3be1af90a4a6ffa78c4658f9ec0c85", 0),
+        ("de0411a1e97484a2804ff1dbde260ac19de841bebad1880c782941aca883b4e9", 1),
+        ("f0a130a84912d03c1d284974f563c5949ac13f8342b8112edff52971599e6a45", 0),
+        ("f320832a9d2e2452af63154bc687493484a0e7745ebd3aaf9ca19eb80834ad60", 0),
+    ];
+
+    /// Outputs of BIP69 test vector 1, in sorted order
+    const BIP69_SORTED_OUTPUTS: [(u64, &str); 2] = [
+        (400_057_456, "76a9144a5fba237213a062f6f57978f796390bdcf8d01588ac"),
+        (40_000_000_000, "76a9145be32612930b8323add2212a4ec03c1562084f8488ac"),
+    ];
+
+    #[test]
+    fn bip69_vector_1() {
+        let mut unordered = BIP69_SORTED_INPUTS;
+        unordered.reverse();
+        unordered.rotate_left(5);
+        let mut tx = unordered_tx(&unordered, &[BIP69_SORTED_OUTPUTS[1], BIP69_SORTED_OUTPUTS[0]]);
+
+        TxOrdering::Bip69.apply(&mut tx);
+        let expected: Vec<(String, u32)> = BIP69_SORTED_INPUTS.iter().map(|(txid, vout)| (txid.to_string(), *vout)).collect();
+        assert_eq!(inputs(&tx), expected);
+        let expected: Vec<(u64, String)> = BIP69_SORTED_OUTPUTS.iter().map(|(sat, script)| (*sat, script.to_string())).collect();
+        assert_eq!(outputs(&tx), expected);
+    }
+
+    #[test]
+    fn bip69_vector_2() {
+        // tx 28204cad1d7fc1d199e8ef4fa22f182de6258a3eaafe1bbe56ebdcacd3069a5f: one txid, so inputs sort by vout
+        let txid = "35288d269cee1941eaebb2ea85e32b42cdb2b04284a56d8b14dcc3f5c65d6055";
+        let small = "41046a0765b5865641ce08dd39690aade26dfbf5511430ca428a3089261361cef170e3929a68aee3d8d4848b0c5111b0a37b82b86ad559fd2a745b44d8e8d9dfdc0cac";
+        let large = "41044a656f065871a353f216ca26cef8dde2f03e8c16202d2f8ad769f02032cb86a5eb5e56842e92e19141d60a01928f8dd2c875a390f67c1f6c94cfc617c0ea45afac";
+        let mut tx = unordered_tx(&[(txid, 1), (txid, 0)], &[(2_400_000_000, large), (100_000_000, small)]);
+
+        TxOrdering::Bip69.apply(&mut tx);
+        assert_eq!(inputs(&tx), [(txid.to_string(), 0), (txid.to_string(), 1)]);
+        assert_eq!(outputs(&tx), [(100_000_000, small.to_string()), (2_400_000_000, large.to_string())]);
+    }
+
+    #[test]
+    fn bip69_equal_amounts_sort_by_script() {
+        let txid = "0e53ec5dfb2cb8a71fec32dc9a634a35b7e24799295ddd5278217822e0b31f57";
+        let mut tx = unordered_tx(&[(txid, 0)], &[(1_000, "0014ffffffffffffffffffffffffffffffffffffffff"), (1_000, "00140000000000000000000000000000000000000000")]);
+
+        TxOrdering::Bip69.apply(&mut tx);
+        assert_eq!(outputs(&tx)[0].1, "00140000000000000000000000000000000000000000");
+    }
+
+    #[test]
+    fn shuffle_is_reproducible_and_leaves_inputs() {
+        let outputs_in: Vec<(u64, &str)> = (1..=10).map(|i| (i * 1_000, "0014ffffffffffffffffffffffffffffffffffffffff")).collect();
+        let unordered = unordered_tx(&BIP69_SORTED_INPUTS, &outputs_in);
+
+        let shuffled = |seed: u64| {
+            let mut tx = unordered.clone();
+            TxOrdering::Shuffle(seed).apply(&mut tx);
+            tx
+        };
+        assert_eq!(shuffled(7), shuffled(7));
+        assert_eq!(inputs(&shuffled(7)), inputs(&unordered));
+        assert!((0..20).any(|seed| outputs(&shuffled(seed)) != outputs(&shuffled(7))));
+    }
+}